@@ -0,0 +1,149 @@
+use crate::lib::db::{self, Executor};
+use crate::lib::extractors::RequestMeta;
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub org_id: Option<i64>,
+    pub actor: String,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<String>,
+    pub before_state: Option<String>,
+    pub after_state: Option<String>,
+    pub diff: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: String,
+}
+
+impl AuditEvent {
+    pub fn to_json(&self) -> Value {
+        let parse = |s: &Option<String>| {
+            s.as_deref()
+                .and_then(|s| serde_json::from_str::<Value>(s).ok())
+                .unwrap_or(Value::Null)
+        };
+
+        json!({
+            "id": self.id,
+            "org_id": self.org_id,
+            "actor": self.actor,
+            "action": self.action,
+            "target_type": self.target_type,
+            "target_id": self.target_id,
+            "before": parse(&self.before_state),
+            "after": parse(&self.after_state),
+            "diff": parse(&self.diff),
+            "ip": self.ip,
+            "user_agent": self.user_agent,
+            "created_at": self.created_at,
+        })
+    }
+}
+
+pub struct AuditEntry<'a> {
+    pub org_id: Option<i64>,
+    pub actor: &'a str,
+    pub action: &'a str,
+    pub target_type: &'a str,
+    pub target_id: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// Field level changes between two snapshots, as `{ field: { from, to } }`.
+pub fn diff(before: Option<&Value>, after: Option<&Value>) -> Value {
+    let empty = Map::new();
+    let before_map = before.and_then(|v| v.as_object()).unwrap_or(&empty);
+    let after_map = after.and_then(|v| v.as_object()).unwrap_or(&empty);
+
+    let mut changes = Map::new();
+
+    for (key, from) in before_map {
+        let to = after_map.get(key).unwrap_or(&Value::Null);
+        if from != to {
+            changes.insert(key.clone(), json!({ "from": from, "to": to }));
+        }
+    }
+
+    for (key, to) in after_map {
+        if !before_map.contains_key(key) {
+            changes.insert(key.clone(), json!({ "from": Value::Null, "to": to }));
+        }
+    }
+
+    Value::Object(changes)
+}
+
+/// Pass the transaction the change was made in, so the event is only kept
+/// when the change is.
+pub async fn record<'c>(
+    executor: impl Into<Executor<'c>>,
+    meta: &RequestMeta,
+    entry: AuditEntry<'_>,
+) -> Result<(), sqlx::Error> {
+    let changes = diff(entry.before.as_ref(), entry.after.as_ref());
+
+    db::query(
+        "INSERT INTO audit_events (org_id, actor, action, target_type, target_id, before_state, after_state, diff, ip, user_agent) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(entry.org_id)
+    .bind(entry.actor)
+    .bind(entry.action)
+    .bind(entry.target_type)
+    .bind(entry.target_id)
+    .bind(entry.before.map(|v| v.to_string()))
+    .bind(entry.after.map(|v| v.to_string()))
+    .bind(changes.to_string())
+    .bind(&meta.ip)
+    .bind(&meta.user_agent)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn to_csv(events: &[AuditEvent]) -> String {
+    let mut out = String::from(
+        "id,org_id,actor,action,target_type,target_id,before,after,diff,ip,user_agent,created_at\n",
+    );
+
+    for e in events {
+        let row = [
+            e.id.to_string(),
+            e.org_id.map(|id| id.to_string()).unwrap_or_default(),
+            e.actor.clone(),
+            e.action.clone(),
+            e.target_type.clone(),
+            e.target_id.clone().unwrap_or_default(),
+            e.before_state.clone().unwrap_or_default(),
+            e.after_state.clone().unwrap_or_default(),
+            e.diff.clone().unwrap_or_default(),
+            e.ip.clone().unwrap_or_default(),
+            e.user_agent.clone().unwrap_or_default(),
+            e.created_at.clone(),
+        ];
+
+        out.push_str(
+            &row.iter()
+                .map(|f| csv_field(f))
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+        out.push('\n');
+    }
+
+    out
+}
@@ -0,0 +1,254 @@
+use crate::lib::db::usage::Since;
+use crate::lib::db::{self, Db};
+use crate::lib::{error::ApiError, fetch, webhooks};
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::FromRow;
+use teloxide::prelude::*;
+use tracing::Instrument;
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Budget {
+    pub id: i64,
+    pub org_id: i64,
+    pub project_id: Option<i64>,
+    pub period: String,
+    pub hard_cap: Option<i64>,
+    pub soft_threshold: Option<i64>,
+    pub alert_channel: Option<String>,
+    pub alert_target: Option<String>,
+    pub last_alert_period: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Budget {
+    /// Identifies the current budget period, `YYYY-MM` for monthly budgets.
+    pub fn current_period(&self) -> String {
+        match self.period.as_str() {
+            "monthly" => chrono::Utc::now().format("%Y-%m").to_string(),
+            _ => "total".to_string(),
+        }
+    }
+
+    fn scope_label(&self) -> String {
+        match self.project_id {
+            Some(project_id) => format!("project {}", project_id),
+            None => format!("organization {}", self.org_id),
+        }
+    }
+}
+
+pub async fn budgets_for(
+    db: &Db,
+    org_id: i64,
+    project_id: i64,
+) -> Result<Vec<Budget>, sqlx::Error> {
+    db::query_as::<Budget>(
+        "SELECT * FROM budgets WHERE org_id = ? AND (project_id IS NULL OR project_id = ?)",
+    )
+    .bind(org_id)
+    .bind(project_id)
+    .fetch_all(db)
+    .await
+}
+
+/// Amount spent within the budget's scope and current period.
+pub async fn spent(db: &Db, budget: &Budget) -> Result<i64, sqlx::Error> {
+    match (budget.project_id, budget.period.as_str()) {
+        (None, "total") => {
+            db::query_scalar("SELECT expenditure FROM organizations WHERE id = ?")
+                .bind(budget.org_id)
+                .fetch_one(db)
+                .await
+        }
+        (None, _) => db::usage::org_spent(db, budget.org_id, Since::StartOfMonth).await,
+        (Some(project_id), "total") => db::usage::project_spent(db, project_id, Since::Ever).await,
+        (Some(project_id), _) => db::usage::project_spent(db, project_id, Since::StartOfMonth).await,
+    }
+}
+
+pub async fn usage(db: &Db, budget: &Budget) -> Result<Value, sqlx::Error> {
+    let spent = spent(db, budget).await?;
+
+    Ok(json!({
+        "budget": budget,
+        "current_period": budget.current_period(),
+        "spent": spent,
+        "remaining": budget.hard_cap.map(|cap| (cap - spent).max(0)),
+        "soft_threshold_reached": budget.soft_threshold.is_some_and(|t| spent >= t),
+        "hard_cap_reached": budget.hard_cap.is_some_and(|cap| spent >= cap),
+    }))
+}
+
+/// Rejects a call whose cost would take the org or project past a hard cap.
+pub async fn enforce(
+    db: &Db,
+    org_id: i64,
+    project_id: i64,
+    projected_cost: u64,
+) -> Result<(), ApiError> {
+    for budget in budgets_for(db, org_id, project_id).await? {
+        let Some(hard_cap) = budget.hard_cap else {
+            continue;
+        };
+
+        let spent = spent(db, &budget).await?;
+        if spent.saturating_add(projected_cost as i64) > hard_cap {
+            return Err(ApiError::PaymentRequired(format!(
+                "{} budget of {} for {} exhausted ({} spent, call costs {})",
+                if budget.period == "monthly" {
+                    "Monthly"
+                } else {
+                    "Overall"
+                },
+                hard_cap,
+                budget.scope_label(),
+                spent,
+                projected_cost
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+async fn send_alert(db: &Db, budget: &Budget, payload: &Value) -> Result<(), ApiError> {
+    let (Some(channel), Some(target)) = (&budget.alert_channel, &budget.alert_target) else {
+        return Ok(());
+    };
+
+    let text = format!(
+        "Haithe budget alert: {} spent {} of its {} budget (soft threshold {}).",
+        budget.scope_label(),
+        payload["spent"],
+        budget.period,
+        budget.soft_threshold.unwrap_or_default()
+    );
+
+    match channel.as_str() {
+        "webhook" => {
+            let fetcher = fetch::client();
+            fetcher.send(fetcher.post(target)?.json(payload), &[]).await?;
+        }
+        "discord" => {
+            let fetcher = fetch::client();
+            fetcher
+                .send(fetcher.post(target)?.json(&json!({ "content": text })), &[])
+                .await?;
+        }
+        "telegram" => {
+            // Alerts go out through the project's own bot, or any bot configured in the org
+            let token: Option<String> = db::query_scalar(
+                "SELECT teloxide_token FROM projects
+                 WHERE org_id = ? AND teloxide_token IS NOT NULL AND teloxide_token != ''
+                 ORDER BY (id = ?) DESC LIMIT 1",
+            )
+            .bind(budget.org_id)
+            .bind(budget.project_id.unwrap_or(0))
+            .fetch_optional(db)
+            .await?;
+
+            let Some(token) = token else {
+                return Err(ApiError::BadRequest(
+                    "No Telegram bot configured to deliver budget alerts".into(),
+                ));
+            };
+
+            let chat_id: i64 = target
+                .parse()
+                .map_err(|_| ApiError::BadRequest("Invalid Telegram chat id".into()))?;
+
+            Bot::new(token)
+                .send_message(ChatId(chat_id), text)
+                .await
+                .map_err(|e| ApiError::Internal(format!("Telegram alert failed: {}", e)))?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Checks the soft thresholds in the background, so alert delivery never holds
+/// up the completion that crossed them.
+pub fn spawn_threshold_check(db: Db, org_id: i64, project_id: i64) {
+    tokio::spawn(
+        async move {
+            if let Err(e) = check_thresholds(&db, org_id, project_id).await {
+                tracing::error!(org_id, project_id, error = %e, "Failed to check budget thresholds");
+            }
+        }
+        .in_current_span(),
+    );
+}
+
+/// Sends an alert, once per period, for every budget whose soft threshold has been crossed.
+/// An alert that fails to send is tried again on the next check.
+async fn check_thresholds(db: &Db, org_id: i64, project_id: i64) -> Result<(), ApiError> {
+    for budget in budgets_for(db, org_id, project_id).await? {
+        let Some(soft_threshold) = budget.soft_threshold else {
+            continue;
+        };
+
+        let period = budget.current_period();
+        if budget.last_alert_period.as_deref() == Some(period.as_str()) {
+            continue;
+        }
+
+        let spent = spent(db, &budget).await?;
+        if spent < soft_threshold {
+            continue;
+        }
+
+        let payload = json!({
+            "event": "budget.threshold_crossed",
+            "budget_id": budget.id,
+            "org_id": budget.org_id,
+            "project_id": budget.project_id,
+            "period": budget.period,
+            "current_period": period,
+            "soft_threshold": soft_threshold,
+            "hard_cap": budget.hard_cap,
+            "spent": spent,
+        });
+
+        // Mark first so concurrent calls don't both alert
+        let claimed = db::query(
+            "UPDATE budgets SET last_alert_period = ? WHERE id = ? AND (last_alert_period IS NULL OR last_alert_period != ?)",
+        )
+        .bind(&period)
+        .bind(budget.id)
+        .bind(&period)
+        .execute(db)
+        .await?;
+
+        if claimed.rows_affected() == 0 {
+            continue;
+        }
+
+        if let Err(e) = send_alert(db, &budget, &payload).await {
+            tracing::error!(budget_id = budget.id, error = %e, "Failed to send budget alert");
+            // Give the claim back so the next call that spends retries the alert
+            db::query("UPDATE budgets SET last_alert_period = ? WHERE id = ? AND last_alert_period = ?")
+                .bind(&budget.last_alert_period)
+                .bind(budget.id)
+                .bind(&period)
+                .execute(db)
+                .await?;
+            continue;
+        }
+
+        match budget.project_id {
+            Some(project_id) => {
+                webhooks::emit(db, project_id, "budget.threshold_crossed", payload).await
+            }
+            None => {
+                webhooks::emit_for_org(db, budget.org_id, "budget.threshold_crossed", payload)
+                    .await
+            }
+        }
+    }
+
+    Ok(())
+}
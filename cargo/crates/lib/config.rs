@@ -0,0 +1,716 @@
+use crate::lib::db::Backend;
+use crate::lib::models;
+use anyhow::{Context, Result, anyhow, bail};
+use ethers::signers::LocalWallet;
+use serde::{Deserialize, Serialize, Serializer};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// The loaded configuration. Panics if called before [`init`].
+pub fn get() -> &'static Config {
+    CONFIG.get().expect("Configuration has not been loaded")
+}
+
+pub fn init(config: Config) {
+    if CONFIG.set(config).is_err() {
+        panic!("Configuration loaded twice");
+    }
+}
+
+/// A value that never appears in logs or API responses.
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("[redacted]")
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str("[redacted]")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Testnet,
+    Mainnet,
+}
+
+impl Network {
+    fn parse(value: &str) -> Result<Self> {
+        match value {
+            "testnet" => Ok(Network::Testnet),
+            "mainnet" => Ok(Network::Mainnet),
+            other => bail!(
+                "unknown network '{}', expected 'testnet' or 'mainnet'",
+                other
+            ),
+        }
+    }
+
+    /// Public endpoints used when none are configured. Mainnet has no defaults.
+    fn default_provider_url(self) -> Option<&'static str> {
+        match self {
+            Network::Testnet => Some("https://hyperion-testnet.metisdevops.link"),
+            Network::Mainnet => None,
+        }
+    }
+
+    fn default_explorer_api_url(self) -> Option<&'static str> {
+        match self {
+            Network::Testnet => Some("https://hyperion-testnet-explorer-api.metisdevops.link"),
+            Network::Mainnet => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Json,
+    Text,
+}
+
+impl LogFormat {
+    fn parse(value: &str) -> Result<Self> {
+        match value {
+            "json" => Ok(LogFormat::Json),
+            "text" => Ok(LogFormat::Text),
+            other => bail!("unknown log format '{}', expected 'json' or 'text'", other),
+        }
+    }
+}
+
+/// How knowledge chunks are embedded for retrieval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbedderKind {
+    /// Feature hashing, offline and deterministic.
+    Local,
+    /// OpenAI embeddings, needs `OPENAI_API_KEY`.
+    Openai,
+}
+
+impl EmbedderKind {
+    fn parse(value: &str) -> Result<Self> {
+        match value {
+            "local" => Ok(EmbedderKind::Local),
+            "openai" => Ok(EmbedderKind::Openai),
+            other => bail!("unknown embedder '{}', expected 'local' or 'openai'", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProviderKeys {
+    pub openai_api_key: Option<Secret>,
+    pub gemini_api_key: Option<Secret>,
+    pub deepseek_api_key: Option<Secret>,
+    pub moonshot_api_key: Option<Secret>,
+    pub groq_api_key: Option<Secret>,
+}
+
+impl ProviderKeys {
+    /// API key for a model provider as named in `models.rs`.
+    pub fn for_provider(&self, provider: &str) -> Option<&str> {
+        let key = match provider {
+            "OpenAI" => &self.openai_api_key,
+            "Google" => &self.gemini_api_key,
+            "DeepSeek" => &self.deepseek_api_key,
+            "Moonshot" => &self.moonshot_api_key,
+            "Haithe" => &self.groq_api_key,
+            _ => return None,
+        };
+        key.as_ref().map(Secret::expose)
+    }
+
+    /// Environment variable holding a provider's API key.
+    pub fn env_var(provider: &str) -> Option<&'static str> {
+        match provider {
+            "OpenAI" => Some("OPENAI_API_KEY"),
+            "Google" => Some("GEMINI_API_KEY"),
+            "DeepSeek" => Some("DEEPSEEK_API_KEY"),
+            "Moonshot" => Some("MOONSHOT_API_KEY"),
+            "Haithe" => Some("GROQ_API_KEY"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Config {
+    pub database_url: Secret,
+    pub bind_address: IpAddr,
+    pub port: u16,
+    /// Empty allows any origin.
+    pub cors_origins: Vec<String>,
+    /// Peers whose `Forwarded`/`X-Forwarded-For` headers are trusted for the
+    /// client address. Empty ignores those headers.
+    pub trusted_proxies: Vec<IpAddr>,
+    pub network: Network,
+    /// `tracing` filter directives, e.g. `info` or `info,main::lib::llm=debug`.
+    pub log_level: String,
+    pub log_format: LogFormat,
+    pub blockchain_provider_url: String,
+    pub explorer_api_url: String,
+    pub jwt_secret: Secret,
+    pub tee_secret: Secret,
+    pub server_pvt_key: Option<Secret>,
+    pub mock_tee_pvt_key: Option<Secret>,
+    /// Wallets allowed to read server-wide settings, lowercase.
+    pub admin_wallets: Vec<String>,
+    /// Bearer token required by `/metrics`; open when unset.
+    pub metrics_token: Option<Secret>,
+    /// Seconds cached product knowledge is served before it is revalidated.
+    pub knowledge_cache_ttl_secs: u64,
+    /// Where cached knowledge is kept, encrypted, across restarts. Memory only when unset.
+    pub knowledge_cache_dir: Option<PathBuf>,
+    pub embedder: EmbedderKind,
+    /// Knowledge chunks added to each completion.
+    pub rag_top_k: usize,
+    /// Hosts tool products may call, lowercase. `*.example.com` also matches
+    /// subdomains. Tool products are unusable while empty.
+    pub tool_allowed_hosts: Vec<String>,
+    /// Upper bound on a single tool call; products may ask for less.
+    pub tool_timeout_secs: u64,
+    /// Calls a tool product may receive per completion.
+    pub tool_max_calls: u64,
+    /// Hosts outbound fetches may reach, in the same format as
+    /// `tool_allowed_hosts`. Empty allows any public host.
+    pub fetch_allowed_hosts: Vec<String>,
+    pub fetch_denied_hosts: Vec<String>,
+    pub fetch_connect_timeout_secs: u64,
+    /// Longest wait for the next bytes of a response.
+    pub fetch_read_timeout_secs: u64,
+    pub fetch_timeout_secs: u64,
+    pub fetch_max_bytes: u64,
+    pub fetch_max_redirects: usize,
+    /// Gateways `ipfs://` URIs are fetched through, tried in order, without a
+    /// trailing slash.
+    pub ipfs_gateways: Vec<String>,
+    /// Gateways `ar://` URIs are fetched through, tried in order.
+    pub arweave_gateways: Vec<String>,
+    pub providers: ProviderKeys,
+}
+
+impl Config {
+    pub fn is_admin(&self, wallet_address: &str) -> bool {
+        self.admin_wallets
+            .iter()
+            .any(|admin| admin.eq_ignore_ascii_case(wallet_address))
+    }
+}
+
+/// Settings accepted in the config file, all optional.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    database_url: Option<Secret>,
+    bind_address: Option<String>,
+    port: Option<u16>,
+    cors_origins: Option<Vec<String>>,
+    trusted_proxies: Option<Vec<String>>,
+    network: Option<Network>,
+    log_level: Option<String>,
+    log_format: Option<LogFormat>,
+    blockchain_provider_url: Option<String>,
+    explorer_api_url: Option<String>,
+    jwt_secret: Option<Secret>,
+    tee_secret: Option<Secret>,
+    server_pvt_key: Option<Secret>,
+    mock_tee_pvt_key: Option<Secret>,
+    admin_wallets: Option<Vec<String>>,
+    metrics_token: Option<Secret>,
+    knowledge_cache_ttl_secs: Option<u64>,
+    knowledge_cache_dir: Option<PathBuf>,
+    embedder: Option<EmbedderKind>,
+    rag_top_k: Option<usize>,
+    tool_allowed_hosts: Option<Vec<String>>,
+    tool_timeout_secs: Option<u64>,
+    tool_max_calls: Option<u64>,
+    fetch_allowed_hosts: Option<Vec<String>>,
+    fetch_denied_hosts: Option<Vec<String>>,
+    fetch_connect_timeout_secs: Option<u64>,
+    fetch_read_timeout_secs: Option<u64>,
+    fetch_timeout_secs: Option<u64>,
+    fetch_max_bytes: Option<u64>,
+    fetch_max_redirects: Option<usize>,
+    ipfs_gateways: Option<Vec<String>>,
+    arweave_gateways: Option<Vec<String>>,
+    #[serde(default)]
+    providers: ProviderKeys,
+}
+
+/// Command line flags, which take precedence over the environment and the config file.
+#[derive(Default)]
+pub struct Cli {
+    pub config_path: Option<PathBuf>,
+    pub bind_address: Option<String>,
+    pub port: Option<String>,
+    pub cors_origins: Vec<String>,
+    pub network: Option<String>,
+    pub log_level: Option<String>,
+    /// Remaining positional arguments, e.g. `migrate status`.
+    pub command: Vec<String>,
+}
+
+impl Cli {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut cli = Cli::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+
+            let mut value = |name: &str| {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| anyhow!("{} expects a value", name))
+            };
+
+            match flag.as_str() {
+                "--config" => cli.config_path = Some(PathBuf::from(value("--config")?)),
+                "--bind" => cli.bind_address = Some(value("--bind")?),
+                "--port" => cli.port = Some(value("--port")?),
+                "--cors-origin" => cli.cors_origins.push(value("--cors-origin")?),
+                "--network" => cli.network = Some(value("--network")?),
+                "--log-level" => cli.log_level = Some(value("--log-level")?),
+                other if other.starts_with("--") => bail!("unknown flag '{}'", other),
+                _ => cli.command.push(arg),
+            }
+        }
+
+        Ok(cli)
+    }
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+}
+
+fn env_number<T: std::str::FromStr>(name: &str, errors: &mut Vec<String>) -> Option<T> {
+    let value = env(name)?;
+    match value.parse() {
+        Ok(number) => Some(number),
+        Err(_) => {
+            errors.push(format!("{}: '{}' is not a number", name, value));
+            None
+        }
+    }
+}
+
+/// Lowercased host patterns, `example.com` or `*.example.com`.
+fn host_list(name: &str, hosts: Vec<String>, errors: &mut Vec<String>) -> Vec<String> {
+    let hosts: Vec<String> = hosts.into_iter().map(|host| host.to_lowercase()).collect();
+    for host in &hosts {
+        let bare = host.strip_prefix("*.").unwrap_or(host);
+        if bare.is_empty() || url::Host::parse(bare).is_err() {
+            errors.push(format!("{}: '{}' is not a host name", name, host));
+        }
+    }
+    hosts
+}
+
+/// Gateway base URLs, `defaults` when unset.
+fn gateway_list(
+    name: &str,
+    gateways: Option<Vec<String>>,
+    defaults: &[&str],
+    errors: &mut Vec<String>,
+) -> Vec<String> {
+    let gateways: Vec<String> = gateways
+        .unwrap_or_else(|| defaults.iter().map(|gateway| gateway.to_string()).collect())
+        .into_iter()
+        .map(|gateway| gateway.trim_end_matches('/').to_string())
+        .collect();
+    for gateway in &gateways {
+        let valid = url::Url::parse(gateway)
+            .map(|url| matches!(url.scheme(), "http" | "https"))
+            .unwrap_or(false);
+        if !valid {
+            errors.push(format!("{}: '{}' is not an http(s) URL", name, gateway));
+        }
+    }
+    gateways
+}
+
+fn env_secret(name: &str) -> Option<Secret> {
+    env(name).map(Secret)
+}
+
+fn env_list(name: &str) -> Option<Vec<String>> {
+    env(name).map(|value| {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+impl Config {
+    /// Builds the configuration from defaults, then the config file, then the
+    /// environment, then command line flags. Every problem is reported at once.
+    pub fn load(cli: &Cli) -> Result<Config> {
+        let path = cli
+            .config_path
+            .clone()
+            .or_else(|| env("HAITHE_CONFIG").map(PathBuf::from));
+        let file = match &path {
+            Some(path) => {
+                let content = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read config file '{}'", path.display()))?;
+                toml::from_str::<FileConfig>(&content)
+                    .with_context(|| format!("Invalid config file '{}'", path.display()))?
+            }
+            None => FileConfig::default(),
+        };
+
+        let mut errors = Vec::new();
+
+        let network = match cli.network.clone().or_else(|| env("NETWORK")) {
+            Some(value) => Network::parse(&value).unwrap_or_else(|e| {
+                errors.push(format!("NETWORK: {}", e));
+                Network::Testnet
+            }),
+            None => file.network.unwrap_or(Network::Testnet),
+        };
+
+        let log_level = cli
+            .log_level
+            .clone()
+            .or_else(|| env("LOG_LEVEL"))
+            .or_else(|| env("RUST_LOG"))
+            .or(file.log_level)
+            .unwrap_or_else(|| "info".to_string());
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&log_level) {
+            errors.push(format!("LOG_LEVEL: {}", e));
+        }
+
+        let log_format = match env("LOG_FORMAT") {
+            Some(value) => LogFormat::parse(&value).unwrap_or_else(|e| {
+                errors.push(format!("LOG_FORMAT: {}", e));
+                LogFormat::Json
+            }),
+            None => file.log_format.unwrap_or(LogFormat::Json),
+        };
+
+        let database_url = env_secret("DATABASE_URL").or(file.database_url);
+        match &database_url {
+            Some(url) => {
+                if let Err(e) = Backend::from_url(url.expose()) {
+                    errors.push(format!("DATABASE_URL: {}", e));
+                }
+            }
+            None => errors.push("DATABASE_URL is required".to_string()),
+        }
+
+        let bind_address = cli
+            .bind_address
+            .clone()
+            .or_else(|| env("BIND_ADDRESS"))
+            .or(file.bind_address)
+            .unwrap_or_else(|| "127.0.0.1".to_string());
+        let bind_address = bind_address.parse::<IpAddr>().unwrap_or_else(|_| {
+            errors.push(format!(
+                "BIND_ADDRESS: '{}' is not an IP address",
+                bind_address
+            ));
+            IpAddr::from([127, 0, 0, 1])
+        });
+
+        let port = match cli.port.clone().or_else(|| env("PORT")) {
+            Some(value) => value.parse::<u16>().unwrap_or_else(|_| {
+                errors.push(format!("PORT: '{}' is not a valid port", value));
+                0
+            }),
+            None => file.port.unwrap_or(8080),
+        };
+
+        let cors_origins = if cli.cors_origins.is_empty() {
+            env_list("CORS_ORIGINS")
+                .or(file.cors_origins)
+                .unwrap_or_default()
+        } else {
+            cli.cors_origins.clone()
+        };
+        for origin in &cors_origins {
+            let valid = url::Url::parse(origin)
+                .map(|url| matches!(url.scheme(), "http" | "https") && url.path() == "/")
+                .unwrap_or(false);
+            if !valid {
+                errors.push(format!(
+                    "CORS_ORIGINS: '{}' must be an http(s) origin such as https://app.haithe.ai",
+                    origin
+                ));
+            }
+        }
+
+        let trusted_proxies = env_list("TRUSTED_PROXIES")
+            .or(file.trusted_proxies)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|proxy| match proxy.parse::<IpAddr>() {
+                Ok(ip) => Some(ip),
+                Err(_) => {
+                    errors.push(format!(
+                        "TRUSTED_PROXIES: '{}' is not an IP address",
+                        proxy
+                    ));
+                    None
+                }
+            })
+            .collect();
+
+        let blockchain_provider_url = env("BLOCKCHAIN_PROVIDER_URL")
+            .or(file.blockchain_provider_url)
+            .or_else(|| network.default_provider_url().map(str::to_string));
+        let explorer_api_url = env("EXPLORER_API_URL")
+            .or(file.explorer_api_url)
+            .or_else(|| network.default_explorer_api_url().map(str::to_string));
+        for (name, value) in [
+            ("BLOCKCHAIN_PROVIDER_URL", &blockchain_provider_url),
+            ("EXPLORER_API_URL", &explorer_api_url),
+        ] {
+            match value {
+                Some(value) if url::Url::parse(value).is_err() => {
+                    errors.push(format!("{}: '{}' is not a URL", name, value))
+                }
+                Some(_) => {}
+                None => errors.push(format!("{} is required on {:?}", name, network)),
+            }
+        }
+
+        let jwt_secret = env_secret("JWT_SECRET").or(file.jwt_secret);
+        if jwt_secret.is_none() {
+            errors.push("JWT_SECRET is required".to_string());
+        }
+
+        let tee_secret = env_secret("TEE_SECRET").or(file.tee_secret);
+        if tee_secret.is_none() {
+            errors.push("TEE_SECRET is required".to_string());
+        }
+
+        let server_pvt_key = env_secret("SERVER_PVT_KEY").or(file.server_pvt_key);
+        let mock_tee_pvt_key = env_secret("MOCK_TEE_PVT_KEY").or(file.mock_tee_pvt_key);
+        for (name, key) in [
+            ("SERVER_PVT_KEY", &server_pvt_key),
+            ("MOCK_TEE_PVT_KEY", &mock_tee_pvt_key),
+        ] {
+            if let Some(key) = key {
+                if key.expose().parse::<LocalWallet>().is_err() {
+                    errors.push(format!("{}: not a valid private key", name));
+                }
+            }
+        }
+
+        let admin_wallets: Vec<String> = env_list("ADMIN_WALLETS")
+            .or(file.admin_wallets)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|wallet| wallet.to_lowercase())
+            .collect();
+        for wallet in &admin_wallets {
+            let valid = wallet.len() == 42
+                && wallet.starts_with("0x")
+                && wallet[2..].chars().all(|c| c.is_ascii_hexdigit());
+            if !valid {
+                errors.push(format!(
+                    "ADMIN_WALLETS: '{}' is not a wallet address",
+                    wallet
+                ));
+            }
+        }
+
+        let metrics_token = env_secret("METRICS_TOKEN").or(file.metrics_token);
+
+        let knowledge_cache_ttl_secs = env_number("KNOWLEDGE_CACHE_TTL_SECS", &mut errors)
+            .or(file.knowledge_cache_ttl_secs)
+            .unwrap_or(300);
+        let knowledge_cache_dir = env("KNOWLEDGE_CACHE_DIR")
+            .map(PathBuf::from)
+            .or(file.knowledge_cache_dir);
+
+        let providers = ProviderKeys {
+            openai_api_key: env_secret("OPENAI_API_KEY").or(file.providers.openai_api_key),
+            gemini_api_key: env_secret("GEMINI_API_KEY").or(file.providers.gemini_api_key),
+            deepseek_api_key: env_secret("DEEPSEEK_API_KEY").or(file.providers.deepseek_api_key),
+            moonshot_api_key: env_secret("MOONSHOT_API_KEY").or(file.providers.moonshot_api_key),
+            groq_api_key: env_secret("GROQ_API_KEY").or(file.providers.groq_api_key),
+        };
+
+        let embedder = match env("EMBEDDER") {
+            Some(value) => EmbedderKind::parse(&value).unwrap_or_else(|e| {
+                errors.push(format!("EMBEDDER: {}", e));
+                EmbedderKind::Local
+            }),
+            None => file.embedder.unwrap_or(EmbedderKind::Local),
+        };
+        if embedder == EmbedderKind::Openai && providers.openai_api_key.is_none() {
+            errors.push("EMBEDDER: 'openai' requires OPENAI_API_KEY".to_string());
+        }
+
+        // Every active model must be callable, grouped by the key it is missing
+        let mut unkeyed: Vec<(String, Vec<String>)> = Vec::new();
+        for model in models::get_models().into_iter().filter(|m| m.is_active) {
+            if providers.for_provider(&model.provider).is_some() {
+                continue;
+            }
+            match unkeyed.iter_mut().find(|(provider, _)| *provider == model.provider) {
+                Some((_, names)) => names.push(model.name),
+                None => unkeyed.push((model.provider, vec![model.name])),
+            }
+        }
+        for (provider, names) in unkeyed {
+            match ProviderKeys::env_var(&provider) {
+                Some(var) => errors.push(format!(
+                    "{} is required for the active {} models ({})",
+                    var,
+                    provider,
+                    names.join(", ")
+                )),
+                None => errors.push(format!(
+                    "Active models ({}) use unsupported provider '{}'",
+                    names.join(", "),
+                    provider
+                )),
+            }
+        }
+
+        let rag_top_k = env_number("RAG_TOP_K", &mut errors)
+            .or(file.rag_top_k)
+            .unwrap_or(4);
+
+        let tool_allowed_hosts = host_list(
+            "TOOL_ALLOWED_HOSTS",
+            env_list("TOOL_ALLOWED_HOSTS")
+                .or(file.tool_allowed_hosts)
+                .unwrap_or_default(),
+            &mut errors,
+        );
+
+        let tool_timeout_secs = env_number("TOOL_TIMEOUT_SECS", &mut errors)
+            .or(file.tool_timeout_secs)
+            .unwrap_or(10);
+        let tool_max_calls = env_number("TOOL_MAX_CALLS", &mut errors)
+            .or(file.tool_max_calls)
+            .unwrap_or(5);
+
+        let fetch_allowed_hosts = host_list(
+            "FETCH_ALLOWED_HOSTS",
+            env_list("FETCH_ALLOWED_HOSTS")
+                .or(file.fetch_allowed_hosts)
+                .unwrap_or_default(),
+            &mut errors,
+        );
+        let fetch_denied_hosts = host_list(
+            "FETCH_DENIED_HOSTS",
+            env_list("FETCH_DENIED_HOSTS")
+                .or(file.fetch_denied_hosts)
+                .unwrap_or_default(),
+            &mut errors,
+        );
+        let fetch_connect_timeout_secs = env_number("FETCH_CONNECT_TIMEOUT_SECS", &mut errors)
+            .or(file.fetch_connect_timeout_secs)
+            .unwrap_or(5);
+        let fetch_read_timeout_secs = env_number("FETCH_READ_TIMEOUT_SECS", &mut errors)
+            .or(file.fetch_read_timeout_secs)
+            .unwrap_or(15);
+        let fetch_timeout_secs = env_number("FETCH_TIMEOUT_SECS", &mut errors)
+            .or(file.fetch_timeout_secs)
+            .unwrap_or(60);
+        let fetch_max_bytes = env_number("FETCH_MAX_BYTES", &mut errors)
+            .or(file.fetch_max_bytes)
+            .unwrap_or(20 * 1024 * 1024);
+        let fetch_max_redirects = env_number("FETCH_MAX_REDIRECTS", &mut errors)
+            .or(file.fetch_max_redirects)
+            .unwrap_or(5);
+        let ipfs_gateways = gateway_list(
+            "IPFS_GATEWAYS",
+            env_list("IPFS_GATEWAYS").or(file.ipfs_gateways),
+            &["https://ipfs.io", "https://dweb.link"],
+            &mut errors,
+        );
+        let arweave_gateways = gateway_list(
+            "ARWEAVE_GATEWAYS",
+            env_list("ARWEAVE_GATEWAYS").or(file.arweave_gateways),
+            &["https://arweave.net"],
+            &mut errors,
+        );
+
+        for (name, secs) in [
+            ("FETCH_CONNECT_TIMEOUT_SECS", fetch_connect_timeout_secs),
+            ("FETCH_READ_TIMEOUT_SECS", fetch_read_timeout_secs),
+            ("FETCH_TIMEOUT_SECS", fetch_timeout_secs),
+            ("TOOL_TIMEOUT_SECS", tool_timeout_secs),
+        ] {
+            if secs == 0 {
+                errors.push(format!("{}: must be at least 1 second", name));
+            }
+        }
+
+        if !errors.is_empty() {
+            bail!("\n  - {}", errors.join("\n  - "));
+        }
+
+        Ok(Config {
+            database_url: database_url.unwrap(),
+            bind_address,
+            port,
+            cors_origins,
+            trusted_proxies,
+            network,
+            log_level,
+            log_format,
+            blockchain_provider_url: blockchain_provider_url.unwrap(),
+            explorer_api_url: explorer_api_url.unwrap(),
+            jwt_secret: jwt_secret.unwrap(),
+            tee_secret: tee_secret.unwrap(),
+            server_pvt_key,
+            mock_tee_pvt_key,
+            admin_wallets,
+            metrics_token,
+            knowledge_cache_ttl_secs,
+            knowledge_cache_dir,
+            embedder,
+            rag_top_k,
+            tool_allowed_hosts,
+            tool_timeout_secs,
+            tool_max_calls,
+            fetch_allowed_hosts,
+            fetch_denied_hosts,
+            fetch_connect_timeout_secs,
+            fetch_read_timeout_secs,
+            fetch_timeout_secs,
+            fetch_max_bytes,
+            fetch_max_redirects,
+            ipfs_gateways,
+            arweave_gateways,
+            providers,
+        })
+    }
+}
@@ -0,0 +1,406 @@
+use crate::lib::config;
+use crate::lib::error::ApiError;
+use crate::lib::fetch::{self, FetchError};
+use crate::lib::knowledge::ensure_protocol;
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use sha2::{Digest, Sha256};
+
+const CODEC_RAW: u64 = 0x55;
+const CODEC_DAG_PB: u64 = 0x70;
+const HASH_SHA2_256: u64 = 0x12;
+
+/// UnixFS node types.
+const UNIXFS_RAW: u64 = 0;
+const UNIXFS_DIRECTORY: u64 = 1;
+const UNIXFS_FILE: u64 = 2;
+
+/// Most blocks fetched for one `ipfs://` URI, and how deeply its directories and
+/// file DAG may nest, so a crafted DAG of tiny or empty blocks is cut off early.
+const IPFS_MAX_BLOCKS: usize = 4096;
+const IPFS_MAX_DEPTH: usize = 32;
+
+/// Where a product's payload lives. Everything but HTTP(S) is immutable, so its
+/// content never has to be fetched twice.
+#[derive(Debug, Clone)]
+pub enum ContentUri {
+    Http(String),
+    /// `ipfs://<cid>[/path]`, every block checked against its CID.
+    Ipfs {
+        cid: Cid,
+        path: Vec<String>,
+    },
+    /// `ar://<transaction id>[/path]`.
+    Arweave {
+        id: String,
+        path: String,
+    },
+    /// `data:[<media type>][;base64],<data>`.
+    Data(Vec<u8>),
+}
+
+/// A content identifier hashed with sha2-256, the only hash gateways commonly serve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cid {
+    codec: u64,
+    digest: Vec<u8>,
+    text: String,
+}
+
+fn invalid(uri: &str, reason: impl std::fmt::Display) -> ApiError {
+    ApiError::BadRequest(format!("Invalid content URI '{}': {}", uri, reason))
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *buf.get(*pos).ok_or("truncated varint")?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("varint too long".to_string())
+}
+
+impl Cid {
+    /// Parses a CIDv0 (`Qm…`) or a CIDv1 in base32, base58btc or base16.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        if text.len() == 46 && text.starts_with("Qm") {
+            let bytes = bs58::decode(text)
+                .into_vec()
+                .map_err(|e| format!("bad base58: {}", e))?;
+            return Cid::from_multihash(CODEC_DAG_PB, &bytes, text);
+        }
+
+        let mut chars = text.chars();
+        let bytes = match (chars.next(), chars.as_str()) {
+            (Some('b'), rest) => data_encoding::BASE32_NOPAD
+                .decode(rest.to_uppercase().as_bytes())
+                .map_err(|e| format!("bad base32: {}", e))?,
+            (Some('z'), rest) => bs58::decode(rest)
+                .into_vec()
+                .map_err(|e| format!("bad base58: {}", e))?,
+            (Some('f'), rest) => hex::decode(rest).map_err(|e| format!("bad base16: {}", e))?,
+            _ => return Err("unsupported multibase".to_string()),
+        };
+        Cid::from_bytes(&bytes, text.to_string())
+    }
+
+    /// A CID in binary form, as stored in dag-pb links: a bare multihash for v0.
+    /// Without `text`, the CID is named in base32 CIDv1 form.
+    fn from_bytes(bytes: &[u8], text: String) -> Result<Self, String> {
+        if bytes.first() == Some(&(HASH_SHA2_256 as u8)) {
+            return Cid::from_multihash(CODEC_DAG_PB, bytes, &text);
+        }
+
+        let mut pos = 0;
+        if read_varint(bytes, &mut pos)? != 1 {
+            return Err("unsupported CID version".to_string());
+        }
+        let codec = read_varint(bytes, &mut pos)?;
+        Cid::from_multihash(codec, &bytes[pos..], &text)
+    }
+
+    fn from_multihash(codec: u64, multihash: &[u8], text: &str) -> Result<Self, String> {
+        let mut pos = 0;
+        if read_varint(multihash, &mut pos)? != HASH_SHA2_256 {
+            return Err("only sha2-256 CIDs can be verified".to_string());
+        }
+        let len = read_varint(multihash, &mut pos)? as usize;
+        let digest = multihash
+            .get(pos..)
+            .filter(|digest| digest.len() == len && len == 32);
+        let Some(digest) = digest else {
+            return Err("malformed multihash".to_string());
+        };
+        if codec != CODEC_RAW && codec != CODEC_DAG_PB {
+            return Err(format!("unsupported codec 0x{:x}", codec));
+        }
+
+        Ok(Cid {
+            codec,
+            digest: digest.to_vec(),
+            text: if text.is_empty() {
+                let bytes = [&[1, codec as u8, HASH_SHA2_256 as u8, 32][..], digest].concat();
+                format!(
+                    "b{}",
+                    data_encoding::BASE32_NOPAD.encode(&bytes).to_lowercase()
+                )
+            } else {
+                text.to_string()
+            },
+        })
+    }
+}
+
+impl ContentUri {
+    /// Strings without a scheme are taken as HTTPS URLs, as product URIs always were.
+    pub fn parse(uri: &str) -> Result<Self, ApiError> {
+        let uri = uri.trim();
+
+        if let Some(rest) = uri.strip_prefix("ipfs://") {
+            let mut segments = rest.split('/').filter(|segment| !segment.is_empty());
+            let cid = segments.next().ok_or_else(|| invalid(uri, "missing CID"))?;
+            let cid = Cid::parse(cid).map_err(|e| invalid(uri, e))?;
+            let path = segments.map(str::to_string).collect();
+            return Ok(ContentUri::Ipfs { cid, path });
+        }
+
+        if let Some(rest) = uri.strip_prefix("ar://") {
+            let (id, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+            let valid = id.len() == 43
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                return Err(invalid(uri, "not an Arweave transaction id"));
+            }
+            return Ok(ContentUri::Arweave {
+                id: id.to_string(),
+                path: path.to_string(),
+            });
+        }
+
+        if let Some(rest) = uri.strip_prefix("data:") {
+            let (meta, data) = rest
+                .split_once(',')
+                .ok_or_else(|| invalid(uri, "missing ','"))?;
+            let bytes = if meta.ends_with(";base64") {
+                STANDARD
+                    .decode(data)
+                    .or_else(|_| URL_SAFE_NO_PAD.decode(data.trim_end_matches('=')))
+                    .map_err(|e| invalid(uri, e))?
+            } else {
+                percent_encoding::percent_decode_str(data).collect()
+            };
+            return Ok(ContentUri::Data(bytes));
+        }
+
+        Ok(ContentUri::Http(ensure_protocol(uri)))
+    }
+
+    /// Content that cannot change under the same URI.
+    pub fn is_immutable(&self) -> bool {
+        !matches!(self, ContentUri::Http(_))
+    }
+
+    /// Bytes behind the URI, fetched unconditionally. The knowledge cache
+    /// fetches HTTP(S) URIs itself so it can revalidate them.
+    pub async fn resolve(&self) -> Result<Vec<u8>, ApiError> {
+        match self {
+            ContentUri::Http(url) => Ok(fetch_any(url).await?),
+            ContentUri::Data(bytes) => Ok(bytes.clone()),
+            ContentUri::Arweave { id, path } => {
+                let mut last_error = None;
+                for gateway in &config::get().arweave_gateways {
+                    match fetch_any(&format!("{}/{}{}", gateway, id, path)).await {
+                        Ok(bytes) => return Ok(bytes),
+                        Err(e) => last_error = Some(e),
+                    }
+                }
+                Err(gateway_error("Arweave", last_error))
+            }
+            ContentUri::Ipfs { cid, path } => Ok(ipfs_file(cid, path).await?),
+        }
+    }
+}
+
+fn gateway_error(network: &str, last_error: Option<FetchError>) -> ApiError {
+    match last_error {
+        Some(e) => {
+            ApiError::BadRequest(format!("No {} gateway served the content: {}", network, e))
+        }
+        None => ApiError::Internal(format!("No {} gateways configured", network)),
+    }
+}
+
+async fn fetch_any(url: &str) -> Result<Vec<u8>, FetchError> {
+    let fetcher = fetch::client();
+    Ok(fetcher.send(fetcher.get(url)?, &[]).await?.body)
+}
+
+/// A block from the first gateway that serves one matching the CID.
+async fn ipfs_block(cid: &Cid) -> Result<Vec<u8>, ApiError> {
+    let fetcher = fetch::client();
+    let mut last_error = None;
+
+    for gateway in &config::get().ipfs_gateways {
+        let url = format!("{}/ipfs/{}?format=raw", gateway, cid.text);
+        let result = async {
+            let req = fetcher
+                .get(&url)?
+                .header("Accept", "application/vnd.ipld.raw");
+            fetcher.send(req, &["application/vnd.ipld.raw"]).await
+        }
+        .await;
+
+        match result {
+            Ok(res) if Sha256::digest(&res.body).as_slice() == cid.digest => return Ok(res.body),
+            Ok(_) => {
+                tracing::warn!(gateway = %gateway, cid = %cid.text, "IPFS gateway served a block that does not match its CID");
+                last_error = Some(FetchError::Blocked {
+                    url,
+                    reason: "content does not match its CID".to_string(),
+                });
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(gateway_error("IPFS", last_error))
+}
+
+/// Length-delimited and varint fields of a protobuf message, others skipped.
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+fn protobuf_fields(buf: &[u8]) -> Result<Vec<(u64, Field<'_>)>, String> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let key = read_varint(buf, &mut pos)?;
+        let field = match key & 7 {
+            0 => Field::Varint(read_varint(buf, &mut pos)?),
+            2 => {
+                let len = read_varint(buf, &mut pos)? as usize;
+                let end = pos.checked_add(len).ok_or("protobuf field length overflows")?;
+                let bytes = buf.get(pos..end).ok_or("truncated protobuf field")?;
+                pos = end;
+                Field::Bytes(bytes)
+            }
+            1 => {
+                pos += 8;
+                continue;
+            }
+            5 => {
+                pos += 4;
+                continue;
+            }
+            wire => return Err(format!("unsupported protobuf wire type {}", wire)),
+        };
+        fields.push((key >> 3, field));
+    }
+    Ok(fields)
+}
+
+struct Link {
+    cid: Cid,
+    name: String,
+}
+
+/// A dag-pb node: its UnixFS type, inline data and links.
+struct Node {
+    kind: u64,
+    data: Vec<u8>,
+    links: Vec<Link>,
+}
+
+fn decode_node(block: &[u8]) -> Result<Node, String> {
+    let mut unixfs: &[u8] = &[];
+    let mut links = Vec::new();
+
+    for (number, field) in protobuf_fields(block)? {
+        match (number, field) {
+            (1, Field::Bytes(bytes)) => unixfs = bytes,
+            (2, Field::Bytes(bytes)) => {
+                let mut hash = None;
+                let mut name = String::new();
+                for (number, field) in protobuf_fields(bytes)? {
+                    match (number, field) {
+                        (1, Field::Bytes(bytes)) => {
+                            hash = Some(Cid::from_bytes(bytes, String::new())?)
+                        }
+                        (2, Field::Bytes(bytes)) => {
+                            name = String::from_utf8_lossy(bytes).into_owned()
+                        }
+                        _ => {}
+                    }
+                }
+                let cid = hash.ok_or("link without a hash")?;
+                links.push(Link { cid, name });
+            }
+            _ => {}
+        }
+    }
+
+    let mut kind = UNIXFS_FILE;
+    let mut data = Vec::new();
+    for (number, field) in protobuf_fields(unixfs)? {
+        match (number, field) {
+            (1, Field::Varint(value)) => kind = value,
+            (2, Field::Bytes(bytes)) => data = bytes.to_vec(),
+            _ => {}
+        }
+    }
+
+    Ok(Node { kind, data, links })
+}
+
+/// Walks `path` through UnixFS directories, then assembles the file there from
+/// its verified blocks.
+async fn ipfs_file(root: &Cid, path: &[String]) -> Result<Vec<u8>, ApiError> {
+    let bad_dag =
+        |e: String| ApiError::BadRequest(format!("Invalid IPFS content {}: {}", root.text, e));
+
+    if path.len() > IPFS_MAX_DEPTH {
+        return Err(bad_dag(format!("path is deeper than {} levels", IPFS_MAX_DEPTH)));
+    }
+
+    let mut blocks = 0;
+    let mut cid = root.clone();
+    for segment in path {
+        blocks += 1;
+        let block = ipfs_block(&cid).await?;
+        let node = decode_node(&block).map_err(bad_dag)?;
+        if cid.codec != CODEC_DAG_PB || node.kind != UNIXFS_DIRECTORY {
+            return Err(bad_dag(format!("'{}' is not inside a directory", segment)));
+        }
+        cid = node
+            .links
+            .into_iter()
+            .find(|link| link.name == *segment)
+            .map(|link| link.cid)
+            .ok_or_else(|| {
+                ApiError::NotFound(format!("'{}' not found in {}", segment, root.text))
+            })?;
+    }
+
+    // Depth-first over the file's blocks keeps their order
+    let limit = config::get().fetch_max_bytes as usize;
+    let mut file = Vec::new();
+    let mut pending = vec![(cid, 0)];
+    while let Some((cid, depth)) = pending.pop() {
+        blocks += 1;
+        let block = ipfs_block(&cid).await?;
+        if cid.codec == CODEC_RAW {
+            file.extend_from_slice(&block);
+        } else {
+            let node = decode_node(&block).map_err(bad_dag)?;
+            if !matches!(node.kind, UNIXFS_FILE | UNIXFS_RAW) {
+                return Err(bad_dag("not a file".to_string()));
+            }
+            if !node.links.is_empty() && depth >= IPFS_MAX_DEPTH {
+                return Err(bad_dag(format!("file is nested deeper than {} levels", IPFS_MAX_DEPTH)));
+            }
+            file.extend_from_slice(&node.data);
+            pending.extend(node.links.into_iter().rev().map(|link| (link.cid, depth + 1)));
+        }
+
+        if blocks + pending.len() > IPFS_MAX_BLOCKS {
+            return Err(bad_dag(format!("file has more than {} blocks", IPFS_MAX_BLOCKS)));
+        }
+
+        if file.len() > limit {
+            return Err(ApiError::BadRequest(format!(
+                "IPFS content {} is larger than {} bytes",
+                root.text, limit
+            )));
+        }
+    }
+
+    Ok(file)
+}
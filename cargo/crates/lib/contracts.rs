@@ -1,10 +1,11 @@
+use crate::lib::{config, metrics};
 use ethers::{
-    abi::Abi,
-    contract::Contract,
+    abi::{Abi, Detokenize},
+    contract::{Contract, ContractCall, ContractError},
     middleware::SignerMiddleware,
     providers::{Http, Middleware, Provider},
     signers::{LocalWallet, Signer},
-    types::Address,
+    types::{Address, TxHash, U64},
 };
 use serde::Deserialize;
 use std::{fs, sync::Arc};
@@ -42,9 +43,7 @@ pub fn get_contract(
     let file_content = fs::read_to_string("./definitions.json")?;
     let contracts: Contracts = serde_json::from_str(&file_content)?;
 
-    let rpc_url = std::env::var("BLOCKCHAIN_PROVIDER_URL")
-        .unwrap_or_else(|_| "https://hyperion-testnet.metisdevops.link".to_string());
-    let provider = Provider::<Http>::try_from(rpc_url.as_str())?;
+    let provider = Provider::<Http>::try_from(config::get().blockchain_provider_url.as_str())?;
     let provider = Arc::new(provider);
 
     let address: Address = match address {
@@ -60,7 +59,7 @@ pub fn get_contract(
     };
     let abi: Abi = serde_json::from_value(contracts.get(name).unwrap().abi.clone())?;
 
-    println!("Creating contract {} at address {}", name, address);
+    tracing::debug!(contract = name, address = ?address, "Creating contract");
     Ok(Contract::new(address, abi, provider.clone()))
 }
 
@@ -71,14 +70,15 @@ pub async fn get_contract_with_wallet(
     let file_content = fs::read_to_string("./definitions.json")?;
     let contracts: Contracts = serde_json::from_str(&file_content)?;
 
-    let rpc_url = std::env::var("BLOCKCHAIN_PROVIDER_URL")
-        .unwrap_or_else(|_| "https://hyperion-testnet.metisdevops.link".to_string());
-    let provider = Provider::<Http>::try_from(rpc_url.as_str())?;
+    let provider = Provider::<Http>::try_from(config::get().blockchain_provider_url.as_str())?;
 
-    let private_key = std::env::var("SERVER_PVT_KEY")
-        .map_err(|_| "PRIVATE_KEY environment variable not found")?;
+    let private_key = config::get()
+        .server_pvt_key
+        .as_ref()
+        .ok_or("SERVER_PVT_KEY is not configured")?;
 
     let wallet: LocalWallet = private_key
+        .expose()
         .parse()
         .map_err(|_| "Invalid private key format")?;
 
@@ -101,10 +101,7 @@ pub async fn get_contract_with_wallet(
     };
     let abi: Abi = serde_json::from_value(contracts.get(name).unwrap().abi.clone())?;
 
-    println!(
-        "Creating wallet-enabled contract {} at address {}",
-        name, address
-    );
+    tracing::debug!(contract = name, address = ?address, chain_id = %chain_id, "Creating wallet-enabled contract");
     Ok(Contract::new(address, abi, signer_middleware))
 }
 
@@ -118,14 +115,71 @@ pub async fn get_contract_auto(
     name: &str,
     address: Option<&str>,
 ) -> Result<ContractType, Box<dyn std::error::Error>> {
-    match std::env::var("SERVER_PVT_KEY") {
-        Ok(_) => {
+    match config::get().server_pvt_key {
+        Some(_) => {
             let contract = get_contract_with_wallet(name, address).await?;
             Ok(ContractType::WithWallet(contract))
         }
-        Err(_) => {
+        None => {
             let contract = get_contract(name, address)?;
             Ok(ContractType::ReadOnly(contract))
         }
     }
 }
+
+/// Sends a transaction and waits for it to be mined, counting the outcome and gas
+/// used per contract method. Reverted transactions are counted but not an error,
+/// matching what callers did before.
+pub async fn send<M: Middleware, D: Detokenize>(
+    call: &ContractCall<M, D>,
+    contract: &str,
+    method: &str,
+) -> Result<TxHash, ContractError<M>> {
+    let pending = match call.send().await {
+        Ok(pending) => pending,
+        Err(e) => {
+            tracing::error!(contract, method, error = %e, "Failed to send transaction");
+            metrics::inc(
+                "haithe_chain_transactions_total",
+                &[("contract", contract), ("method", method), ("status", "failed")],
+            );
+            return Err(e);
+        }
+    };
+
+    let tx_hash = pending.tx_hash();
+    tracing::info!(tx_hash = ?tx_hash, contract, method, "Transaction sent");
+
+    let receipt = match pending.await {
+        Ok(receipt) => receipt,
+        Err(e) => {
+            tracing::error!(tx_hash = ?tx_hash, contract, method, error = %e, "Transaction failed");
+            metrics::inc(
+                "haithe_chain_transactions_total",
+                &[("contract", contract), ("method", method), ("status", "failed")],
+            );
+            return Err(ContractError::ProviderError { e });
+        }
+    };
+
+    let status = match &receipt {
+        Some(receipt) if receipt.status == Some(U64::from(1)) => "mined",
+        Some(_) => "reverted",
+        None => "dropped",
+    };
+    tracing::info!(tx_hash = ?tx_hash, contract, method, status, "Transaction settled");
+
+    metrics::inc(
+        "haithe_chain_transactions_total",
+        &[("contract", contract), ("method", method), ("status", status)],
+    );
+    if let Some(gas_used) = receipt.and_then(|r| r.gas_used) {
+        metrics::add(
+            "haithe_chain_gas_used_total",
+            &[("contract", contract), ("method", method)],
+            gas_used.as_u128() as f64,
+        );
+    }
+
+    Ok(tx_hash)
+}
@@ -0,0 +1,169 @@
+use super::{Db, query_as};
+use serde::Serialize;
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct MessageSearchResult {
+    pub id: i32,
+    pub conversation_id: i32,
+    pub conversation_title: Option<String>,
+    pub sender: String,
+    pub snippet: String,
+    pub created_at: String,
+}
+
+/// Quotes every term so user input can't be parsed as FTS5 query syntax.
+fn fts_query(input: &str) -> String {
+    input
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Full-text search over a caller's messages in a project, newest first.
+/// SQLite uses the FTS5 index, Postgres a `simple` text search configuration.
+pub async fn search_messages(
+    db: &Db,
+    project_id: i64,
+    wallet_address: &str,
+    terms: &str,
+    before_id: Option<i64>,
+    limit: i64,
+) -> Result<Vec<MessageSearchResult>, sqlx::Error> {
+    match db {
+        Db::Sqlite(_) => {
+            query_as(
+                "SELECT m.id, m.conversation_id, c.title AS conversation_title, m.sender,
+                        snippet(agent_preview_messages_fts, 0, '**', '**', '…', 16) AS snippet, m.created_at
+                 FROM agent_preview_messages_fts
+                 JOIN agent_preview_messages m ON m.id = agent_preview_messages_fts.rowid
+                 JOIN conversations c ON c.id = m.conversation_id
+                 WHERE agent_preview_messages_fts MATCH ?
+                   AND c.wallet_address = ? AND c.project_id = ?
+                   AND (? IS NULL OR m.id < ?)
+                 ORDER BY m.id DESC
+                 LIMIT ?",
+            )
+            .bind(fts_query(terms))
+            .bind(wallet_address)
+            .bind(project_id)
+            .bind(before_id)
+            .bind(before_id)
+            .bind(limit)
+            .fetch_all(db)
+            .await
+        }
+        Db::Postgres(_) => {
+            query_as(
+                "SELECT m.id, m.conversation_id, c.title AS conversation_title, m.sender,
+                        ts_headline('simple', m.message, plainto_tsquery('simple', ?),
+                                    'StartSel=**, StopSel=**, MaxWords=16, MinWords=4') AS snippet,
+                        m.created_at
+                 FROM agent_preview_messages m
+                 JOIN conversations c ON c.id = m.conversation_id
+                 WHERE to_tsvector('simple', m.message) @@ plainto_tsquery('simple', ?)
+                   AND c.wallet_address = ? AND c.project_id = ?
+                   AND (? IS NULL OR m.id < ?)
+                 ORDER BY m.id DESC
+                 LIMIT ?",
+            )
+            .bind(terms)
+            .bind(terms)
+            .bind(wallet_address)
+            .bind(project_id)
+            .bind(before_id)
+            .bind(before_id)
+            .bind(limit)
+            .fetch_all(db)
+            .await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::db::{self, Backend, testing};
+
+    #[test]
+    fn quotes_fts_syntax() {
+        assert_eq!(fts_query("keeper OR \"lamp* NEAR("), "\"keeper\" \"OR\" \"\"\"lamp*\" \"NEAR(\"");
+    }
+
+    #[actix_web::test]
+    async fn searches_messages_on_both_backends() {
+        for backend in [Backend::Sqlite, Backend::Postgres] {
+            let db = testing::migrated(backend).await;
+            let project_id = testing::project(&db).await;
+
+            let conversation_id: i32 = db::query_scalar(
+                "INSERT INTO conversations (wallet_address, project_id, title) VALUES (?, ?, ?) RETURNING id",
+            )
+            .bind(testing::OWNER)
+            .bind(project_id)
+            .bind("Lighthouse keeping")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+            let mut ids = Vec::new();
+            for (sender, message) in [
+                ("user", "Who keeps the lighthouse?"),
+                ("ai", "The keeper does, every night."),
+                ("user", "And who lights the lighthouse lamp?"),
+            ] {
+                let id: i32 = db::query_scalar(
+                    "INSERT INTO agent_preview_messages (conversation_id, sender, message) VALUES (?, ?, ?) RETURNING id",
+                )
+                .bind(conversation_id)
+                .bind(sender)
+                .bind(message)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+                ids.push(id);
+            }
+
+            let hits = search_messages(&db, project_id, testing::OWNER, "Lighthouse", None, 10)
+                .await
+                .unwrap();
+            assert_eq!(
+                hits.iter().map(|h| h.id).collect::<Vec<_>>(),
+                [ids[2], ids[0]],
+                "{:?}",
+                backend
+            );
+            assert!(hits.iter().all(|h| h.snippet.contains("**lighthouse**")), "{:?}", hits);
+            assert_eq!(hits[0].conversation_title.as_deref(), Some("Lighthouse keeping"));
+
+            let older = search_messages(
+                &db,
+                project_id,
+                testing::OWNER,
+                "lighthouse",
+                Some(hits[0].id as i64),
+                10,
+            )
+            .await
+            .unwrap();
+            assert_eq!(older.iter().map(|h| h.id).collect::<Vec<_>>(), [ids[0]]);
+
+            let limited = search_messages(&db, project_id, testing::OWNER, "lighthouse", None, 1)
+                .await
+                .unwrap();
+            assert_eq!(limited.len(), 1);
+
+            let others = search_messages(&db, project_id, "0xsomeoneelse", "lighthouse", None, 10)
+                .await
+                .unwrap();
+            assert!(others.is_empty());
+
+            // Operators and stray quotes are searched for, never parsed
+            let syntax = search_messages(&db, project_id, testing::OWNER, "keeper\" OR NEAR(", None, 10)
+                .await
+                .unwrap();
+            assert!(syntax.is_empty(), "{:?}", backend);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use super::{Db, Executor, query, query_scalar};
+
+pub async fn id_by_uid(db: &Db, organization_uid: &str) -> Result<Option<i64>, sqlx::Error> {
+    query_scalar("SELECT id FROM organizations WHERE organization_uid = ?")
+        .bind(organization_uid)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn enrolled_model_ids(db: &Db, org_id: i64) -> Result<Vec<u64>, sqlx::Error> {
+    let ids: Vec<i64> = query_scalar("SELECT model_id FROM org_model_enrollments WHERE org_id = ?")
+        .bind(org_id)
+        .fetch_all(db)
+        .await?;
+
+    Ok(ids.into_iter().map(|id| id as u64).collect())
+}
+
+/// Enrolls the org in a model, doing nothing if it already is.
+pub async fn enroll_model<'c>(
+    executor: impl Into<Executor<'c>>,
+    org_id: i64,
+    model_id: u64,
+) -> Result<(), sqlx::Error> {
+    query(
+        "INSERT INTO org_model_enrollments (org_id, model_id) VALUES (?, ?)
+         ON CONFLICT (org_id, model_id) DO NOTHING",
+    )
+    .bind(org_id)
+    .bind(model_id)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+pub async fn expenditure(db: &Db, org_id: i64) -> Result<i64, sqlx::Error> {
+    query_scalar("SELECT expenditure FROM organizations WHERE id = ?")
+        .bind(org_id)
+        .fetch_one(db)
+        .await
+}
+
+pub async fn add_expenditure(db: &Db, org_id: i64, amount: i64) -> Result<(), sqlx::Error> {
+    query("UPDATE organizations SET expenditure = expenditure + ? WHERE id = ?")
+        .bind(amount)
+        .bind(org_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
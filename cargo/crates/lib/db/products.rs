@@ -0,0 +1,52 @@
+use super::{Db, Executor, query, query_scalar};
+
+/// Addresses of the products a project has enabled.
+pub async fn enabled_addresses(db: &Db, project_id: i64) -> Result<Vec<String>, sqlx::Error> {
+    query_scalar(
+        "SELECT p.address FROM products p
+         JOIN project_products_enabled ppe ON p.id = ppe.product_id
+         WHERE ppe.project_id = ?",
+    )
+    .bind(project_id)
+    .fetch_all(db)
+    .await
+}
+
+/// Combined per-call price of the products a project has enabled.
+pub async fn enabled_price_per_call(db: &Db, project_id: i64) -> Result<i64, sqlx::Error> {
+    query_scalar(
+        "SELECT CAST(COALESCE(SUM(p.price_per_call), 0) AS BIGINT)
+         FROM products p
+         INNER JOIN project_products_enabled ppe ON p.id = ppe.product_id
+         WHERE ppe.project_id = ?",
+    )
+    .bind(project_id)
+    .fetch_one(db)
+    .await
+}
+
+/// Registers a wallet as a product creator, replacing an earlier registration.
+pub async fn upsert_creator<'c>(
+    executor: impl Into<Executor<'c>>,
+    wallet_address: &str,
+    uri: &str,
+    pvt_key_seed: &str,
+    pub_key: &str,
+) -> Result<(), sqlx::Error> {
+    query(
+        "INSERT INTO creators (wallet_address, uri, pvt_key_seed, pub_key, created_at)
+         VALUES (?, ?, ?, ?, CURRENT_TIMESTAMP)
+         ON CONFLICT (wallet_address) DO UPDATE SET
+             uri = excluded.uri,
+             pvt_key_seed = excluded.pvt_key_seed,
+             pub_key = excluded.pub_key,
+             created_at = excluded.created_at",
+    )
+    .bind(wallet_address)
+    .bind(uri)
+    .bind(pvt_key_seed)
+    .bind(pub_key)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use super::{Db, Executor, query_as, query_scalar};
+use sqlx::FromRow;
+
+/// How a project's agent is set up. Unset fields keep the platform defaults.
+#[derive(Debug, Clone, FromRow)]
+pub struct AgentConfig {
+    pub system_prompt: Option<String>,
+    pub persona_name: Option<String>,
+    pub max_tokens: Option<i64>,
+    pub min_temperature: Option<f32>,
+    pub max_temperature: Option<f32>,
+    /// JSON array of strings.
+    pub stop_sequences: String,
+    /// JSON array of model ids, tried in order when the requested model fails.
+    pub fallback_model_ids: String,
+    pub max_retries: i64,
+    pub retry_backoff_ms: i64,
+    pub routing_strategy: String,
+}
+
+impl AgentConfig {
+    pub fn stop_sequences(&self) -> Vec<String> {
+        serde_json::from_str(&self.stop_sequences).unwrap_or_default()
+    }
+
+    pub fn fallback_model_ids(&self) -> Vec<i64> {
+        serde_json::from_str(&self.fallback_model_ids).unwrap_or_default()
+    }
+}
+
+pub async fn id_by_uid(db: &Db, project_uid: &str) -> Result<Option<i64>, sqlx::Error> {
+    query_scalar("SELECT id FROM projects WHERE project_uid = ?")
+        .bind(project_uid)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn org_id<'c>(
+    executor: impl Into<Executor<'c>>,
+    project_id: i64,
+) -> Result<Option<i64>, sqlx::Error> {
+    query_scalar("SELECT org_id FROM projects WHERE id = ?")
+        .bind(project_id)
+        .fetch_optional(executor)
+        .await
+}
+
+/// `(organization_uid, project_uid)` for a project, as API callers address it.
+pub async fn uids(db: &Db, project_id: i64) -> Result<(String, String), sqlx::Error> {
+    query_as(
+        "SELECT o.organization_uid, p.project_uid FROM projects p
+         JOIN organizations o ON o.id = p.org_id WHERE p.id = ?",
+    )
+    .bind(project_id)
+    .fetch_one(db)
+    .await
+}
+
+/// `(search_enabled, memory_enabled)` for a project.
+pub async fn features(db: &Db, project_id: i64) -> Result<(bool, bool), sqlx::Error> {
+    query_as("SELECT search_enabled, memory_enabled FROM projects WHERE id = ?")
+        .bind(project_id)
+        .fetch_one(db)
+        .await
+}
+
+/// The project's prompt variables, a JSON object stored as text.
+pub async fn prompt_variables(db: &Db, project_id: i64) -> Result<String, sqlx::Error> {
+    query_scalar("SELECT prompt_variables FROM projects WHERE id = ?")
+        .bind(project_id)
+        .fetch_one(db)
+        .await
+}
+
+pub async fn agent_config(db: &Db, project_id: i64) -> Result<AgentConfig, sqlx::Error> {
+    query_as(
+        "SELECT system_prompt, persona_name, max_tokens, min_temperature, max_temperature,
+                stop_sequences, fallback_model_ids, max_retries, retry_backoff_ms, routing_strategy
+         FROM projects WHERE id = ?",
+    )
+    .bind(project_id)
+    .fetch_one(db)
+    .await
+}
@@ -0,0 +1,66 @@
+use super::{Db, Executor, epoch_seconds, query, query_scalar};
+
+/// Creates the account row for a wallet on first sight.
+pub async fn ensure_account<'c>(
+    executor: impl Into<Executor<'c>>,
+    wallet_address: &str,
+) -> Result<(), sqlx::Error> {
+    query("INSERT INTO accounts (wallet_address) VALUES (?) ON CONFLICT (wallet_address) DO NOTHING")
+        .bind(wallet_address)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Replaces the wallet's session with a freshly issued token.
+pub async fn start<'c>(
+    executor: impl Into<Executor<'c>>,
+    wallet_address: &str,
+    token: &str,
+    ip: &str,
+    user_agent: &str,
+) -> Result<(), sqlx::Error> {
+    query(
+        "INSERT INTO sessions (wallet_address, token, ip, user_agent)
+         VALUES (?, ?, ?, ?)
+         ON CONFLICT (wallet_address)
+         DO UPDATE SET token = excluded.token, ip = excluded.ip, user_agent = excluded.user_agent, created_at = CURRENT_TIMESTAMP",
+    )
+    .bind(wallet_address)
+    .bind(token)
+    .bind(ip)
+    .bind(user_agent)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+pub async fn token(db: &Db, wallet_address: &str) -> Result<Option<String>, sqlx::Error> {
+    query_scalar("SELECT token FROM sessions WHERE wallet_address = ?")
+        .bind(wallet_address)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn end<'c>(
+    executor: impl Into<Executor<'c>>,
+    wallet_address: &str,
+) -> Result<(), sqlx::Error> {
+    query("DELETE FROM sessions WHERE wallet_address = ?")
+        .bind(wallet_address)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// When the wallet's current API key was issued, in seconds since the epoch.
+/// `None` when no key has been issued or it was revoked.
+pub async fn api_key_issued_at(db: &Db, wallet_address: &str) -> Result<Option<i64>, sqlx::Error> {
+    let issued_at: Option<Option<String>> =
+        query_scalar("SELECT api_key_last_issued_at FROM accounts WHERE wallet_address = ?")
+            .bind(wallet_address)
+            .fetch_optional(db)
+            .await?;
+
+    Ok(issued_at.flatten().as_deref().and_then(epoch_seconds))
+}
@@ -0,0 +1,104 @@
+use super::{Db, query_scalar, timestamp};
+use chrono::{Datelike, TimeZone, Utc};
+
+/// Start of a spend window, compared against `usage_records.created_at`.
+#[derive(Debug, Clone, Copy)]
+pub enum Since {
+    Today,
+    StartOfMonth,
+    Ever,
+}
+
+impl Since {
+    fn boundary(self) -> String {
+        let now = Utc::now();
+        let start = match self {
+            Since::Today => Utc
+                .with_ymd_and_hms(now.year(), now.month(), now.day(), 0, 0, 0)
+                .single(),
+            Since::StartOfMonth => Utc
+                .with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+                .single(),
+            Since::Ever => None,
+        };
+        start.map(timestamp).unwrap_or_default()
+    }
+}
+
+/// Records a billed call and returns its id.
+pub async fn record(
+    db: &Db,
+    org_id: i64,
+    project_id: i64,
+    wallet_address: Option<&str>,
+    model: &str,
+    cost: i64,
+) -> Result<i64, sqlx::Error> {
+    query_scalar(
+        "INSERT INTO usage_records (org_id, project_id, wallet_address, model, cost) VALUES (?, ?, ?, ?, ?) RETURNING id",
+    )
+    .bind(org_id)
+    .bind(project_id)
+    .bind(wallet_address.map(str::to_lowercase))
+    .bind(model)
+    .bind(cost)
+    .fetch_one(db)
+    .await
+}
+
+pub async fn org_spent(db: &Db, org_id: i64, since: Since) -> Result<i64, sqlx::Error> {
+    query_scalar(
+        "SELECT CAST(COALESCE(SUM(cost), 0) AS BIGINT) FROM usage_records
+         WHERE org_id = ? AND created_at >= ?",
+    )
+    .bind(org_id)
+    .bind(since.boundary())
+    .fetch_one(db)
+    .await
+}
+
+pub async fn project_spent(db: &Db, project_id: i64, since: Since) -> Result<i64, sqlx::Error> {
+    query_scalar(
+        "SELECT CAST(COALESCE(SUM(cost), 0) AS BIGINT) FROM usage_records
+         WHERE project_id = ? AND created_at >= ?",
+    )
+    .bind(project_id)
+    .bind(since.boundary())
+    .fetch_one(db)
+    .await
+}
+
+pub async fn project_uid_spent(
+    db: &Db,
+    org_id: i64,
+    project_uid: &str,
+    since: Since,
+) -> Result<i64, sqlx::Error> {
+    query_scalar(
+        "SELECT CAST(COALESCE(SUM(u.cost), 0) AS BIGINT) FROM usage_records u
+         JOIN projects p ON p.id = u.project_id
+         WHERE u.org_id = ? AND p.project_uid = ? AND u.created_at >= ?",
+    )
+    .bind(org_id)
+    .bind(project_uid)
+    .bind(since.boundary())
+    .fetch_one(db)
+    .await
+}
+
+pub async fn wallet_spent(
+    db: &Db,
+    org_id: i64,
+    wallet_address: &str,
+    since: Since,
+) -> Result<i64, sqlx::Error> {
+    query_scalar(
+        "SELECT CAST(COALESCE(SUM(cost), 0) AS BIGINT) FROM usage_records
+         WHERE org_id = ? AND wallet_address = ? AND created_at >= ?",
+    )
+    .bind(org_id)
+    .bind(wallet_address)
+    .bind(since.boundary())
+    .fetch_one(db)
+    .await
+}
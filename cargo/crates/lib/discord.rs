@@ -1,6 +1,8 @@
+use crate::lib::{db, metrics};
 use crate::lib::llm::{LlmResponseParams, generate_llm_response};
 use crate::lib::models::get_model_by_id;
 use crate::lib::state::{AppState, DiscordBotHandle};
+use crate::lib::webhooks;
 use actix_web::web;
 use anyhow::Error;
 use serde_json::json;
@@ -33,9 +35,26 @@ impl EventHandler for Handler {
             n: 1,
             org_uid: self.org_uid.clone(),
             project_uid: self.project_uid.clone(),
+            wallet_address: None,
+            metadata: serde_json::Map::from_iter([
+                ("platform".to_string(), json!("discord")),
+                ("user".to_string(), json!(msg.author.name)),
+            ]),
+            invocation_id: None,
+            timeout: None,
         };
 
-        match generate_llm_response(params, &self.state).await {
+        let result = generate_llm_response(params, &self.state).await;
+        metrics::inc(
+            "haithe_bot_messages_total",
+            &[
+                ("platform", "discord"),
+                ("project_uid", &self.project_uid),
+                ("status", if result.is_ok() { "ok" } else { "error" }),
+            ],
+        );
+
+        match result {
             Ok(response) => {
                 if let Some(choice) = response.choices.first() {
                     if let Some(content) = choice
@@ -44,13 +63,21 @@ impl EventHandler for Handler {
                         .and_then(|c| c.as_str())
                     {
                         if let Err(e) = msg.channel_id.say(&ctx.http, content).await {
-                            eprintln!("Failed to send message: {}", e);
+                            tracing::error!(error = %e, "Failed to send Discord message");
                         }
                     }
                 }
             }
             Err(e) => {
                 let _ = msg.channel_id.say(&ctx.http, format!("Error: {}", e)).await;
+
+                webhooks::emit_for_project_uid(
+                    &self.state.db,
+                    &self.project_uid,
+                    "bot.error",
+                    json!({ "platform": "discord", "error": e.to_string() }),
+                )
+                .await;
             }
         }
     }
@@ -80,7 +107,7 @@ pub async fn start_discord_bot(
         .expect("Error creating Discord client");
 
         if let Err(why) = client.start().await {
-            eprintln!("Discord client ended: {:?}", why);
+            tracing::warn!(error = ?why, "Discord client ended");
         }
     });
 
@@ -103,7 +130,7 @@ pub async fn sync_discord_bots(state: web::Data<AppState>) -> Result<(), Error>
         default_model_id: Option<i64>,
     }
 
-    let rows: Vec<Row> = sqlx::query_as::<_, Row>(
+    let rows: Vec<Row> = db::query_as::<Row>(
         r#"
         SELECT pr.discord_token as token,
                o.organization_uid as org_uid,
@@ -0,0 +1,305 @@
+use crate::lib::config;
+use crate::lib::error::ApiError;
+use pulldown_cmark::{Event, Options, Parser, TagEnd};
+use serde_json::Value;
+use std::io::{Cursor, Read};
+
+/// Text of every page of a PDF, read from memory. Pages without extractable
+/// text are skipped.
+pub fn pdf(bytes: &[u8]) -> Result<String, ApiError> {
+    let document = lopdf::Document::load_mem(bytes)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid PDF: {}", e)))?;
+
+    let text = document
+        .get_pages()
+        .keys()
+        .filter_map(|page| document.extract_text(&[*page]).ok())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if text.trim().is_empty() {
+        return Err(ApiError::BadRequest(
+            "PDF has no extractable text".to_string(),
+        ));
+    }
+    Ok(text)
+}
+
+/// Paragraph text of a Word document's main body.
+pub fn docx(bytes: &[u8]) -> Result<String, ApiError> {
+    read_docx(bytes, config::get().fetch_max_bytes)
+}
+
+/// [`docx`], refusing a body that inflates past `max_bytes`, as a small
+/// archive can hold a very large `word/document.xml`.
+fn read_docx(bytes: &[u8], max_bytes: u64) -> Result<String, ApiError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+        .map_err(|e| ApiError::BadRequest(format!("Invalid DOCX: {}", e)))?;
+
+    let mut xml = Vec::new();
+    archive
+        .by_name("word/document.xml")
+        .map_err(|e| ApiError::BadRequest(format!("Invalid DOCX: {}", e)))?
+        .take(max_bytes.saturating_add(1))
+        .read_to_end(&mut xml)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid DOCX: {}", e)))?;
+
+    if xml.len() as u64 > max_bytes {
+        return Err(ApiError::BadRequest(format!(
+            "DOCX body is larger than {} bytes",
+            max_bytes
+        )));
+    }
+
+    let xml = String::from_utf8(xml)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid DOCX: {}", e)))?;
+    Ok(docx_text(&xml))
+}
+
+/// Collects `<w:t>` runs, ending paragraphs and breaks with newlines.
+fn docx_text(xml: &str) -> String {
+    let mut text = String::new();
+    let mut in_run = false;
+    let mut pos = 0;
+
+    while let Some(open) = xml[pos..].find('<').map(|i| pos + i) {
+        if in_run {
+            text.push_str(&unescape_xml(&xml[pos..open]));
+        }
+
+        let Some(close) = xml[open..].find('>').map(|i| open + i) else {
+            break;
+        };
+        let tag = &xml[open + 1..close];
+        pos = close + 1;
+
+        if let Some(name) = tag.strip_prefix('/') {
+            match name.trim() {
+                "w:t" => in_run = false,
+                "w:p" => text.push('\n'),
+                _ => {}
+            }
+            continue;
+        }
+
+        let name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+        match name {
+            "w:t" => in_run = !tag.ends_with('/'),
+            "w:tab" => text.push('\t'),
+            "w:br" | "w:cr" => text.push('\n'),
+            _ => {}
+        }
+    }
+
+    text.trim().to_string()
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Markdown rendered to plain text, keeping block boundaries as blank lines.
+/// Table rows become lines of ` | ` separated cells.
+pub fn markdown(source: &str) -> String {
+    let mut text = String::new();
+
+    for event in Parser::new_ext(source, Options::ENABLE_TABLES) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak => text.push('\n'),
+            Event::End(TagEnd::Item) => text.push('\n'),
+            Event::End(TagEnd::TableCell) => text.push_str(" | "),
+            // The header's cells are not wrapped in a row
+            Event::End(TagEnd::TableHead | TagEnd::TableRow) => {
+                if text.ends_with(" | ") {
+                    text.truncate(text.len() - " | ".len());
+                }
+                text.push('\n');
+            }
+            Event::End(TagEnd::Table) => text.push('\n'),
+            Event::End(
+                TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::CodeBlock | TagEnd::BlockQuote(_),
+            ) => text.push_str("\n\n"),
+            _ => {}
+        }
+    }
+
+    text.trim().to_string()
+}
+
+/// Rows as `column: value` pairs, so each line stands on its own once chunked.
+pub fn csv(source: &str) -> Result<String, ApiError> {
+    let mut records = csv_records(source.trim_start_matches('\u{feff}')).into_iter();
+    let header = records
+        .next()
+        .ok_or_else(|| ApiError::BadRequest("CSV is empty".to_string()))?;
+
+    let lines: Vec<String> = records
+        .map(|record| {
+            header
+                .iter()
+                .zip(&record)
+                .filter(|(_, value)| !value.trim().is_empty())
+                .map(|(column, value)| format!("{}: {}", column.trim(), value.trim()))
+                .collect::<Vec<_>>()
+                .join("; ")
+        })
+        .collect();
+
+    Ok(lines.join("\n"))
+}
+
+/// RFC 4180 records: quoted fields may hold commas, newlines and doubled quotes.
+fn csv_records(source: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    records.retain(|record| record.iter().any(|field| !field.trim().is_empty()));
+    records
+}
+
+/// One `path: value` line per leaf, e.g. `plans[0].price: 10`.
+pub fn json(bytes: &[u8]) -> Result<String, ApiError> {
+    let value: Value = serde_json::from_slice(bytes)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid JSON: {}", e)))?;
+
+    let mut lines = Vec::new();
+    flatten_json(&value, String::new(), &mut lines);
+    Ok(lines.join("\n"))
+}
+
+fn flatten_json(value: &Value, path: String, lines: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                flatten_json(value, path, lines);
+            }
+        }
+        Value::Array(items) => {
+            for (i, value) in items.iter().enumerate() {
+                flatten_json(value, format!("{}[{}]", path, i), lines);
+            }
+        }
+        Value::Null => {}
+        leaf => {
+            let leaf = match leaf {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            if path.is_empty() {
+                lines.push(leaf);
+            } else {
+                lines.push(format!("{}: {}", path, leaf));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_pdf_pages() {
+        let text = pdf(include_bytes!("../../test-data/documents/knowledge.pdf")).unwrap();
+        let guide = text.find("Lighthouse maintenance guide").expect(&text);
+        let lamp = text.find("Replace the lamp every spring.").expect(&text);
+        assert!(guide < lamp, "{}", text);
+
+        assert!(pdf(b"%PDF-1.4 not really").is_err());
+    }
+
+    #[test]
+    fn reads_docx_tabs_and_breaks() {
+        let bytes = include_bytes!("../../test-data/documents/knowledge.docx");
+        assert_eq!(
+            read_docx(bytes, 1024 * 1024).unwrap(),
+            "Opening hours\nMon\t9 - 17\nLine one\nLine two\nFish & chips <fresh>"
+        );
+
+        let error = read_docx(bytes, 64).unwrap_err().to_string();
+        assert!(error.contains("larger than 64 bytes"), "{}", error);
+    }
+
+    #[test]
+    fn reads_csv_with_quotes_newlines_and_bom() {
+        let source = std::str::from_utf8(include_bytes!("../../test-data/documents/knowledge.csv")).unwrap();
+        assert!(source.starts_with('\u{feff}'));
+        assert_eq!(
+            csv(source).unwrap(),
+            "name: Lamp, large; notes: Fits the \"north\" tower\nand the pier; price: 12\nname: Wick; price: 3"
+        );
+
+        assert!(csv("").is_err());
+    }
+
+    #[test]
+    fn flattens_nested_json() {
+        let text = json(include_bytes!("../../test-data/documents/knowledge.json")).unwrap();
+        let mut lines: Vec<&str> = text.lines().collect();
+        lines.sort_unstable();
+        assert_eq!(
+            lines,
+            [
+                "contact.email: keeper@example.com",
+                "plans[0].name: Basic",
+                "plans[0].price: 10",
+                "plans[1].features[0]: night watch",
+                "plans[1].features[1]: fog horn",
+                "plans[1].name: Pro",
+                "plans[1].price: 25",
+                "service: Lighthouse",
+            ]
+        );
+
+        assert_eq!(json(b"\"plain\"").unwrap(), "plain");
+        assert!(json(b"{").is_err());
+    }
+
+    #[test]
+    fn renders_markdown_tables() {
+        let source = std::str::from_utf8(include_bytes!("../../test-data/documents/knowledge.md")).unwrap();
+        assert_eq!(
+            markdown(source),
+            "Tide table\n\nDay | High | Low\nMon | 06:10 | 12:20\nTue | 06:55 | 13:05\n\nSee notes below."
+        );
+    }
+}
@@ -13,6 +13,10 @@ pub enum ApiError {
     Forbidden,
     #[error("Bad request: {0}")]
     BadRequest(String),
+    #[error("Payment required: {0}")]
+    PaymentRequired(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
     #[error("Internal error: {0}")]
     Internal(String),
     #[error("Database error: {0}")]
@@ -25,14 +29,18 @@ impl ResponseError for ApiError {
     fn error_response(&self) -> HttpResponse {
         use ApiError::*;
 
-        // Log the error
-        println!("Error occurred: {}", self);
+        match self {
+            Internal(_) | Sqlx(_) | Task(_) => tracing::error!(error = %self, "Request failed"),
+            _ => tracing::debug!(error = %self, "Request rejected"),
+        }
 
         let (msg, status) = match self {
             NotFound(m) => (m.as_str(), 404),
             Unauthorized => ("Unauthorized", 401),
             Forbidden => ("Forbidden", 403),
             BadRequest(m) => (m.as_str(), 400),
+            PaymentRequired(m) => (m.as_str(), 402),
+            Conflict(m) => (m.as_str(), 409),
             Internal(m) => (m.as_str(), 500),
             Sqlx(_) => ("Database error", 500),
             Task(_) => ("Task execution error", 500),
@@ -110,6 +118,12 @@ impl From<alith::ToolError> for ApiError {
     }
 }
 
+impl From<crate::lib::fetch::FetchError> for ApiError {
+    fn from(err: crate::lib::fetch::FetchError) -> Self {
+        ApiError::BadRequest(err.to_string())
+    }
+}
+
 impl From<ApiError> for alith::ToolError {
     fn from(err: ApiError) -> Self {
         alith::ToolError::Unknown(format!("API error: {}", err))
@@ -1,8 +1,10 @@
-use crate::lib::{error::ApiError, state::AppState};
+use crate::lib::db::{self, sessions};
+use crate::lib::{config, error::ApiError, logging, state::AppState};
 use crate::utils;
 use actix_web::{FromRequest, HttpRequest, web};
 use futures_util::future::{Ready, ready};
 use sqlx::FromRow;
+use tracing::Instrument;
 
 #[derive(Debug, Clone, FromRow)]
 pub struct AuthUser {
@@ -17,6 +19,52 @@ pub struct ApiCaller {
     pub project_uid: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct RequestMeta {
+    pub ip: String,
+    pub user_agent: String,
+}
+
+impl RequestMeta {
+    pub fn new(req: &HttpRequest) -> Self {
+        let user_agent = req
+            .headers()
+            .get("User-Agent")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("unknown")
+            .to_string();
+
+        RequestMeta {
+            ip: client_ip(req),
+            user_agent,
+        }
+    }
+}
+
+impl FromRequest for RequestMeta {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        ready(Ok(RequestMeta::new(req)))
+    }
+}
+
+/// The caller's address. Forwarding headers are only believed when the peer
+/// is one of `TRUSTED_PROXIES`, as anyone else can set them.
+pub fn client_ip(req: &HttpRequest) -> String {
+    let Some(peer) = req.peer_addr() else {
+        return "unknown".to_string();
+    };
+
+    if config::get().trusted_proxies.contains(&peer.ip()) {
+        if let Some(ip) = req.connection_info().realip_remote_addr() {
+            return ip.to_string();
+        }
+    }
+    peer.ip().to_string()
+}
+
 impl FromRequest for AuthUser {
     type Error = ApiError;
     type Future = Ready<Result<Self, Self::Error>>;
@@ -44,16 +92,13 @@ impl FromRequest for AuthUser {
         let token_header = token_header.to_string();
 
         let result = futures_executor::block_on(async move {
-            let token_from_db: Option<String> =
-                sqlx::query_scalar("SELECT token FROM sessions WHERE wallet_address = ?")
-                    .bind(&wallet_address)
-                    .fetch_optional(&db)
-                    .await
-                    .map_err(|_| ApiError::Internal("DB error".into()))?;
+            let token_from_db = sessions::token(&db, &wallet_address)
+                .await
+                .map_err(|_| ApiError::Internal("DB error".into()))?;
 
             match token_from_db {
                 Some(token) if token == token_header => {
-                    let user = sqlx::query_as::<_, AuthUser>(
+                    let user = db::query_as::<AuthUser>(
                         "SELECT wallet_address, created_at FROM accounts WHERE wallet_address = ?",
                     )
                     .bind(&wallet_address)
@@ -97,16 +142,13 @@ impl FromRequest for ApiCaller {
 
             // Check if this is a valid JWT session and get org/project permissions
             let jwt_result = futures_executor::block_on(async move {
-                let token_from_db: Option<String> =
-                    sqlx::query_scalar("SELECT token FROM sessions WHERE wallet_address = ?")
-                        .bind(&wallet_address)
-                        .fetch_optional(&db)
-                        .await
-                        .map_err(|_| ApiError::Internal("DB error".into()))?;
+                let token_from_db = sessions::token(&db, &wallet_address)
+                    .await
+                    .map_err(|_| ApiError::Internal("DB error".into()))?;
 
                 match token_from_db {
                     Some(token) if token == token_header => {
-                        let user = sqlx::query_as::<_, AuthUser>(
+                        let user = db::query_as::<AuthUser>(
                             "SELECT wallet_address, created_at FROM accounts WHERE wallet_address = ?",
                         )
                         .bind(&wallet_address)
@@ -165,11 +207,15 @@ impl FromRequest for ApiCaller {
                 let wallet_address = claims.sub.clone();
 
                 // Verify the user has permissions for the org/project
+                let span = tracing::debug_span!(
+                    "auth",
+                    method = "jwt",
+                    wallet_address = %wallet_address,
+                    org_uid = %org_uid_header,
+                    project_uid = %proj_uid_header,
+                );
                 let permission_result = futures_executor::block_on(async move {
-                    println!("Debug: JWT auth - wallet_address = {}", wallet_address);
-                    println!("Debug: JWT auth - org_uid = {}", org_uid_header);
-                    println!("Debug: JWT auth - project_uid = {}", proj_uid_header);
-                    let org_role: Option<String> = sqlx
+                    let org_role: Option<String> = db
                         ::query_scalar(
                             "SELECT CASE 
                             WHEN organizations.owner = ? THEN 'owner'
@@ -187,11 +233,11 @@ impl FromRequest for ApiCaller {
                         .bind(&wallet_address)
                         .fetch_optional(&db).await
                         .map_err(|e| {
-                            println!("DB Error - Organization role query: {:?}", e);
+                            tracing::error!(error = %e, "Organization role query failed");
                             ApiError::Internal("Failed to fetch organization role".into())
                         })?;
 
-                    let project_role: Option<String> = sqlx::query_scalar(
+                    let project_role: Option<String> = db::query_scalar(
                         "SELECT pm.role 
                              FROM project_members pm
                              JOIN projects p ON pm.project_id = p.id
@@ -202,7 +248,7 @@ impl FromRequest for ApiCaller {
                     .fetch_optional(&db)
                     .await
                     .map_err(|e| {
-                        println!("DB Error - Project role query: {:?}", e);
+                        tracing::error!(error = %e, "Project role query failed");
                         ApiError::Internal("Failed to fetch project role".into())
                     })?;
 
@@ -219,8 +265,8 @@ impl FromRequest for ApiCaller {
                         return Err(ApiError::Forbidden);
                     }
 
-                    let project_exists: Option<bool> = sqlx::query_scalar(
-                        "SELECT 1 
+                    let project_exists: Option<bool> = db::query_scalar(
+                        "SELECT TRUE 
                              FROM projects p
                              JOIN organizations o ON p.org_id = o.id
                              WHERE p.project_uid = ? AND o.organization_uid = ?",
@@ -230,7 +276,7 @@ impl FromRequest for ApiCaller {
                     .fetch_optional(&db)
                     .await
                     .map_err(|e| {
-                        println!("DB Error - Project exists query: {:?}", e);
+                        tracing::error!(error = %e, "Project exists query failed");
                         ApiError::Internal("Failed to verify project existence".into())
                     })?;
 
@@ -240,14 +286,14 @@ impl FromRequest for ApiCaller {
                         ));
                     }
 
-                    println!("Debug: JWT authentication successful!");
+                    tracing::debug!("JWT authentication succeeded");
 
                     Ok(ApiCaller {
                         wallet_address,
                         org_uid: org_uid_header,
                         project_uid: proj_uid_header,
                     })
-                });
+                }.instrument(span));
 
                 return ready(permission_result);
             }
@@ -313,55 +359,48 @@ impl FromRequest for ApiCaller {
         let wallet_address = parsed_api_key.address.clone();
         let api_key = api_key.to_string();
 
+        let span = tracing::debug_span!(
+            "auth",
+            method = "api_key",
+            wallet_address = %wallet_address,
+            org_uid = %org_uid_header,
+            project_uid = %proj_uid_header,
+            api_key = %logging::redact(&api_key),
+        );
         let result = futures_executor::block_on(async move {
-            println!("Debug: API Key auth - wallet_address = {}", wallet_address);
-            println!("Debug: API Key auth - org_uid = {}", org_uid_header);
-            println!("Debug: API Key auth - project_uid = {}", proj_uid_header);
-            println!(
-                "Debug: API Key auth - signature = {}",
-                parsed_api_key.signature
-            );
 
-            let api_key_timestamp: Option<i64> = sqlx::query_scalar(
-                "SELECT unixepoch(api_key_last_issued_at) FROM accounts WHERE wallet_address = ?",
-            )
-            .bind(&wallet_address)
-            .fetch_optional(&db)
-            .await
+            let api_key_timestamp = sessions::api_key_issued_at(&db, &wallet_address)
+                .await
             .map_err(|e| {
-                println!("DB Error 1 - API key timestamp query: {:?}", e);
+                tracing::error!(error = %e, "API key timestamp query failed");
                 ApiError::Internal("Failed to fetch API key timestamp".into())
             })?;
 
             let timestamp = match api_key_timestamp {
-                Some(ts) => {
-                    println!("Debug: Found timestamp = {}", ts);
-                    ts
-                }
+                Some(ts) => ts,
                 None => {
-                    println!(
-                        "Debug: No API key timestamp found for address: {}",
-                        wallet_address
-                    );
+                    tracing::debug!("No API key issued for wallet");
                     return Err(ApiError::Unauthorized);
                 }
             };
 
-            let private_key = std::env::var("MOCK_TEE_PVT_KEY")
-                .map_err(|_| ApiError::Internal("TEE private key not configured".into()))?;
+            let private_key = config::get()
+                .mock_tee_pvt_key
+                .as_ref()
+                .ok_or_else(|| ApiError::Internal("TEE private key not configured".into()))?;
 
-            let public_key = utils::derive_public_key_from_private(&private_key)
+            let public_key = utils::derive_public_key_from_private(private_key.expose())
                 .map_err(|_| ApiError::Internal("Failed to derive public key".into()))?;
 
             let is_valid = utils::verify_api_key(&public_key, &api_key, timestamp)
                 .map_err(|_| ApiError::Internal("Failed to verify API key".into()))?;
 
             if !is_valid {
-                println!("Debug: API key signature invalid");
+                tracing::debug!("API key signature invalid");
                 return Err(ApiError::Unauthorized);
             }
 
-            let org_role: Option<String> = sqlx
+            let org_role: Option<String> = db
                 ::query_scalar(
                     "SELECT CASE 
                     WHEN organizations.owner = ? THEN 'owner'
@@ -379,13 +418,12 @@ impl FromRequest for ApiCaller {
                 .bind(&wallet_address)
                 .fetch_optional(&db).await
                 .map_err(|e| {
-                    println!("DB Error 2 - Organization role query: {:?}", e);
+                    tracing::error!(error = %e, "Organization role query failed");
                     ApiError::Internal("Failed to fetch organization role".into())
                 })?;
 
-            println!("Debug: org_role = {:?}", org_role);
 
-            let project_role: Option<String> = sqlx::query_scalar(
+            let project_role: Option<String> = db::query_scalar(
                 "SELECT pm.role 
                      FROM project_members pm
                      JOIN projects p ON pm.project_id = p.id
@@ -396,11 +434,10 @@ impl FromRequest for ApiCaller {
             .fetch_optional(&db)
             .await
             .map_err(|e| {
-                println!("DB Error 3 - Project role query: {:?}", e);
+                tracing::error!(error = %e, "Project role query failed");
                 ApiError::Internal("Failed to fetch project role".into())
             })?;
 
-            println!("Debug: project_role = {:?}", project_role);
 
             let has_org_permission = match org_role.as_ref().map(|s| s.as_str()) {
                 Some("owner") | Some("admin") => true,
@@ -412,17 +449,20 @@ impl FromRequest for ApiCaller {
                 _ => false,
             };
 
-            println!(
-                "Debug: has_org_permission = {}, has_project_permission = {}",
-                has_org_permission, has_project_permission
+            tracing::debug!(
+                org_role = ?org_role,
+                project_role = ?project_role,
+                has_org_permission,
+                has_project_permission,
+                "Resolved API key permissions"
             );
 
             if !has_org_permission && !has_project_permission {
                 return Err(ApiError::Forbidden);
             }
 
-            let project_exists: Option<bool> = sqlx::query_scalar(
-                "SELECT 1 
+            let project_exists: Option<bool> = db::query_scalar(
+                "SELECT TRUE 
                      FROM projects p
                      JOIN organizations o ON p.org_id = o.id
                      WHERE p.project_uid = ? AND o.organization_uid = ?",
@@ -432,28 +472,25 @@ impl FromRequest for ApiCaller {
             .fetch_optional(&db)
             .await
             .map_err(|e| {
-                println!("DB Error 4 - Project exists query: {:?}", e);
+                tracing::error!(error = %e, "Project exists query failed");
                 ApiError::Internal("Failed to verify project existence".into())
             })?;
 
             if project_exists.is_none() {
-                println!(
-                    "Debug: Project {} not found in org {}",
-                    proj_uid_header, org_uid_header
-                );
+                tracing::debug!("Project not found in organization");
                 return Err(ApiError::BadRequest(
                     "Project not found or does not belong to organization".into(),
                 ));
             }
 
-            println!("Debug: API key authentication successful!");
+            tracing::debug!("API key authentication succeeded");
 
             Ok(ApiCaller {
                 wallet_address,
                 org_uid: org_uid_header,
                 project_uid: proj_uid_header,
             })
-        });
+        }.instrument(span));
 
         ready(result)
     }
@@ -0,0 +1,374 @@
+use crate::lib::config;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{CONTENT_TYPE, HeaderMap};
+use reqwest::redirect::Policy;
+use reqwest::{Method, RequestBuilder, StatusCode};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use thiserror::Error;
+use url::{Host, Url};
+
+/// Content types of web pages read as knowledge.
+pub const PAGE_TYPES: &[&str] = &["text/html", "application/xhtml+xml", "text/plain"];
+/// Content types of JSON APIs.
+pub const JSON_TYPES: &[&str] = &["application/json", "text/json"];
+
+#[derive(Debug, Error)]
+pub enum FetchError {
+    #[error("Invalid URL '{0}'")]
+    InvalidUrl(String),
+    #[error("Refusing to fetch {url}: {reason}")]
+    Blocked { url: String, reason: String },
+    #[error("Too many redirects fetching {url} (limit {limit})")]
+    TooManyRedirects { url: String, limit: usize },
+    #[error("Response from {url} is larger than {limit} bytes")]
+    TooLarge { url: String, limit: u64 },
+    #[error("Unexpected content type '{content_type}' from {url}")]
+    ContentType { url: String, content_type: String },
+    #[error("{url} answered {status}")]
+    Status { url: String, status: StatusCode },
+    #[error("Timed out fetching {0}")]
+    Timeout(String),
+    #[error("Failed to fetch {url}: {source}")]
+    Request {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+}
+
+/// A successful response, or a `304 Not Modified` with an empty body.
+pub struct Fetched {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+/// Whether `host` matches an entry of `patterns`, where `*.example.com` also
+/// matches subdomains.
+pub fn host_matches(patterns: &[String], host: &str) -> bool {
+    let host = host.to_lowercase();
+    patterns
+        .iter()
+        .any(|pattern| match pattern.strip_prefix("*.") {
+            Some(domain) => host == domain || host.ends_with(&format!(".{}", domain)),
+            None => host == *pattern,
+        })
+}
+
+/// False for loopback, private, link-local (cloud metadata), shared, reserved
+/// and other addresses that do not belong to the public internet.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, c, _] = v4.octets();
+            !(v4.is_unspecified()
+                || v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                || v4.is_multicast()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 192 && b == 0 && c == 0)
+                || (a == 198 && (b == 18 || b == 19))
+                || a >= 240)
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let s = v6.segments();
+            // NAT64 addresses reach whatever IPv4 address they embed
+            if s[0] == 0x64 && s[1] == 0xff9b {
+                let [.., hi, lo] = s;
+                return is_public(IpAddr::V4((u32::from(hi) << 16 | u32::from(lo)).into()));
+            }
+            // So do 6to4 addresses, through the 32 bits after the prefix
+            if s[0] == 0x2002 {
+                return is_public(IpAddr::V4((u32::from(s[1]) << 16 | u32::from(s[2])).into()));
+            }
+            // Teredo tunnels to an obfuscated address, never a destination of ours
+            if s[0] == 0x2001 && s[1] == 0 {
+                return false;
+            }
+            !(v6.is_unspecified()
+                || v6.is_loopback()
+                || v6.is_multicast()
+                || (s[0] & 0xfe00) == 0xfc00
+                || (s[0] & 0xffc0) == 0xfe80
+                || (s[0] == 0x2001 && s[1] == 0x0db8))
+        }
+    }
+}
+
+/// Checks a URL against the scheme, the host lists and, for IP literals, the
+/// address ranges. Names are checked again once resolved.
+fn check_url(url: &Url) -> Result<(), FetchError> {
+    let blocked = |reason: String| FetchError::Blocked {
+        url: url.to_string(),
+        reason,
+    };
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(blocked(format!("scheme '{}' is not allowed", url.scheme())));
+    }
+
+    let config = config::get();
+    let host = match url.host() {
+        Some(Host::Domain(domain)) => domain.to_string(),
+        Some(Host::Ipv4(ip)) => ip.to_string(),
+        Some(Host::Ipv6(ip)) => ip.to_string(),
+        None => return Err(blocked("no host".to_string())),
+    };
+    if host_matches(&config.fetch_denied_hosts, &host) {
+        return Err(blocked(format!("host '{}' is in FETCH_DENIED_HOSTS", host)));
+    }
+    if !config.fetch_allowed_hosts.is_empty() && !host_matches(&config.fetch_allowed_hosts, &host) {
+        return Err(blocked(format!(
+            "host '{}' is not in FETCH_ALLOWED_HOSTS",
+            host
+        )));
+    }
+
+    let ip = match url.host() {
+        Some(Host::Ipv4(ip)) => Some(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => Some(IpAddr::V6(ip)),
+        _ => None,
+    };
+    match ip {
+        Some(ip) if !is_public(ip) => Err(blocked(format!("{} is not a public address", ip))),
+        _ => Ok(()),
+    }
+}
+
+/// Resolves names to their public addresses only, so a name cannot point
+/// requests at internal services, including by changing between check and use.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(public_addrs(name.as_str().to_string()))
+    }
+}
+
+async fn public_addrs(host: String) -> Result<Addrs, Box<dyn std::error::Error + Send + Sync>> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+        .await?
+        .filter(|addr| is_public(addr.ip()))
+        .collect();
+
+    if addrs.is_empty() {
+        return Err(Box::new(FetchError::Blocked {
+            reason: format!("'{}' does not resolve to a public address", host),
+            url: host,
+        }));
+    }
+    Ok(Box::new(addrs.into_iter()))
+}
+
+/// HTTP client for URLs that come from users, creators or products.
+pub struct Fetcher {
+    client: reqwest::Client,
+}
+
+impl Fetcher {
+    fn new(max_redirects: usize) -> Self {
+        let config = config::get();
+        let policy = Policy::custom(move |attempt| {
+            if attempt.previous().len() > max_redirects {
+                let url = attempt.previous()[0].to_string();
+                return attempt.error(FetchError::TooManyRedirects {
+                    url,
+                    limit: max_redirects,
+                });
+            }
+            match check_url(attempt.url()) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(e),
+            }
+        });
+
+        let client = reqwest::Client::builder()
+            .dns_resolver(Arc::new(PublicResolver))
+            .redirect(policy)
+            .connect_timeout(Duration::from_secs(config.fetch_connect_timeout_secs))
+            .read_timeout(Duration::from_secs(config.fetch_read_timeout_secs))
+            .timeout(Duration::from_secs(config.fetch_timeout_secs))
+            .build()
+            .expect("fetch client configuration is valid");
+
+        Fetcher { client }
+    }
+
+    pub fn get(&self, url: &str) -> Result<RequestBuilder, FetchError> {
+        self.request(Method::GET, url)
+    }
+
+    pub fn post(&self, url: &str) -> Result<RequestBuilder, FetchError> {
+        self.request(Method::POST, url)
+    }
+
+    pub fn request(&self, method: Method, url: &str) -> Result<RequestBuilder, FetchError> {
+        let parsed = Url::parse(url).map_err(|_| FetchError::InvalidUrl(url.to_string()))?;
+        check_url(&parsed)?;
+        Ok(self.client.request(method, parsed))
+    }
+
+    /// Sends a request built by this fetcher and reads the body up to
+    /// `FETCH_MAX_BYTES`. An empty `accept` allows any content type.
+    pub async fn send(&self, req: RequestBuilder, accept: &[&str]) -> Result<Fetched, FetchError> {
+        let (client, req) = req.build_split();
+        let req = req.map_err(|e| FetchError::InvalidUrl(e.to_string()))?;
+        let url = req.url().to_string();
+
+        let mut res = client
+            .execute(req)
+            .await
+            .map_err(|e| request_error(&url, e))?;
+
+        let status = res.status();
+        if status == StatusCode::NOT_MODIFIED {
+            return Ok(Fetched {
+                status,
+                headers: res.headers().clone(),
+                body: Vec::new(),
+            });
+        }
+        if !status.is_success() {
+            return Err(FetchError::Status { url, status });
+        }
+
+        if !accept.is_empty() {
+            let content_type = res
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            let essence = content_type
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_lowercase();
+            if !accept.contains(&essence.as_str()) {
+                return Err(FetchError::ContentType { url, content_type });
+            }
+        }
+
+        let limit = config::get().fetch_max_bytes;
+        if res.content_length().is_some_and(|len| len > limit) {
+            return Err(FetchError::TooLarge { url, limit });
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = res.chunk().await.map_err(|e| request_error(&url, e))? {
+            if (body.len() + chunk.len()) as u64 > limit {
+                return Err(FetchError::TooLarge { url, limit });
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(Fetched {
+            status,
+            headers: res.headers().clone(),
+            body,
+        })
+    }
+}
+
+/// Surfaces blocks raised inside the resolver or the redirect policy instead
+/// of reqwest's generic wrapper.
+fn request_error(url: &str, e: reqwest::Error) -> FetchError {
+    let mut source = std::error::Error::source(&e);
+    while let Some(err) = source {
+        match err.downcast_ref::<FetchError>() {
+            Some(FetchError::Blocked { url, reason }) => {
+                return FetchError::Blocked {
+                    url: url.clone(),
+                    reason: reason.clone(),
+                };
+            }
+            Some(FetchError::TooManyRedirects { url, limit }) => {
+                return FetchError::TooManyRedirects {
+                    url: url.clone(),
+                    limit: *limit,
+                };
+            }
+            _ => source = err.source(),
+        }
+    }
+
+    if e.is_timeout() {
+        FetchError::Timeout(url.to_string())
+    } else {
+        FetchError::Request {
+            url: url.to_string(),
+            source: e,
+        }
+    }
+}
+
+/// Follows up to `FETCH_MAX_REDIRECTS` redirects.
+pub fn client() -> &'static Fetcher {
+    static CLIENT: OnceLock<Fetcher> = OnceLock::new();
+    CLIENT.get_or_init(|| Fetcher::new(config::get().fetch_max_redirects))
+}
+
+/// For endpoints the server is configured with, such as the block explorer and
+/// the embeddings API. Those may be local, so addresses are not checked, but the
+/// fetch timeouts and redirect limit still apply.
+pub fn trusted_client() -> &'static reqwest::Client {
+    static TRUSTED: OnceLock<reqwest::Client> = OnceLock::new();
+    TRUSTED.get_or_init(|| {
+        let config = config::get();
+        reqwest::Client::builder()
+            .redirect(Policy::limited(config.fetch_max_redirects))
+            .connect_timeout(Duration::from_secs(config.fetch_connect_timeout_secs))
+            .read_timeout(Duration::from_secs(config.fetch_read_timeout_secs))
+            .timeout(Duration::from_secs(config.fetch_timeout_secs))
+            .build()
+            .expect("fetch client configuration is valid")
+    })
+}
+
+/// Never follows redirects, for requests whose destination is pinned further,
+/// such as tool calls limited to `TOOL_ALLOWED_HOSTS`.
+pub fn direct() -> &'static Fetcher {
+    static DIRECT: OnceLock<Fetcher> = OnceLock::new();
+    DIRECT.get_or_init(|| Fetcher::new(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public(ip: &str) -> bool {
+        is_public(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn refuses_internal_addresses() {
+        for ip in ["127.0.0.1", "10.1.2.3", "169.254.169.254", "100.64.0.1", "::1", "fd00::1", "fe80::1"] {
+            assert!(!public(ip), "{}", ip);
+        }
+        for ip in ["93.184.216.34", "2606:4700::1111"] {
+            assert!(public(ip), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn judges_ipv6_by_the_ipv4_address_it_embeds() {
+        // Mapped, NAT64 and 6to4 forms of 169.254.169.254 and 93.184.216.34
+        for ip in ["::ffff:169.254.169.254", "64:ff9b::a9fe:a9fe", "2002:a9fe:a9fe::1", "2002:7f00:1::"] {
+            assert!(!public(ip), "{}", ip);
+        }
+        for ip in ["::ffff:93.184.216.34", "64:ff9b::5db8:d822", "2002:5db8:d822::1"] {
+            assert!(public(ip), "{}", ip);
+        }
+        assert!(!public("2001:0:4136:e378:8000:63bf:3fff:fdd2"));
+    }
+}
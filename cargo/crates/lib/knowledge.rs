@@ -0,0 +1,380 @@
+use crate::lib::content_uri::ContentUri;
+use crate::lib::fetch::{self, FetchError};
+use crate::lib::product_kinds::ProductKind;
+use crate::lib::promptset::PromptSet;
+use crate::lib::tools::HttpToolSpec;
+use crate::lib::{config, error::ApiError, metrics};
+use alith::data::crypto::{decrypt, encrypt};
+use reqwest::StatusCode;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub fn ensure_protocol(url_str: &str) -> String {
+    if url_str.starts_with("http://") || url_str.starts_with("https://") {
+        url_str.to_string()
+    } else {
+        format!("https://{}", url_str)
+    }
+}
+
+/// A product's decrypted payload, parsed by the handler of its kind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "data", rename_all = "snake_case")]
+pub enum ProductContent {
+    Text(String),
+    Html {
+        html: String,
+        url: String,
+    },
+    /// A web page, fetched and revalidated by the cache on its own validators.
+    Page {
+        html: String,
+        url: String,
+    },
+    Prompts(PromptSet),
+    Tool(HttpToolSpec),
+}
+
+/// Validators from the last successful fetch of a resource.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+enum Fetched {
+    NotModified,
+    Body {
+        bytes: Vec<u8>,
+        validators: Validators,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Entry {
+    uri: String,
+    kind: ProductKind,
+    /// SHA-256 of the encrypted payload the content was parsed from.
+    content_hash: String,
+    validators: Validators,
+    /// Validators of the page behind [`ProductContent::Page`] content.
+    page_validators: Validators,
+    /// Unix seconds of the last check against the origin.
+    checked_at: i64,
+    content: ProductContent,
+}
+
+/// Parsed product knowledge keyed by product address, so completions skip the
+/// download, decrypt and parse unless the payload changed.
+///
+/// Entries are served for the TTL, then revalidated with `If-None-Match` /
+/// `If-Modified-Since`. Entries for `ipfs://`, `ar://` and `data:` URIs are
+/// pinned, their content cannot change. A changed body is only decrypted again when its hash
+/// differs. An entry is dropped as soon as the product's URI or kind no
+/// longer matches what it was cached for.
+pub struct KnowledgeCache {
+    entries: Mutex<HashMap<String, Entry>>,
+    ttl: Duration,
+    dir: Option<PathBuf>,
+}
+
+/// Conditional GET through the hardened fetcher. `accept` limits the content
+/// types of a changed body.
+async fn fetch(
+    url: &str,
+    validators: Option<&Validators>,
+    accept: &[&str],
+) -> Result<Fetched, FetchError> {
+    let fetcher = fetch::client();
+    let mut req = fetcher.get(url)?;
+    if let Some(validators) = validators {
+        if let Some(etag) = &validators.etag {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            req = req.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let res = fetcher.send(req, accept).await?;
+    if res.status == StatusCode::NOT_MODIFIED {
+        return Ok(Fetched::NotModified);
+    }
+
+    let header = |name| {
+        res.headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let validators = Validators {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    };
+
+    Ok(Fetched::Body {
+        bytes: res.body,
+        validators,
+    })
+}
+
+fn content_hash(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+impl KnowledgeCache {
+    pub fn new(ttl: Duration, dir: Option<PathBuf>) -> Self {
+        let dir = dir.filter(|dir| match std::fs::create_dir_all(dir) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!(dir = %dir.display(), error = %e, "Knowledge cache directory unusable, caching in memory only");
+                false
+            }
+        });
+
+        KnowledgeCache {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+            dir,
+        }
+    }
+
+    pub fn invalidate(&self, address: &str) {
+        let key = address.to_lowercase();
+        self.entries.lock().unwrap().remove(&key);
+        if let Some(path) = self.path(&key) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    /// Content of a product, fetching only what changed since it was cached.
+    pub async fn get(
+        &self,
+        address: &str,
+        uri: &str,
+        kind: ProductKind,
+    ) -> Result<ProductContent, ApiError> {
+        let key = address.to_lowercase();
+        let cached = match self.lookup(&key) {
+            Some(entry) if entry.uri == uri && entry.kind == kind => Some(entry),
+            Some(_) => {
+                tracing::debug!(address, "Product changed, dropping cached knowledge");
+                self.invalidate(&key);
+                None
+            }
+            None => None,
+        };
+
+        let now = chrono::Utc::now().timestamp();
+        if let Some(entry) = &cached {
+            // Content behind an immutable URI is pinned, only pages it links to expire
+            let pinned = ContentUri::parse(&entry.uri).is_ok_and(|source| source.is_immutable())
+                && !matches!(entry.content, ProductContent::Page { .. });
+            if pinned || now - entry.checked_at < self.ttl.as_secs() as i64 {
+                metrics::inc("haithe_knowledge_cache_total", &[("result", "hit")]);
+                return Ok(entry.content.clone());
+            }
+        }
+
+        let mut entry = self.refresh(uri, kind, cached).await?;
+        entry.checked_at = now;
+
+        let content = entry.content.clone();
+        self.store(&key, entry);
+        Ok(content)
+    }
+
+    async fn refresh(
+        &self,
+        uri: &str,
+        kind: ProductKind,
+        cached: Option<Entry>,
+    ) -> Result<Entry, ApiError> {
+        let category = kind.category();
+        let source = ContentUri::parse(uri)?;
+        let cached = match cached {
+            Some(mut entry) if source.is_immutable() => {
+                self.revalidate_page(&mut entry).await?;
+                metrics::inc("haithe_knowledge_cache_total", &[("result", "revalidated")]);
+                return Ok(entry);
+            }
+            cached => cached,
+        };
+
+        let started = Instant::now();
+        let fetched = match &source {
+            ContentUri::Http(url) => {
+                fetch(url, cached.as_ref().map(|entry| &entry.validators), &[])
+                    .await
+                    .map_err(|e| {
+                        ApiError::BadRequest(format!("Failed to fetch product data: {}", e))
+                    })
+            }
+            source => source.resolve().await.map(|bytes| Fetched::Body {
+                bytes,
+                validators: Validators::default(),
+            }),
+        };
+        let fetched = fetched.inspect_err(|_| {
+            metrics::inc(
+                "haithe_knowledge_fetch_failures_total",
+                &[("category", category), ("stage", "fetch")],
+            );
+        })?;
+        metrics::observe(
+            "haithe_knowledge_fetch_duration_seconds",
+            &[("category", category), ("stage", "fetch")],
+            started.elapsed(),
+        );
+
+        let mut entry = match (fetched, cached) {
+            (Fetched::NotModified, Some(entry)) => entry,
+            (Fetched::Body { bytes, validators }, Some(entry))
+                if content_hash(&bytes) == entry.content_hash =>
+            {
+                Entry {
+                    validators,
+                    ..entry
+                }
+            }
+            (Fetched::Body { bytes, validators }, _) => {
+                metrics::inc("haithe_knowledge_cache_total", &[("result", "miss")]);
+                return self.parse(uri, kind, bytes, validators).await;
+            }
+            (Fetched::NotModified, None) => {
+                return Err(ApiError::BadRequest(format!(
+                    "Product data answered 304 to an unconditional request - URI: {}",
+                    uri
+                )));
+            }
+        };
+
+        self.revalidate_page(&mut entry).await?;
+
+        metrics::inc("haithe_knowledge_cache_total", &[("result", "revalidated")]);
+        Ok(entry)
+    }
+
+    async fn parse(
+        &self,
+        uri: &str,
+        kind: ProductKind,
+        encrypted: Vec<u8>,
+        validators: Validators,
+    ) -> Result<Entry, ApiError> {
+        let category = kind.category();
+        let started = Instant::now();
+        let decrypted = match decrypt(&encrypted, config::get().tee_secret.expose().to_string()) {
+            Ok(data) => data,
+            Err(e) => {
+                metrics::inc(
+                    "haithe_knowledge_fetch_failures_total",
+                    &[("category", category), ("stage", "decrypt")],
+                );
+                return Err(e.into());
+            }
+        };
+        metrics::observe(
+            "haithe_knowledge_fetch_duration_seconds",
+            &[("category", category), ("stage", "decrypt")],
+            started.elapsed(),
+        );
+        tracing::debug!(
+            bytes = decrypted.len(),
+            "Fetched and decrypted product data"
+        );
+
+        let mut page_validators = Validators::default();
+        let mut content = kind.handler().decode(decrypted)?;
+        if let ProductContent::Page { html, url } = &mut content {
+            if let Fetched::Body { bytes, validators } = self.fetch_page(url, None).await? {
+                *html = String::from_utf8_lossy(&bytes).into_owned();
+                page_validators = validators;
+            }
+        }
+
+        Ok(Entry {
+            uri: uri.to_string(),
+            kind,
+            content_hash: content_hash(&encrypted),
+            validators,
+            page_validators,
+            checked_at: 0,
+            content,
+        })
+    }
+
+    async fn fetch_page(
+        &self,
+        url: &str,
+        validators: Option<&Validators>,
+    ) -> Result<Fetched, ApiError> {
+        fetch(url, validators, fetch::PAGE_TYPES)
+            .await
+            .map_err(|e| ApiError::BadRequest(format!("Failed to fetch URL content: {}", e)))
+    }
+
+    async fn revalidate_page(&self, entry: &mut Entry) -> Result<(), ApiError> {
+        let ProductContent::Page { html, url } = &mut entry.content else {
+            return Ok(());
+        };
+
+        if let Fetched::Body { bytes, validators } =
+            self.fetch_page(url, Some(&entry.page_validators)).await?
+        {
+            *html = String::from_utf8_lossy(&bytes).into_owned();
+            entry.page_validators = validators;
+        }
+
+        Ok(())
+    }
+
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.bin", key)))
+    }
+
+    fn lookup(&self, key: &str) -> Option<Entry> {
+        if let Some(entry) = self.entries.lock().unwrap().get(key) {
+            return Some(entry.clone());
+        }
+
+        let entry = self.load(key)?;
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), entry.clone());
+        Some(entry)
+    }
+
+    fn load(&self, key: &str) -> Option<Entry> {
+        let bytes = std::fs::read(self.path(key)?).ok()?;
+        let plain = decrypt(&bytes, config::get().tee_secret.expose().to_string())
+            .map_err(|e| tracing::warn!(key, error = %e, "Failed to decrypt cached knowledge"))
+            .ok()?;
+        serde_json::from_slice(&plain).ok()
+    }
+
+    fn store(&self, key: &str, entry: Entry) {
+        if let Some(path) = self.path(key) {
+            let result = serde_json::to_vec(&entry)
+                .map_err(|e| e.to_string())
+                .and_then(|plain| {
+                    encrypt(&plain, config::get().tee_secret.expose().to_string())
+                        .map_err(|e| e.to_string())
+                })
+                .and_then(|encrypted| std::fs::write(&path, encrypted).map_err(|e| e.to_string()));
+            if let Err(e) = result {
+                tracing::warn!(key, error = %e, "Failed to persist cached knowledge");
+            }
+        }
+
+        self.entries.lock().unwrap().insert(key.to_string(), entry);
+    }
+}
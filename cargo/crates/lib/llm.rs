@@ -1,22 +1,19 @@
-use crate::lib::{contracts, error::ApiError, models, state::AppState};
-use alith::data::crypto::decrypt;
-use alith::{
-    Agent, Chat, HtmlKnowledge, Knowledge, PdfFileKnowledge, SearchTool, StringKnowledge,
-    WindowBufferMemory,
+use crate::lib::db::{self, orgs, products, projects, usage};
+use crate::lib::product_kinds::{AgentInputs, Billing, ProductKind, ProductRef};
+use crate::lib::promptset;
+use crate::lib::models::Model;
+use crate::lib::retrieval::{self, Citation, Embedder};
+use crate::lib::tools::HttpTool;
+use crate::lib::{
+    budget, config, contracts, error::ApiError, metrics, models, routing, state::AppState,
+    template, webhooks,
 };
+use alith::{Agent, Chat, Knowledge, LLM, SearchTool, StringKnowledge, WindowBufferMemory};
 use ethers::abi::Address;
-use serde_json::{Value, json};
-use std::io::Cursor;
+use serde_json::{Map, Value, json};
 use std::sync::Arc;
-use url::Url;
-
-fn ensure_protocol(url_str: &str) -> String {
-    if url_str.starts_with("http://") || url_str.starts_with("https://") {
-        url_str.to_string()
-    } else {
-        format!("https://{}", url_str)
-    }
-}
+use std::time::{Duration, Instant};
+use tracing::Instrument;
 
 #[derive(Debug)]
 pub struct LlmResponseParams {
@@ -26,6 +23,16 @@ pub struct LlmResponseParams {
     pub n: u32,
     pub org_uid: String,
     pub project_uid: String,
+    pub wallet_address: Option<String>,
+    /// Fills `{{variable}}` placeholders in prompt products, over the project's
+    /// prompt variables.
+    pub metadata: Map<String, Value>,
+    /// Trigger invocation the completion runs for, marked charged before the
+    /// first payment so it is never run again.
+    pub invocation_id: Option<i64>,
+    /// Longest the models and tools may take to answer. Payments start only
+    /// once they have, and are never cut short.
+    pub timeout: Option<Duration>,
 }
 
 #[derive(Debug)]
@@ -34,54 +41,71 @@ pub struct LlmResponse {
     pub total_cost: u64,
     pub current_expenditure: u64,
     pub prompt_tokens: u64,
+    pub usage_id: i64,
+    /// The model the completion was charged for, which answered unless the
+    /// requested model failed.
+    pub model: String,
+    /// Knowledge chunks the agent was given, numbered as it was told to cite them.
+    pub citations: Vec<Citation>,
 }
 
+#[tracing::instrument(
+    skip_all,
+    fields(
+        org_uid = %params.org_uid,
+        project_uid = %params.project_uid,
+        model = %params.model,
+        n = params.n,
+    )
+)]
 pub async fn generate_llm_response(
     params: LlmResponseParams,
     state: &AppState,
 ) -> Result<LlmResponse, ApiError> {
+    let deadline = params.timeout.map(|timeout| tokio::time::Instant::now() + timeout);
     let models = models::get_models();
 
-    let org_id: i64 = sqlx::query_scalar("SELECT id FROM organizations WHERE organization_uid = ?")
-        .bind(&params.org_uid)
-        .fetch_one(&state.db)
-        .await?;
+    let org_id = orgs::id_by_uid(&state.db, &params.org_uid)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Organization not found".to_string()))?;
 
-    let project_id: i64 = sqlx::query_scalar("SELECT id FROM projects WHERE project_uid = ?")
-        .bind(&params.project_uid)
-        .fetch_one(&state.db)
-        .await?;
+    let project_id = projects::id_by_uid(&state.db, &params.project_uid)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Project not found".to_string()))?;
 
-    let (search_enabled, memory_enabled): (bool, bool) = sqlx::query_as::<_, (bool, bool)>(
-        "SELECT search_enabled, memory_enabled FROM projects WHERE id = ?",
-    )
-    .bind(project_id)
-    .fetch_one(&state.db)
-    .await?;
+    let (search_enabled, memory_enabled) = projects::features(&state.db, project_id).await?;
+    let agent_config = projects::agent_config(&state.db, project_id).await?;
 
-    let enabled_models: Vec<u64> =
-        sqlx::query_scalar::<_, u64>("SELECT model_id FROM org_model_enrollments WHERE org_id = ?")
-            .bind(org_id)
-            .fetch_all(&state.db)
-            .await?;
+    let enabled_models = orgs::enrolled_model_ids(&state.db, org_id).await?;
 
-    let model_info = models.iter().find(|m| m.name == params.model);
-    let model_id = match model_info {
-        Some(model) => model.id,
-        None => return Err(ApiError::BadRequest("Invalid model".to_string())),
-    };
+    let requested = models
+        .iter()
+        .find(|m| m.name == params.model)
+        .ok_or_else(|| ApiError::BadRequest("Invalid model".to_string()))?;
 
-    if !enabled_models.contains(&model_id) {
+    if !enabled_models.contains(&requested.id) {
         return Err(ApiError::Forbidden);
     }
 
+    let retry = RetryPolicy {
+        max_retries: agent_config.max_retries.clamp(0, MAX_RETRIES) as u32,
+        backoff: Duration::from_millis(agent_config.retry_backoff_ms.max(0) as u64),
+    };
+    let candidates = routing::candidates(
+        &models,
+        requested,
+        &agent_config.fallback_model_ids(),
+        &enabled_models,
+        agent_config.routing_strategy.parse()?,
+    );
+
     if params.n > 5 {
         return Err(ApiError::BadRequest(
             "n must be less than or equal to 5".to_string(),
         ));
     }
 
-    let org_address: String = sqlx::query_scalar("SELECT address FROM organizations WHERE id = ?")
+    let org_address: String = db::query_scalar("SELECT address FROM organizations WHERE id = ?")
         .bind(org_id)
         .fetch_one(&state.db)
         .await?;
@@ -105,14 +129,7 @@ pub async fn generate_llm_response(
         .await
         .map_err(|e| ApiError::Internal(format!("Failed to call contract method: {}", e)))?;
 
-    let enabled_products_for_project: Vec<String> = sqlx::query_scalar::<_, String>(
-        "SELECT p.address FROM products p 
-             JOIN project_products_enabled ppe ON p.id = ppe.product_id 
-             WHERE ppe.project_id = ?",
-    )
-    .bind(project_id)
-    .fetch_all(&state.db)
-    .await?;
+    let enabled_products_for_project = products::enabled_addresses(&state.db, project_id).await?;
 
     let enabled_products: Vec<String> = enabled_products_for_organization
         .iter()
@@ -137,212 +154,215 @@ pub async fn generate_llm_response(
             enabled_products
         };
 
-    let llm = models::resolve_model(&params.model);
-    let mut knowledges: Vec<Box<dyn Knowledge>> = vec![Box::new(StringKnowledge::new(
-        "You are an AI assistant integrated with the Haithe platform. You can use the attached knowledge to answer context aware questions when the user asks about them, else you can answer in general.",
-    ))];
+    // A project's own system prompt replaces the generic one
+    let mut knowledges: Vec<Box<dyn Knowledge>> = Vec::new();
+    if agent_config.system_prompt.is_none() {
+        knowledges.push(Box::new(StringKnowledge::new(
+            "You are an AI assistant integrated with the Haithe platform. You can use the attached knowledge to answer context aware questions when the user asks about them, else you can answer in general.",
+        )));
+    }
 
-    let mut preamble = String::new();
-    let mut total_cost = models
-        .iter()
-        .find(|m| m.name == params.model)
-        .map_or(0, |m| m.price_per_call) as u64;
+    let mut inputs = AgentInputs::default();
+    let mut total_cost: u64 = 0;
 
     let mut product_payments: Vec<(String, String, u64)> = Vec::new();
+    // Most a completion can owe tool products, held against budgets up front
+    let mut tool_cost_limit: u64 = 0;
 
     for p in final_enabled_products {
-        let (uri, _encrypted_key, _price_per_call, category, creator): (String, String, i64, String, String) =
-            sqlx::query_as::<_, (String, String, i64, String, String)>("SELECT uri, encrypted_key, price_per_call, category, creator FROM products WHERE address = ?")
+        let (product_id, product_name, uri, _encrypted_key, _price_per_call, category, creator): (i64, String, String, String, i64, String, String) =
+            db::query_as::<(i64, String, String, String, i64, String, String)>("SELECT id, name, uri, encrypted_key, price_per_call, category, creator FROM products WHERE address = ?")
                 .bind(&p)
                 .fetch_one(&state.db)
                 .await?;
 
+        // Products of unknown kinds contribute nothing, so they are not charged for
+        let kind = match category.parse::<ProductKind>() {
+            Ok(kind) => kind,
+            Err(_) => {
+                tracing::warn!(address = %p, category = %category, "Skipping product of unknown category");
+                continue;
+            }
+        };
+
+        let handler = kind.handler();
         let product_cost = _price_per_call as u64;
-        total_cost += product_cost;
-        product_payments.push((p.clone(), creator, product_cost));
+        match handler.billing() {
+            Billing::PerCompletion => {
+                total_cost += product_cost;
+                product_payments.push((p.clone(), creator.clone(), product_cost));
+            }
+            Billing::PerInvocation => {
+                tool_cost_limit = tool_cost_limit.saturating_add(
+                    product_cost
+                        .saturating_mul(config::get().tool_max_calls)
+                        .saturating_mul(params.n as u64),
+                );
+            }
+        }
 
         if uri.is_empty() {
             return Err(ApiError::BadRequest("Product URI is empty".to_string()));
         }
 
-        let uri_with_protocol = ensure_protocol(&uri);
-        let parsed_uri = url::Url::parse(&uri_with_protocol).map_err(|e| {
-            ApiError::BadRequest(format!("Invalid URI format: {} - URI: {}", e, uri))
-        })?;
+        let product = ProductRef {
+            id: product_id,
+            address: p.clone(),
+            name: product_name,
+            uri,
+            creator,
+            price_per_call: product_cost,
+        };
+        let content = handler
+            .fetch(&state.knowledge_cache, &product)
+            .instrument(tracing::info_span!("product", address = %p, category = %category))
+            .await?;
+        handler.attach(&product, content, &mut inputs);
+    }
 
-        let response = reqwest::get(parsed_uri).await.map_err(|e| {
-            ApiError::BadRequest(format!(
-                "Failed to fetch product data: {} - URI: {}",
-                e, uri
-            ))
-        })?;
+    let mut variables: Map<String, Value> =
+        serde_json::from_str(&projects::prompt_variables(&state.db, project_id).await?)
+            .unwrap_or_default();
+    variables.extend(params.metadata.clone());
+    let mut preamble = String::new();
+    if let Some(system_prompt) = &agent_config.system_prompt {
+        preamble.push_str(&template::render(system_prompt, &Value::Object(variables.clone())));
+        preamble.push('\n');
+    }
+    preamble.push_str(&promptset::preamble(&inputs.prompt_sets, &variables)?);
 
-        let encrypted_data = response
-            .bytes()
-            .await
-            .map_err(|e| ApiError::BadRequest(format!("Failed to read response bytes: {}", e)))?;
-        let encrypted_bytes: Vec<u8> = encrypted_data.to_vec();
-
-        let decrypted_data = decrypt(&encrypted_bytes, std::env::var("TEE_SECRET")?)?;
-
-        if category.starts_with("knowledge") {
-            if category == "knowledge:text" {
-                knowledges.push(Box::new(StringKnowledge::new(String::from_utf8(
-                    decrypted_data,
-                )?)));
-            } else if category == "knowledge:html" {
-                let html = String::from_utf8(decrypted_data)?;
-                let default_url = Url::parse("https://haithe.ai").unwrap();
-                knowledges.push(Box::new(HtmlKnowledge::new(
-                    Cursor::new(html),
-                    default_url,
-                    false,
-                )));
-            } else if category == "knowledge:pdf" {
-                let pdf_content = String::from_utf8(decrypted_data)?;
-                knowledges.push(Box::new(PdfFileKnowledge::new(pdf_content)));
-            } else if category == "knowledge:url" {
-                let url_string = String::from_utf8(decrypted_data)?;
-
-                if url_string.is_empty() {
-                    return Err(ApiError::BadRequest("URL string is empty".to_string()));
-                }
+    let prompt = transcript(&params.messages);
 
-                let url_with_protocol = ensure_protocol(&url_string);
-                let url = Url::parse(&url_with_protocol)
-                    .map_err(|e| ApiError::BadRequest(format!("Invalid URL: {}", e)))?;
-
-                let html = reqwest::get(&url_with_protocol)
-                    .await
-                    .map_err(|e| {
-                        ApiError::BadRequest(format!("Failed to fetch URL content: {}", e))
-                    })?
-                    .text()
-                    .await
-                    .map_err(|e| {
-                        ApiError::BadRequest(format!("Failed to read URL response: {}", e))
-                    })?;
-
-                knowledges.push(Box::new(HtmlKnowledge::new(Cursor::new(html), url, false)));
-            }
-        } else if category == "promptset" {
-            let prompts: Vec<String> = serde_json::from_slice(&decrypted_data)
-                .map_err(|e| ApiError::BadRequest(format!("Failed to parse prompts: {}", e)))?;
-            for prompt in prompts {
-                preamble.push_str(&prompt);
-                preamble.push('\n');
-            }
-        }
+    // Only the knowledge chunks closest to the prompt are handed to the agent
+    let embedder = Embedder::from_config();
+    for document in &inputs.documents {
+        retrieval::index(&state.db, &embedder, document).await?;
+    }
+    let citations = retrieval::retrieve(
+        &state.db,
+        &embedder,
+        &inputs.documents,
+        &prompt,
+        config::get().rag_top_k,
+    )
+    .await?;
+    if !citations.is_empty() {
+        knowledges.push(Box::new(StringKnowledge::new(retrieval::context(&citations))));
     }
 
-    for (_product_address, creator_address, cost) in product_payments {
-        if cost > 0 {
-            let formatted_organization_address: Address = org_address
-                .parse()
-                .map_err(|_| ApiError::BadRequest("Invalid organization address format".into()))?;
+    // Nothing is paid until a model answers, but budgets must cover the
+    // dearest model the completion may fall back to
+    let model_cost_limit = candidates.iter().map(|m| m.price_per_call).max().unwrap_or(0);
+    budget::enforce(
+        &state.db,
+        org_id,
+        project_id,
+        total_cost
+            .saturating_add(model_cost_limit)
+            .saturating_add(tool_cost_limit),
+    )
+    .await?;
 
-            let orchestrator_contract =
-                contracts::get_contract_with_wallet("HaitheOrchestrator", None).await?;
+    let mut temperature = params.temperature;
+    if let Some(min) = agent_config.min_temperature {
+        temperature = temperature.max(min);
+    }
+    if let Some(max) = agent_config.max_temperature {
+        temperature = temperature.min(max);
+    }
 
-            let creator_addr: Address = creator_address
-                .parse()
-                .map_err(|_| ApiError::BadRequest("Invalid creator address format".into()))?;
+    if memory_enabled {
+        let mut memory_map = state.window_buffer_memory.lock().unwrap();
+        if !memory_map.contains_key(&params.project_uid) {
+            memory_map.insert(params.project_uid.clone(), WindowBufferMemory::new(30));
+        }
+    }
 
-            let creator_id: u64 = orchestrator_contract
-                .method::<_, u64>("creators", (creator_addr,))?
-                .call()
-                .await?;
+    let setup = AgentSetup {
+        name: agent_config.persona_name.as_deref().unwrap_or("Haithe Agent"),
+        preamble: &preamble,
+        temperature,
+        max_tokens: agent_config.max_tokens.map_or(DEFAULT_MAX_TOKENS, |t| t as usize),
+        knowledges: Arc::new(knowledges),
+        search_enabled,
+        memory_enabled,
+        tools: &inputs.tools,
+    };
+    let mut agents: Vec<Option<Agent<LLM>>> = candidates.iter().map(|_| None).collect();
+    let stop_sequences = agent_config.stop_sequences();
 
-            if creator_id == 0 {
-                continue;
+    let mut choices = Vec::new();
+    // Later choices start from the model that answered the previous one
+    let mut first_candidate = 0;
+    let mut answered_by: Vec<&Model> = Vec::new();
+
+    let answering = async {
+        for i in 0..params.n {
+            let (index, reply) = prompt_with_routing(
+                &candidates[first_candidate..],
+                &mut agents[first_candidate..],
+                &setup,
+                &retry,
+                &prompt,
+            )
+            .await?;
+            first_candidate += index;
+            let model = candidates[first_candidate];
+            if !answered_by.iter().any(|m| m.id == model.id) {
+                answered_by.push(model);
             }
 
-            let contract_call = orchestrator_contract.method::<_, ()>(
-                "collectPaymentForCall",
-                (
-                    formatted_organization_address,
-                    creator_id,
-                    formatted_organization_address,
-                    cost,
-                ),
-            )?;
-
-            let tx = contract_call.send().await?;
-            let _receipt = tx
-                .await
-                .map_err(|e| ApiError::BadRequest(format!("Transaction failed: {}", e)))?;
+            choices.push(json!({
+                "index": i,
+                "model": model.name,
+                "message": {
+                    "role": "assistant",
+                    "content": cut_at_stop_sequence(reply, &stop_sequences)
+                },
+                "finish_reason": "stop"
+            }));
         }
-    }
-
-    let llm_cost = models
-        .iter()
-        .find(|m| m.name == params.model)
-        .map_or(0, |m| m.price_per_call) as u64;
-
-    if llm_cost > 0 {
-        let formatted_organization_address: Address = org_address
-            .parse()
-            .map_err(|_| ApiError::BadRequest("Invalid organization address format".into()))?;
-
-        let orchestrator_contract =
-            contracts::get_contract_with_wallet("HaitheOrchestrator", None).await?;
-
-        let contract_call = orchestrator_contract.method::<_, ()>(
-            "collectPaymentForLLMCall",
-            (
-                formatted_organization_address,
-                formatted_organization_address,
-                llm_cost,
-            ),
-        )?;
-
-        let tx = contract_call.send().await?;
-        let _receipt = tx
+        Ok::<_, ApiError>(())
+    };
+    let answered = match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, answering)
             .await
-            .map_err(|e| ApiError::BadRequest(format!("Transaction failed: {}", e)))?;
+            .unwrap_or_else(|_| Err(ApiError::Internal("Completion timed out".to_string()))),
+        None => answering.await,
+    };
+    if let Err(e) = answered {
+        settle_tool_calls(state, &params, org_id, project_id, &org_address, &inputs.tools).await?;
+        return Err(e);
     }
 
-    let mut agent = Agent::new("Haithe Agent", llm).preamble(&preamble);
-    agent.temperature = Some(params.temperature);
-    agent.max_tokens = Some(1024);
-    agent.knowledges = Arc::new(knowledges);
-
-    if search_enabled {
-        agent = agent.tool(SearchTool::default()).await;
+    // Choices answered by different models are charged the dearest of them
+    let charged = answered_by
+        .iter()
+        .copied()
+        .max_by_key(|m| m.price_per_call)
+        .unwrap_or(requested);
+    if charged.id != requested.id {
+        metrics::inc(
+            "haithe_llm_fallbacks_total",
+            &[("requested", &requested.name), ("model", &charged.name)],
+        );
     }
 
-    if memory_enabled {
-        {
-            let mut memory_map = state.window_buffer_memory.lock().unwrap();
-            if !memory_map.contains_key(&params.project_uid) {
-                memory_map.insert(params.project_uid.clone(), WindowBufferMemory::new(30));
-            }
-        }
-        agent = agent.memory(WindowBufferMemory::new(30));
+    if let Some(invocation_id) = params.invocation_id {
+        webhooks::mark_charged(&state.db, invocation_id).await?;
     }
 
-    let prompt = params
-        .messages
-        .iter()
-        .filter_map(|msg| msg.get("content").and_then(|c| c.as_str()))
-        .collect::<Vec<&str>>()
-        .join("\n");
+    // The calls were made whatever happens to the payments below, so tools are paid first
+    total_cost += collect_tool_payments(&org_address, &inputs.tools).await?;
 
-    let mut choices = Vec::new();
-
-    for i in 0..params.n {
-        let response = agent.prompt(&prompt).await?;
-
-        choices.push(json!({
-            "index": i,
-            "message": {
-                "role": "assistant",
-                "content": response
-            },
-            "finish_reason": "stop"
-        }));
+    for (_product_address, creator_address, cost) in product_payments {
+        collect_product_payment(&org_address, &creator_address, cost).await?;
     }
 
+    let llm_cost = charged.price_per_call;
+    total_cost += llm_cost;
+    collect_model_payment(&org_address, llm_cost, &charged.name).await?;
+
     let formatted_organization_address: Address = org_address
         .parse()
         .map_err(|_| ApiError::BadRequest("Invalid wallet address format".into()))?;
@@ -367,11 +387,7 @@ pub async fn generate_llm_response(
         .await
         .map_err(|e| ApiError::Internal(format!("Failed to call balanceOf: {}", e)))?;
 
-    let current_expenditure: i64 =
-        sqlx::query_scalar("SELECT expenditure FROM organizations WHERE id = ?")
-            .bind(org_id)
-            .fetch_one(&state.db)
-            .await?;
+    let current_expenditure = orgs::expenditure(&state.db, org_id).await?;
 
     let current_expenditure_u64 = current_expenditure.max(0) as u64;
 
@@ -379,16 +395,382 @@ pub async fn generate_llm_response(
         return Err(ApiError::BadRequest("Insufficient funds".to_string()));
     }
 
-    sqlx::query("UPDATE organizations SET expenditure = expenditure + ? WHERE id = ?")
-        .bind(total_cost as i64)
-        .bind(org_id)
-        .execute(&state.db)
-        .await?;
+    orgs::add_expenditure(&state.db, org_id, total_cost as i64).await?;
+
+    let usage_id = usage::record(
+        &state.db,
+        org_id,
+        project_id,
+        params.wallet_address.as_deref(),
+        &charged.name,
+        total_cost as i64,
+    )
+    .await?;
+
+    budget::spawn_threshold_check(state.db.clone(), org_id, project_id);
+
+    tracing::info!(cost = total_cost, usage_id, model = %charged.name, choices = choices.len(), "Completion finished");
+
+    webhooks::emit(
+        &state.db,
+        project_id,
+        "completion.finished",
+        json!({
+            "model": charged.name,
+            "requested_model": params.model,
+            "wallet_address": params.wallet_address,
+            "choices": choices.len(),
+            "cost": total_cost,
+        }),
+    )
+    .await;
 
     Ok(LlmResponse {
         choices,
         total_cost,
         current_expenditure: current_expenditure_u64,
         prompt_tokens: prompt.len() as u64,
+        usage_id,
+        model: charged.name.clone(),
+        citations,
     })
 }
+
+const DEFAULT_MAX_TOKENS: usize = 1024;
+/// Upper bound for a project's `max_retries`.
+pub const MAX_RETRIES: i64 = 5;
+
+/// How hard a completion tries each model before moving to the next.
+struct RetryPolicy {
+    max_retries: u32,
+    backoff: Duration,
+}
+
+/// The prompt the agent is given for the conversation so far. A lone user
+/// message is passed as-is, longer histories are written out turn by turn so
+/// the agent can tell who said what.
+fn transcript(messages: &[Value]) -> String {
+    let turns: Vec<(&str, &str)> = messages
+        .iter()
+        .filter_map(|msg| {
+            let content = msg.get("content").and_then(|c| c.as_str())?;
+            let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+            Some((role, content))
+        })
+        .collect();
+
+    if let [("user", content)] = turns.as_slice() {
+        return content.to_string();
+    }
+
+    turns
+        .iter()
+        .map(|(role, content)| {
+            let speaker = match *role {
+                "system" => "System",
+                "assistant" => "Assistant",
+                _ => "User",
+            };
+            format!("{}: {}", speaker, content)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// What the agents a completion may run on have in common.
+struct AgentSetup<'a> {
+    name: &'a str,
+    preamble: &'a str,
+    temperature: f32,
+    max_tokens: usize,
+    knowledges: Arc<Vec<Box<dyn Knowledge>>>,
+    search_enabled: bool,
+    memory_enabled: bool,
+    tools: &'a [HttpTool],
+}
+
+async fn build_agent(llm: LLM, setup: &AgentSetup<'_>) -> Agent<LLM> {
+    let mut agent = Agent::new(setup.name, llm).preamble(setup.preamble);
+    agent.temperature = Some(setup.temperature);
+    agent.max_tokens = Some(setup.max_tokens);
+    agent.knowledges = setup.knowledges.clone();
+
+    if setup.search_enabled {
+        agent = agent.tool(SearchTool::default()).await;
+    }
+
+    for tool in setup.tools {
+        agent = agent.tool(tool.clone()).await;
+    }
+
+    if setup.memory_enabled {
+        agent = agent.memory(WindowBufferMemory::new(30));
+    }
+
+    agent
+}
+
+async fn prompt_agent(
+    agent: &Agent<LLM>,
+    prompt: &str,
+    model: &str,
+    provider: &str,
+) -> Result<String, ApiError> {
+    let started = Instant::now();
+    let result = agent.prompt(prompt).await;
+    let status = if result.is_ok() { "ok" } else { "error" };
+    metrics::inc(
+        "haithe_llm_requests_total",
+        &[("model", model), ("provider", provider), ("status", status)],
+    );
+    metrics::observe(
+        "haithe_llm_request_duration_seconds",
+        &[("model", model), ("provider", provider)],
+        started.elapsed(),
+    );
+    if result.is_ok() {
+        routing::record_latency(model, started.elapsed());
+    }
+    Ok(result?)
+}
+
+/// Asks the candidates in order, each up to `1 + max_retries` times with backoff
+/// in between while its errors are transient, and returns the reply with the
+/// index of the model that gave it.
+/// Agents are built the first time their model is needed.
+async fn prompt_with_routing(
+    candidates: &[&Model],
+    agents: &mut [Option<Agent<LLM>>],
+    setup: &AgentSetup<'_>,
+    retry: &RetryPolicy,
+    prompt: &str,
+) -> Result<(usize, String), ApiError> {
+    let mut last_error = None;
+
+    for (index, model) in candidates.iter().enumerate() {
+        if agents[index].is_none() {
+            match models::resolve_model(&model.name) {
+                Ok(llm) => agents[index] = Some(build_agent(llm, setup).await),
+                Err(e) => {
+                    tracing::warn!(model = %model.name, error = %e, "Model is unavailable");
+                    last_error = Some(e);
+                    continue;
+                }
+            }
+        }
+        let agent = agents[index].as_ref().expect("agent is built");
+
+        for attempt in 0..=retry.max_retries {
+            if attempt > 0 {
+                tokio::time::sleep(routing::backoff(retry.backoff, attempt)).await;
+            }
+            match prompt_agent(agent, prompt, &model.name, &model.provider).await {
+                Ok(reply) => return Ok((index, reply)),
+                Err(e) => {
+                    let transient = routing::is_transient(&e.to_string());
+                    tracing::warn!(model = %model.name, attempt, transient, error = %e, "Model call failed");
+                    last_error = Some(e);
+                    // Errors that will only repeat go straight to the next model
+                    if !transient {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| ApiError::Internal("No model to answer with".to_string())))
+}
+
+/// A one-off completion for server-side housekeeping, such as conversation
+/// titles and summaries. It runs on the bare model: no products, knowledge or
+/// tools are loaded, and only the model call is billed to the project.
+pub async fn complete_bare(
+    state: &AppState,
+    org_id: i64,
+    project_id: i64,
+    wallet_address: Option<&str>,
+    model_name: &str,
+    prompt: &str,
+) -> Result<String, ApiError> {
+    let model = models::get_models()
+        .into_iter()
+        .find(|m| m.name == model_name && m.is_active)
+        .ok_or_else(|| ApiError::BadRequest("Invalid model".to_string()))?;
+
+    if !orgs::enrolled_model_ids(&state.db, org_id).await?.contains(&model.id) {
+        return Err(ApiError::Forbidden);
+    }
+
+    let cost = model.price_per_call;
+    budget::enforce(&state.db, org_id, project_id, cost).await?;
+
+    let mut agent = Agent::new("Haithe Agent", models::resolve_model(&model.name)?);
+    agent.temperature = Some(0.3);
+    agent.max_tokens = Some(DEFAULT_MAX_TOKENS);
+
+    let reply = prompt_agent(&agent, prompt, &model.name, &model.provider).await?;
+
+    let org_address: String = db::query_scalar("SELECT address FROM organizations WHERE id = ?")
+        .bind(org_id)
+        .fetch_one(&state.db)
+        .await?;
+    collect_model_payment(&org_address, cost, &model.name).await?;
+
+    orgs::add_expenditure(&state.db, org_id, cost as i64).await?;
+    let usage_id =
+        usage::record(&state.db, org_id, project_id, wallet_address, &model.name, cost as i64)
+            .await?;
+    budget::spawn_threshold_check(state.db.clone(), org_id, project_id);
+
+    tracing::info!(cost, usage_id, model = %model.name, "Bare completion finished");
+
+    Ok(reply)
+}
+
+/// Cuts a reply at the first of the project's stop sequences. Providers are not
+/// all able to stop on them, so they are applied here.
+fn cut_at_stop_sequence(mut reply: String, stop_sequences: &[String]) -> String {
+    if let Some(end) = stop_sequences
+        .iter()
+        .filter(|stop| !stop.is_empty())
+        .filter_map(|stop| reply.find(stop.as_str()))
+        .min()
+    {
+        reply.truncate(end);
+    }
+    reply
+}
+
+/// Pays for the tool calls the agent made, known only once it is done, and
+/// returns the total. Tool products are billed per successful call.
+async fn collect_tool_payments(org_address: &str, tools: &[HttpTool]) -> Result<u64, ApiError> {
+    let mut paid: u64 = 0;
+    for tool in tools {
+        let cost = tool.calls().saturating_mul(tool.price_per_call);
+        if cost > 0 {
+            collect_product_payment(org_address, &tool.creator, cost).await?;
+            paid = paid.saturating_add(cost);
+        }
+    }
+    Ok(paid)
+}
+
+/// Charges for the tools called by a completion that then failed. Their
+/// calls were made, so they are paid and recorded as usage of the requested
+/// model even though no answer is returned.
+async fn settle_tool_calls(
+    state: &AppState,
+    params: &LlmResponseParams,
+    org_id: i64,
+    project_id: i64,
+    org_address: &str,
+    tools: &[HttpTool],
+) -> Result<(), ApiError> {
+    if tools.iter().all(|tool| tool.calls() == 0) {
+        return Ok(());
+    }
+
+    if let Some(invocation_id) = params.invocation_id {
+        webhooks::mark_charged(&state.db, invocation_id).await?;
+    }
+
+    let cost = collect_tool_payments(org_address, tools).await?;
+    if cost == 0 {
+        return Ok(());
+    }
+
+    orgs::add_expenditure(&state.db, org_id, cost as i64).await?;
+    let usage_id = usage::record(
+        &state.db,
+        org_id,
+        project_id,
+        params.wallet_address.as_deref(),
+        &params.model,
+        cost as i64,
+    )
+    .await?;
+    budget::spawn_threshold_check(state.db.clone(), org_id, project_id);
+
+    tracing::info!(cost, usage_id, "Tool calls of failed completion charged");
+    Ok(())
+}
+
+/// Pays a product creator through `collectPaymentForCall`. Creators no longer
+/// registered with the orchestrator are skipped.
+/// Pays the platform for a model's answer through `collectPaymentForLLMCall`.
+async fn collect_model_payment(org_address: &str, cost: u64, model: &str) -> Result<(), ApiError> {
+    if cost == 0 {
+        return Ok(());
+    }
+
+    let formatted_organization_address: Address = org_address
+        .parse()
+        .map_err(|_| ApiError::BadRequest("Invalid organization address format".into()))?;
+
+    let orchestrator_contract =
+        contracts::get_contract_with_wallet("HaitheOrchestrator", None).await?;
+
+    let contract_call = orchestrator_contract.method::<_, ()>(
+        "collectPaymentForLLMCall",
+        (
+            formatted_organization_address,
+            formatted_organization_address,
+            cost,
+        ),
+    )?;
+
+    let tx_hash = contracts::send(&contract_call, "HaitheOrchestrator", "collectPaymentForLLMCall")
+        .await
+        .map_err(|e| ApiError::BadRequest(format!("Transaction failed: {}", e)))?;
+    tracing::info!(tx_hash = ?tx_hash, cost, model = %model, "Model payment collected");
+
+    Ok(())
+}
+
+async fn collect_product_payment(
+    org_address: &str,
+    creator_address: &str,
+    cost: u64,
+) -> Result<(), ApiError> {
+    if cost == 0 {
+        return Ok(());
+    }
+
+    let formatted_organization_address: Address = org_address
+        .parse()
+        .map_err(|_| ApiError::BadRequest("Invalid organization address format".into()))?;
+
+    let orchestrator_contract =
+        contracts::get_contract_with_wallet("HaitheOrchestrator", None).await?;
+
+    let creator_addr: Address = creator_address
+        .parse()
+        .map_err(|_| ApiError::BadRequest("Invalid creator address format".into()))?;
+
+    let creator_id: u64 = orchestrator_contract
+        .method::<_, u64>("creators", (creator_addr,))?
+        .call()
+        .await?;
+
+    if creator_id == 0 {
+        return Ok(());
+    }
+
+    let contract_call = orchestrator_contract.method::<_, ()>(
+        "collectPaymentForCall",
+        (
+            formatted_organization_address,
+            creator_id,
+            formatted_organization_address,
+            cost,
+        ),
+    )?;
+
+    let tx_hash = contracts::send(&contract_call, "HaitheOrchestrator", "collectPaymentForCall")
+        .await
+        .map_err(|e| ApiError::BadRequest(format!("Transaction failed: {}", e)))?;
+    tracing::info!(tx_hash = ?tx_hash, creator = %creator_address, cost, "Product payment collected");
+
+    Ok(())
+}
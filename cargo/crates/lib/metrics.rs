@@ -0,0 +1,258 @@
+use crate::lib::db::Db;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Every metric the server exports, as (name, type, help). Series are only
+/// rendered for metrics listed here.
+const METRICS: &[(&str, &str, &str)] = &[
+    (
+        "haithe_http_requests_total",
+        "counter",
+        "HTTP requests by route and status",
+    ),
+    (
+        "haithe_http_request_duration_seconds",
+        "histogram",
+        "HTTP request latency by route",
+    ),
+    (
+        "haithe_llm_requests_total",
+        "counter",
+        "Model calls by model, provider and outcome",
+    ),
+    (
+        "haithe_llm_request_duration_seconds",
+        "histogram",
+        "Model call latency",
+    ),
+    (
+        "haithe_llm_fallbacks_total",
+        "counter",
+        "Completions charged to a model other than the requested one",
+    ),
+    (
+        "haithe_chain_transactions_total",
+        "counter",
+        "Contract transactions by method and outcome",
+    ),
+    (
+        "haithe_chain_gas_used_total",
+        "counter",
+        "Gas used by settled contract transactions",
+    ),
+    (
+        "haithe_knowledge_fetch_duration_seconds",
+        "histogram",
+        "Product data fetch and decrypt time",
+    ),
+    (
+        "haithe_knowledge_cache_total",
+        "counter",
+        "Knowledge cache lookups by result (hit, revalidated, miss)",
+    ),
+    (
+        "haithe_knowledge_fetch_failures_total",
+        "counter",
+        "Failed product data fetches and decrypts",
+    ),
+    (
+        "haithe_tool_calls_total",
+        "counter",
+        "Tool product calls by product and outcome",
+    ),
+    (
+        "haithe_tool_call_duration_seconds",
+        "histogram",
+        "Tool product call latency",
+    ),
+    (
+        "haithe_bot_messages_total",
+        "counter",
+        "Bot messages handled by platform, project and outcome",
+    ),
+];
+
+/// Upper bounds in seconds, shared by every histogram.
+const BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+type Labels = Vec<(&'static str, String)>;
+
+struct Histogram {
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+#[derive(Default)]
+struct Registry {
+    counters: BTreeMap<(&'static str, Labels), f64>,
+    histograms: BTreeMap<(&'static str, Labels), Histogram>,
+}
+
+static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+
+fn registry() -> MutexGuard<'static, Registry> {
+    REGISTRY.get_or_init(Default::default).lock().unwrap()
+}
+
+fn owned(labels: &[(&'static str, &str)]) -> Labels {
+    labels
+        .iter()
+        .map(|(key, value)| (*key, value.to_string()))
+        .collect()
+}
+
+pub fn inc(name: &'static str, labels: &[(&'static str, &str)]) {
+    add(name, labels, 1.0);
+}
+
+pub fn add(name: &'static str, labels: &[(&'static str, &str)], value: f64) {
+    *registry()
+        .counters
+        .entry((name, owned(labels)))
+        .or_default() += value;
+}
+
+pub fn observe(name: &'static str, labels: &[(&'static str, &str)], elapsed: Duration) {
+    let seconds = elapsed.as_secs_f64();
+    let mut registry = registry();
+    let histogram = registry
+        .histograms
+        .entry((name, owned(labels)))
+        .or_insert_with(|| Histogram {
+            counts: vec![0; BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        });
+
+    for (count, bound) in histogram.counts.iter_mut().zip(BUCKETS) {
+        if seconds <= *bound {
+            *count += 1;
+        }
+    }
+    histogram.sum += seconds;
+    histogram.count += 1;
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_labels(labels: &Labels, extra: Option<(&str, &str)>) -> String {
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| (*key, value.as_str()))
+        .chain(extra)
+        .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+        .collect();
+
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+/// Renders all metrics in the Prometheus text exposition format.
+pub fn render(db: &Db) -> String {
+    let registry = registry();
+    let mut out = String::new();
+
+    for (name, kind, help) in METRICS {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+
+        for ((_, labels), value) in registry.counters.iter().filter(|((n, _), _)| n == name) {
+            let _ = writeln!(out, "{}{} {}", name, format_labels(labels, None), value);
+        }
+
+        for ((_, labels), histogram) in registry.histograms.iter().filter(|((n, _), _)| n == name) {
+            for (count, bound) in histogram.counts.iter().zip(BUCKETS) {
+                let le = bound.to_string();
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{} {}",
+                    name,
+                    format_labels(labels, Some(("le", &le))),
+                    count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{}_bucket{} {}",
+                name,
+                format_labels(labels, Some(("le", "+Inf"))),
+                histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "{}_sum{} {}",
+                name,
+                format_labels(labels, None),
+                histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "{}_count{} {}",
+                name,
+                format_labels(labels, None),
+                histogram.count
+            );
+        }
+    }
+
+    let (size, idle) = db.pool_stats();
+    let _ = writeln!(
+        out,
+        "# HELP haithe_db_pool_connections Database pool connections by state"
+    );
+    let _ = writeln!(out, "# TYPE haithe_db_pool_connections gauge");
+    let _ = writeln!(out, "haithe_db_pool_connections{{state=\"idle\"}} {}", idle);
+    let _ = writeln!(
+        out,
+        "haithe_db_pool_connections{{state=\"in_use\"}} {}",
+        (size as usize).saturating_sub(idle)
+    );
+
+    out
+}
+
+/// Counts requests and their latency per route pattern, so ids in paths
+/// don't create a series each.
+pub async fn track_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let method = req.method().to_string();
+    let started = Instant::now();
+
+    let res = next.call(req).await?;
+
+    let route = res
+        .request()
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+    let status = res.status().as_u16().to_string();
+
+    inc(
+        "haithe_http_requests_total",
+        &[("method", &method), ("route", &route), ("status", &status)],
+    );
+    observe(
+        "haithe_http_request_duration_seconds",
+        &[("method", &method), ("route", &route)],
+        started.elapsed(),
+    );
+
+    Ok(res)
+}
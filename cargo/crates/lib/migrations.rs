@@ -0,0 +1,538 @@
+use crate::lib::db::{self, Backend, Db};
+use anyhow::{Result, anyhow};
+use sha3::{Digest, Sha3_256};
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every schema change, in order. Applied migrations must never be edited; add a new one instead.
+pub const SQLITE_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 0,
+        name: "0000_baseline",
+        sql: include_str!("../../data/migrations/0000_baseline.sql"),
+    },
+    Migration {
+        version: 1,
+        name: "0001_message_usage",
+        sql: include_str!("../../data/migrations/0001_message_usage.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "0002_project_conversations",
+        sql: include_str!("../../data/migrations/0002_project_conversations.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "0003_message_search",
+        sql: include_str!("../../data/migrations/0003_message_search.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "0004_message_branches",
+        sql: include_str!("../../data/migrations/0004_message_branches.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "0005_conversation_titles_summaries",
+        sql: include_str!("../../data/migrations/0005_conversation_titles_summaries.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "0006_knowledge_chunks",
+        sql: include_str!("../../data/migrations/0006_knowledge_chunks.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "0007_prompt_variables",
+        sql: include_str!("../../data/migrations/0007_prompt_variables.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "0008_project_agent_config",
+        sql: include_str!("../../data/migrations/0008_project_agent_config.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "0009_model_routing",
+        sql: include_str!("../../data/migrations/0009_model_routing.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "0010_trigger_invocation_lease",
+        sql: include_str!("../../data/migrations/0010_trigger_invocation_lease.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "0011_webhook_delivery_schedule",
+        sql: include_str!("../../data/migrations/0011_webhook_delivery_schedule.sql"),
+    },
+];
+
+/// Postgres deployments start from a baseline equal to the SQLite schema at 0005.
+/// Later schema changes need a migration here too, with the same version.
+pub const POSTGRES_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 5,
+        name: "0005_baseline",
+        sql: include_str!("../../data/migrations/postgres/0005_baseline.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "0006_knowledge_chunks",
+        sql: include_str!("../../data/migrations/postgres/0006_knowledge_chunks.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "0007_prompt_variables",
+        sql: include_str!("../../data/migrations/postgres/0007_prompt_variables.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "0008_project_agent_config",
+        sql: include_str!("../../data/migrations/postgres/0008_project_agent_config.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "0009_model_routing",
+        sql: include_str!("../../data/migrations/postgres/0009_model_routing.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "0010_trigger_invocation_lease",
+        sql: include_str!("../../data/migrations/postgres/0010_trigger_invocation_lease.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "0011_webhook_delivery_schedule",
+        sql: include_str!("../../data/migrations/postgres/0011_webhook_delivery_schedule.sql"),
+    },
+];
+
+pub fn migrations(backend: Backend) -> &'static [Migration] {
+    match backend {
+        Backend::Sqlite => SQLITE_MIGRATIONS,
+        Backend::Postgres => POSTGRES_MIGRATIONS,
+    }
+}
+
+impl Migration {
+    /// SHA3-256 of the script, ignoring line ending differences between checkouts.
+    pub fn checksum(&self) -> String {
+        hex::encode(Sha3_256::digest(self.sql.replace('\r', "").as_bytes()))
+    }
+}
+
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: &'static str,
+    pub applied_at: Option<String>,
+    pub checksum_matches: Option<bool>,
+}
+
+async fn ensure_tracking_table(db: &Db) -> Result<()> {
+    match db {
+        Db::Sqlite(_) => {
+            db::query(
+                "CREATE TABLE IF NOT EXISTS schema_migrations (
+                    version INTEGER PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    checksum TEXT,
+                    applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                )",
+            )
+            .execute(db)
+            .await?;
+
+            // Tables created before checksums were tracked
+            let has_checksum: i64 = db::query_scalar(
+                "SELECT COUNT(*) FROM pragma_table_info('schema_migrations') WHERE name = 'checksum'",
+            )
+            .fetch_one(db)
+            .await?;
+
+            if has_checksum == 0 {
+                db::query("ALTER TABLE schema_migrations ADD COLUMN checksum TEXT")
+                    .execute(db)
+                    .await?;
+            }
+        }
+        Db::Postgres(_) => {
+            db::query(
+                "CREATE TABLE IF NOT EXISTS schema_migrations (
+                    version BIGINT PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    checksum TEXT,
+                    applied_at TEXT DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+                )",
+            )
+            .execute(db)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn applied(db: &Db) -> Result<Vec<(i64, Option<String>, String)>> {
+    Ok(db::query_as::<(i64, Option<String>, String)>(
+        "SELECT version, checksum, applied_at FROM schema_migrations ORDER BY version",
+    )
+    .fetch_all(db)
+    .await?)
+}
+
+/// Applies pending migrations in order, each in its own transaction.
+/// Refuses to run if an applied migration has since been modified.
+pub async fn run(db: &Db) -> Result<Vec<&'static Migration>> {
+    ensure_tracking_table(db).await?;
+
+    let applied = applied(db).await?;
+    let mut newly_applied = Vec::new();
+
+    for migration in migrations(db.backend()) {
+        let checksum = migration.checksum();
+
+        match applied.iter().find(|(version, _, _)| *version == migration.version) {
+            Some((_, Some(recorded), _)) if *recorded != checksum => {
+                return Err(anyhow!(
+                    "Migration {} was modified after it was applied (checksum {} != {})",
+                    migration.name,
+                    recorded,
+                    checksum
+                ));
+            }
+            Some((_, Some(_), _)) => continue,
+            Some((_, None, _)) => {
+                // Applied before checksums existed, adopt the current script
+                db::query("UPDATE schema_migrations SET name = ?, checksum = ? WHERE version = ?")
+                    .bind(migration.name)
+                    .bind(&checksum)
+                    .bind(migration.version)
+                    .execute(db)
+                    .await?;
+                continue;
+            }
+            None => {}
+        }
+
+        let mut tx = db.begin().await?;
+        tx.execute_script(migration.sql)
+            .await
+            .map_err(|e| anyhow!("Migration {} failed: {}", migration.name, e))?;
+        db::query("INSERT INTO schema_migrations (version, name, checksum) VALUES (?, ?, ?)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .bind(&checksum)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        newly_applied.push(migration);
+    }
+
+    Ok(newly_applied)
+}
+
+pub async fn status(db: &Db) -> Result<Vec<MigrationStatus>> {
+    ensure_tracking_table(db).await?;
+
+    let applied = applied(db).await?;
+
+    Ok(migrations(db.backend())
+        .iter()
+        .map(|migration| {
+            let record = applied.iter().find(|(version, _, _)| *version == migration.version);
+            MigrationStatus {
+                version: migration.version,
+                name: migration.name,
+                applied_at: record.map(|(_, _, applied_at)| applied_at.clone()),
+                checksum_matches: record.and_then(|(_, checksum, _)| {
+                    checksum.as_ref().map(|c| *c == migration.checksum())
+                }),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::db::testing;
+
+    /// Applies migrations up to and including `version`, as an older build would have.
+    async fn migrate_to(db: &Db, version: i64) {
+        ensure_tracking_table(db).await.unwrap();
+
+        for migration in migrations(db.backend()).iter().filter(|m| m.version <= version) {
+            let mut tx = db.begin().await.unwrap();
+            tx.execute_script(migration.sql).await.unwrap();
+            db::query("INSERT INTO schema_migrations (version, name, checksum) VALUES (?, ?, ?)")
+                .bind(migration.version)
+                .bind(migration.name)
+                .bind(migration.checksum())
+                .execute(&mut tx)
+                .await
+                .unwrap();
+            tx.commit().await.unwrap();
+        }
+    }
+
+    /// Rows a deployment would have, using only columns every schema version has.
+    async fn seed(db: &Db) {
+        for sql in [
+            "INSERT INTO accounts (wallet_address) VALUES ('0xowner')",
+            "INSERT INTO organizations (id, organization_uid, address, orchestrator_idx, name, owner) VALUES (1, 'org-1', '0xorg', 0, 'Acme', '0xowner')",
+            "INSERT INTO projects (id, project_uid, org_id, name) VALUES (1, 'project-1', 1, 'Support')",
+            "INSERT INTO usage_records (id, org_id, project_id, wallet_address, model, cost) VALUES (1, 1, 1, '0xowner', 'gpt-4o', 7)",
+            "INSERT INTO conversations (id, wallet_address, title) VALUES (1, '0xowner', 'Lighthouse keeping')",
+            "INSERT INTO agent_preview_messages (id, conversation_id, sender, message) VALUES (1, 1, 'user', 'Who keeps the lighthouse?')",
+            "INSERT INTO agent_preview_messages (id, conversation_id, sender, message) VALUES (2, 1, 'ai', 'The keeper does.')",
+            "INSERT INTO webhook_triggers (id, trigger_uid, project_id, name, secret, prompt_template, model) VALUES (1, 'trigger-1', 1, 'Nightly', 'whsec_test', 'Summarise {{body}}', 'gpt-4o')",
+            "INSERT INTO webhook_trigger_invocations (id, trigger_id, idempotency_key, status, response) VALUES (1, 1, 'key-1', 'succeeded', 'done')",
+            "INSERT INTO webhook_endpoints (id, project_id, url, secret) VALUES (1, 1, 'https://hooks.example.com', 'whsec_test')",
+            "INSERT INTO webhook_deliveries (id, endpoint_id, event, payload, attempts) VALUES (1, 1, 'member.added', '{}', 2)",
+        ] {
+            db::query(sql).execute(db).await.unwrap();
+        }
+    }
+
+    async fn assert_upgraded(db: &Db, seeded_at: i64) {
+        let recorded = db::query_as::<(i64, Option<String>)>(
+            "SELECT version, checksum FROM schema_migrations ORDER BY version",
+        )
+        .fetch_all(db)
+        .await
+        .unwrap();
+        let expected: Vec<_> = migrations(db.backend())
+            .iter()
+            .map(|m| (m.version, Some(m.checksum())))
+            .collect();
+        assert_eq!(recorded, expected);
+
+        let project = db::query_as::<(String, i64, String, String, String)>(
+            "SELECT name, max_retries, routing_strategy, prompt_variables, fallback_model_ids FROM projects WHERE id = 1",
+        )
+        .fetch_one(db)
+        .await
+        .unwrap();
+        assert_eq!(
+            project,
+            ("Support".into(), 1, "ordered".into(), "{}".into(), "[]".into())
+        );
+
+        let invocation = db::query_as::<(String, Option<String>, bool, Option<i64>)>(
+            "SELECT status, response, charged, usage_id FROM webhook_trigger_invocations WHERE id = 1",
+        )
+        .fetch_one(db)
+        .await
+        .unwrap();
+        assert_eq!(invocation, ("succeeded".into(), Some("done".into()), false, None));
+
+        // Deliveries left pending by an older build are due straight away
+        let delivery = db::query_as::<(String, i64, Option<String>)>(
+            "SELECT status, attempts, next_attempt_at FROM webhook_deliveries WHERE id = 1",
+        )
+        .fetch_one(db)
+        .await
+        .unwrap();
+        assert_eq!(delivery, ("pending".into(), 2, None));
+
+        let cost: i64 = db::query_scalar("SELECT cost FROM usage_records WHERE id = 1")
+            .fetch_one(db)
+            .await
+            .unwrap();
+        assert_eq!(cost, 7);
+
+        let messages: Vec<String> = db::query_scalar(
+            "SELECT message FROM agent_preview_messages WHERE conversation_id = 1 ORDER BY id",
+        )
+        .fetch_all(db)
+        .await
+        .unwrap();
+        assert_eq!(messages, ["Who keeps the lighthouse?", "The keeper does."]);
+
+        if db.backend() != Backend::Sqlite {
+            return;
+        }
+
+        // Backfills of the SQLite-only migrations, for rows that predate them
+        if seeded_at < 2 {
+            let project_id: Option<i64> =
+                db::query_scalar("SELECT project_id FROM conversations WHERE id = 1")
+                    .fetch_one(db)
+                    .await
+                    .unwrap();
+            assert_eq!(project_id, Some(1));
+        }
+
+        if seeded_at < 3 {
+            let hits: i64 = db::query_scalar(
+                "SELECT COUNT(*) FROM agent_preview_messages_fts WHERE agent_preview_messages_fts MATCH 'lighthouse'",
+            )
+            .fetch_one(db)
+            .await
+            .unwrap();
+            assert_eq!(hits, 1);
+        }
+
+        if seeded_at < 4 {
+            let parent_id: Option<i32> =
+                db::query_scalar("SELECT parent_id FROM agent_preview_messages WHERE id = 2")
+                    .fetch_one(db)
+                    .await
+                    .unwrap();
+            assert_eq!(parent_id, Some(1));
+
+            let active_leaf_id: Option<i32> =
+                db::query_scalar("SELECT active_leaf_id FROM conversations WHERE id = 1")
+                    .fetch_one(db)
+                    .await
+                    .unwrap();
+            assert_eq!(active_leaf_id, Some(2));
+        }
+
+        if seeded_at < 5 {
+            let title_source: String =
+                db::query_scalar("SELECT title_source FROM conversations WHERE id = 1")
+                    .fetch_one(db)
+                    .await
+                    .unwrap();
+            assert_eq!(title_source, "user");
+        }
+    }
+
+    async fn upgrades_from_every_version(backend: Backend) {
+        let db = testing::connect(backend).await;
+        assert_eq!(run(&db).await.unwrap().len(), migrations(backend).len());
+        assert!(run(&db).await.unwrap().is_empty());
+
+        for from in migrations(backend) {
+            let db = testing::connect(backend).await;
+            migrate_to(&db, from.version).await;
+            seed(&db).await;
+
+            let applied: Vec<i64> = run(&db).await.unwrap().iter().map(|m| m.version).collect();
+            let pending: Vec<i64> = migrations(backend)
+                .iter()
+                .map(|m| m.version)
+                .filter(|version| *version > from.version)
+                .collect();
+            assert_eq!(applied, pending, "upgrading from {}", from.name);
+
+            assert_upgraded(&db, from.version).await;
+        }
+    }
+
+    async fn rejects_modified_migrations(backend: Backend) {
+        let db = testing::connect(backend).await;
+        run(&db).await.unwrap();
+
+        let first = &migrations(backend)[0];
+        db::query("UPDATE schema_migrations SET checksum = ? WHERE version = ?")
+            .bind("0".repeat(64))
+            .bind(first.version)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let error = run(&db).await.unwrap_err().to_string();
+        assert!(
+            error.starts_with(&format!("Migration {} was modified after it was applied", first.name)),
+            "{}",
+            error
+        );
+
+        let statuses = status(&db).await.unwrap();
+        assert_eq!(statuses[0].checksum_matches, Some(false));
+        assert!(statuses[1..].iter().all(|s| s.checksum_matches == Some(true)));
+    }
+
+    async fn adopts_rows_without_checksums(backend: Backend) {
+        let db = testing::connect(backend).await;
+
+        // The tracking table as builds before checksums created it
+        let legacy_table = match backend {
+            Backend::Sqlite => {
+                "CREATE TABLE schema_migrations (
+                    version INTEGER PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                )"
+            }
+            Backend::Postgres => {
+                "CREATE TABLE schema_migrations (
+                    version BIGINT PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    checksum TEXT,
+                    applied_at TEXT DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+                )"
+            }
+        };
+        db::query(legacy_table).execute(&db).await.unwrap();
+
+        let (legacy, pending) = migrations(backend).split_at(migrations(backend).len() - 1);
+        for migration in legacy {
+            let mut tx = db.begin().await.unwrap();
+            tx.execute_script(migration.sql).await.unwrap();
+            db::query("INSERT INTO schema_migrations (version, name) VALUES (?, ?)")
+                .bind(migration.version)
+                .bind(format!("legacy_{}", migration.version))
+                .execute(&mut tx)
+                .await
+                .unwrap();
+            tx.commit().await.unwrap();
+        }
+        seed(&db).await;
+
+        let applied: Vec<i64> = run(&db).await.unwrap().iter().map(|m| m.version).collect();
+        assert_eq!(applied, pending.iter().map(|m| m.version).collect::<Vec<_>>());
+
+        let recorded = db::query_as::<(i64, String, Option<String>)>(
+            "SELECT version, name, checksum FROM schema_migrations ORDER BY version",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        let expected: Vec<_> = migrations(backend)
+            .iter()
+            .map(|m| (m.version, m.name.to_string(), Some(m.checksum())))
+            .collect();
+        assert_eq!(recorded, expected);
+
+        assert_upgraded(&db, legacy.last().unwrap().version).await;
+    }
+
+    #[actix_web::test]
+    async fn sqlite_upgrades_from_every_version() {
+        upgrades_from_every_version(Backend::Sqlite).await;
+    }
+
+    #[actix_web::test]
+    async fn postgres_upgrades_from_every_version() {
+        upgrades_from_every_version(Backend::Postgres).await;
+    }
+
+    #[actix_web::test]
+    async fn sqlite_rejects_modified_migrations() {
+        rejects_modified_migrations(Backend::Sqlite).await;
+    }
+
+    #[actix_web::test]
+    async fn postgres_rejects_modified_migrations() {
+        rejects_modified_migrations(Backend::Postgres).await;
+    }
+
+    #[actix_web::test]
+    async fn sqlite_adopts_rows_without_checksums() {
+        adopts_rows_without_checksums(Backend::Sqlite).await;
+    }
+
+    #[actix_web::test]
+    async fn postgres_adopts_rows_without_checksums() {
+        adopts_rows_without_checksums(Backend::Postgres).await;
+    }
+}
@@ -1,9 +1,29 @@
+pub mod audit;
+pub mod budget;
+pub mod config;
+pub mod content_uri;
 pub mod contracts;
+pub mod db;
 pub mod discord;
+pub mod documents;
 pub mod error;
 pub mod extractors;
+pub mod fetch;
+pub mod knowledge;
 pub mod llm;
+pub mod logging;
+pub mod metrics;
+pub mod migrations;
 pub mod models;
+pub mod pagination;
+pub mod product_kinds;
+pub mod promptset;
+pub mod ratelimit;
 pub mod respond;
+pub mod retrieval;
+pub mod routing;
 pub mod state;
 pub mod telegram;
+pub mod template;
+pub mod tools;
+pub mod webhooks;
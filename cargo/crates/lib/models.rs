@@ -1,3 +1,5 @@
+use crate::lib::config;
+use crate::lib::error::ApiError;
 use alith::LLM;
 use serde::{Deserialize, Serialize};
 
@@ -143,27 +145,35 @@ pub fn get_models() -> Vec<Model> {
     models
 }
 
-pub fn resolve_model(name: &str) -> LLM {
+/// The client for an active model. Keys for every active model's provider are
+/// checked when the configuration is loaded, so failing here is unexpected.
+pub fn resolve_model(name: &str) -> Result<LLM, ApiError> {
     let model = get_models()
         .into_iter()
         .find(|m| m.name == name && m.is_active)
-        .unwrap_or_else(|| panic!("Model {} not found or not supported", name));
+        .ok_or_else(|| ApiError::BadRequest(format!("Model {} not found or not supported", name)))?;
 
-    let (base_url, env_var) = match model.provider.as_str() {
-        "OpenAI" => ("https://api.openai.com/v1", "OPENAI_API_KEY"),
-        "Google" => (
-            "https://generativelanguage.googleapis.com/v1beta/openai",
-            "GEMINI_API_KEY",
-        ),
-        "DeepSeek" => ("https://api.deepseek.com/v1", "DEEPSEEK_API_KEY"),
-        "Moonshot" => ("https://api.moonshot.com/v1", "MOONSHOT_API_KEY"),
-        "Haithe" => ("https://api.groq.com/openai/v1", "GROQ_API_KEY"),
-        _ => panic!("Unsupported provider: {}", model.provider),
+    let base_url = match model.provider.as_str() {
+        "OpenAI" => "https://api.openai.com/v1",
+        "Google" => "https://generativelanguage.googleapis.com/v1beta/openai",
+        "DeepSeek" => "https://api.deepseek.com/v1",
+        "Moonshot" => "https://api.moonshot.com/v1",
+        "Haithe" => "https://api.groq.com/openai/v1",
+        _ => {
+            return Err(ApiError::Internal(format!(
+                "Unsupported provider: {}",
+                model.provider
+            )));
+        }
     };
 
-    let api_key = std::env::var(env_var).unwrap_or_else(|_| panic!("{} not set", env_var));
+    let api_key = config::get()
+        .providers
+        .for_provider(&model.provider)
+        .ok_or_else(|| ApiError::Internal(format!("No API key configured for {}", model.provider)))?;
 
-    LLM::openai_compatible_model(&api_key, base_url, &model.name).unwrap_or_else(|_| {
-        panic!("Failed to initialize model {}", name);
+    LLM::openai_compatible_model(api_key, base_url, &model.name).map_err(|e| {
+        tracing::error!(model = name, error = %e, "Failed to initialize model");
+        ApiError::Internal(format!("Failed to initialize model {}", name))
     })
 }
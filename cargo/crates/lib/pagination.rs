@@ -0,0 +1,39 @@
+use crate::lib::error::ApiError;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_LIMIT: i64 = 50;
+pub const MAX_LIMIT: i64 = 200;
+
+/// Position after the last row of a page: its sort key and id as a tie-breaker.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cursor {
+    pub key: String,
+    pub id: i64,
+}
+
+impl Cursor {
+    /// Opaque hex form handed to clients.
+    pub fn encode(&self) -> String {
+        hex::encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    pub fn decode(value: &str) -> Result<Self, ApiError> {
+        hex::decode(value)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| ApiError::BadRequest("Invalid cursor".into()))
+    }
+}
+
+pub fn limit(requested: Option<i64>) -> i64 {
+    requested.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+}
+
+/// `ASC`/`DESC` from an `order` parameter, newest first unless asked otherwise.
+pub fn direction(order: Option<&str>) -> Result<&'static str, ApiError> {
+    match order.unwrap_or("desc") {
+        "asc" => Ok("ASC"),
+        "desc" => Ok("DESC"),
+        _ => Err(ApiError::BadRequest("order must be 'asc' or 'desc'".into())),
+    }
+}
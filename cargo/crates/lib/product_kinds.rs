@@ -0,0 +1,357 @@
+use crate::lib::documents;
+use crate::lib::error::ApiError;
+use crate::lib::knowledge::{KnowledgeCache, ProductContent, ensure_protocol};
+use crate::lib::promptset::PromptSet;
+use crate::lib::retrieval::Document;
+use crate::lib::tools::{HttpTool, HttpToolSpec};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::str::FromStr;
+
+/// The categories a product can be registered under on-chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ProductKind {
+    #[serde(rename = "knowledge:text")]
+    KnowledgeText,
+    #[serde(rename = "knowledge:html")]
+    KnowledgeHtml,
+    #[serde(rename = "knowledge:pdf")]
+    KnowledgePdf,
+    #[serde(rename = "knowledge:docx")]
+    KnowledgeDocx,
+    #[serde(rename = "knowledge:markdown")]
+    KnowledgeMarkdown,
+    #[serde(rename = "knowledge:csv")]
+    KnowledgeCsv,
+    #[serde(rename = "knowledge:json")]
+    KnowledgeJson,
+    #[serde(rename = "knowledge:url")]
+    KnowledgeUrl,
+    #[serde(rename = "promptset")]
+    Promptset,
+    #[serde(rename = "tool:http")]
+    ToolHttp,
+}
+
+impl ProductKind {
+    pub const ALL: [ProductKind; 10] = [
+        ProductKind::KnowledgeText,
+        ProductKind::KnowledgeHtml,
+        ProductKind::KnowledgePdf,
+        ProductKind::KnowledgeDocx,
+        ProductKind::KnowledgeMarkdown,
+        ProductKind::KnowledgeCsv,
+        ProductKind::KnowledgeJson,
+        ProductKind::KnowledgeUrl,
+        ProductKind::Promptset,
+        ProductKind::ToolHttp,
+    ];
+
+    /// The category string stored on-chain and in the `products` table.
+    pub fn category(self) -> &'static str {
+        match self {
+            ProductKind::KnowledgeText => "knowledge:text",
+            ProductKind::KnowledgeHtml => "knowledge:html",
+            ProductKind::KnowledgePdf => "knowledge:pdf",
+            ProductKind::KnowledgeDocx => "knowledge:docx",
+            ProductKind::KnowledgeMarkdown => "knowledge:markdown",
+            ProductKind::KnowledgeCsv => "knowledge:csv",
+            ProductKind::KnowledgeJson => "knowledge:json",
+            ProductKind::KnowledgeUrl => "knowledge:url",
+            ProductKind::Promptset => "promptset",
+            ProductKind::ToolHttp => "tool:http",
+        }
+    }
+
+    pub fn handler(self) -> &'static dyn ProductHandler {
+        REGISTRY
+            .iter()
+            .copied()
+            .find(|handler| handler.kind() == self)
+            .expect("every product kind has a registered handler")
+    }
+}
+
+impl FromStr for ProductKind {
+    type Err = ApiError;
+
+    fn from_str(category: &str) -> Result<Self, Self::Err> {
+        ProductKind::ALL
+            .into_iter()
+            .find(|kind| kind.category() == category)
+            .ok_or_else(|| ApiError::BadRequest(format!("Unknown product category '{}'", category)))
+    }
+}
+
+/// When a product's `price_per_call` is charged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Billing {
+    /// Once for every completion the product is enabled in.
+    PerCompletion,
+    /// Once for every successful call the agent makes to it.
+    PerInvocation,
+}
+
+/// A product enabled for a completion.
+pub struct ProductRef {
+    pub id: i64,
+    pub address: String,
+    pub name: String,
+    pub uri: String,
+    pub creator: String,
+    pub price_per_call: u64,
+}
+
+/// What the enabled products contribute to the agent answering a completion.
+#[derive(Default)]
+pub struct AgentInputs {
+    /// Prompt sets by product name, rendered into the preamble once all are loaded.
+    pub prompt_sets: Vec<(String, PromptSet)>,
+    pub documents: Vec<Document>,
+    pub tools: Vec<HttpTool>,
+}
+
+/// How products of one kind are loaded and handed to the agent.
+#[async_trait]
+pub trait ProductHandler: Send + Sync {
+    fn kind(&self) -> ProductKind;
+
+    fn description(&self) -> &'static str;
+
+    /// What the decrypted payload behind the product's URI must contain.
+    fn payload_format(&self) -> &'static str;
+
+    fn billing(&self) -> Billing {
+        Billing::PerCompletion
+    }
+
+    /// Loads the product's content. Payloads go through the knowledge cache,
+    /// which calls [`ProductHandler::decode`] whenever they changed.
+    async fn fetch(
+        &self,
+        cache: &KnowledgeCache,
+        product: &ProductRef,
+    ) -> Result<ProductContent, ApiError> {
+        cache.get(&product.address, &product.uri, self.kind()).await
+    }
+
+    /// Parses a decrypted payload.
+    fn decode(&self, payload: Vec<u8>) -> Result<ProductContent, ApiError>;
+
+    /// Adds decoded content to the agent. Knowledge is retrieved from by default.
+    fn attach(&self, product: &ProductRef, content: ProductContent, inputs: &mut AgentInputs) {
+        if let Some(document) = Document::from_content(product.id, &product.name, &content) {
+            inputs.documents.push(document);
+        }
+    }
+
+    fn describe(&self) -> Value {
+        json!({
+            "category": self.kind().category(),
+            "description": self.description(),
+            "payload_format": self.payload_format(),
+            "billing": self.billing(),
+        })
+    }
+}
+
+/// Products whose payload is a document the agent retrieves from.
+struct KnowledgeHandler {
+    kind: ProductKind,
+    description: &'static str,
+    payload_format: &'static str,
+    decode: fn(Vec<u8>) -> Result<ProductContent, ApiError>,
+}
+
+#[async_trait]
+impl ProductHandler for KnowledgeHandler {
+    fn kind(&self) -> ProductKind {
+        self.kind
+    }
+
+    fn description(&self) -> &'static str {
+        self.description
+    }
+
+    fn payload_format(&self) -> &'static str {
+        self.payload_format
+    }
+
+    fn decode(&self, payload: Vec<u8>) -> Result<ProductContent, ApiError> {
+        (self.decode)(payload)
+    }
+}
+
+struct PromptsetHandler;
+
+#[async_trait]
+impl ProductHandler for PromptsetHandler {
+    fn kind(&self) -> ProductKind {
+        ProductKind::Promptset
+    }
+
+    fn description(&self) -> &'static str {
+        "Instructions and few-shot examples prepended to the agent's preamble, with {{variable}} placeholders filled from project settings and request metadata"
+    }
+
+    fn payload_format(&self) -> &'static str {
+        "JSON object: version (1), priority (higher first), variables (name to description, default, required), messages (role system, user or assistant, and content); or a JSON array of strings"
+    }
+
+    fn decode(&self, payload: Vec<u8>) -> Result<ProductContent, ApiError> {
+        PromptSet::parse(&payload).map(ProductContent::Prompts)
+    }
+
+    fn attach(&self, product: &ProductRef, content: ProductContent, inputs: &mut AgentInputs) {
+        if let ProductContent::Prompts(set) = content {
+            inputs.prompt_sets.push((product.name.clone(), set));
+        }
+    }
+}
+
+struct HttpToolHandler;
+
+#[async_trait]
+impl ProductHandler for HttpToolHandler {
+    fn kind(&self) -> ProductKind {
+        ProductKind::ToolHttp
+    }
+
+    fn description(&self) -> &'static str {
+        "An HTTP or JSON-RPC endpoint the agent can call, on a host in TOOL_ALLOWED_HOSTS"
+    }
+
+    fn payload_format(&self) -> &'static str {
+        "JSON object: name, description, url, method (GET or POST, default POST), optional rpc_method for JSON-RPC 2.0, parameters (JSON schema), headers, timeout_secs"
+    }
+
+    fn billing(&self) -> Billing {
+        Billing::PerInvocation
+    }
+
+    fn decode(&self, payload: Vec<u8>) -> Result<ProductContent, ApiError> {
+        HttpToolSpec::parse(&payload).map(ProductContent::Tool)
+    }
+
+    fn attach(&self, product: &ProductRef, content: ProductContent, inputs: &mut AgentInputs) {
+        if let ProductContent::Tool(spec) = content {
+            inputs.tools.push(HttpTool::new(
+                spec,
+                product.address.clone(),
+                product.creator.clone(),
+                product.price_per_call,
+            ));
+        }
+    }
+}
+
+fn decode_text(payload: Vec<u8>) -> Result<ProductContent, ApiError> {
+    Ok(ProductContent::Text(String::from_utf8(payload)?))
+}
+
+fn decode_html(payload: Vec<u8>) -> Result<ProductContent, ApiError> {
+    Ok(ProductContent::Html {
+        html: String::from_utf8(payload)?,
+        url: "https://haithe.ai".to_string(),
+    })
+}
+
+fn decode_pdf(payload: Vec<u8>) -> Result<ProductContent, ApiError> {
+    Ok(ProductContent::Text(documents::pdf(&payload)?))
+}
+
+fn decode_docx(payload: Vec<u8>) -> Result<ProductContent, ApiError> {
+    Ok(ProductContent::Text(documents::docx(&payload)?))
+}
+
+fn decode_markdown(payload: Vec<u8>) -> Result<ProductContent, ApiError> {
+    Ok(ProductContent::Text(documents::markdown(
+        &String::from_utf8(payload)?,
+    )))
+}
+
+fn decode_csv(payload: Vec<u8>) -> Result<ProductContent, ApiError> {
+    Ok(ProductContent::Text(documents::csv(&String::from_utf8(
+        payload,
+    )?)?))
+}
+
+fn decode_json(payload: Vec<u8>) -> Result<ProductContent, ApiError> {
+    Ok(ProductContent::Text(documents::json(&payload)?))
+}
+
+/// The page itself is fetched, and revalidated, by the knowledge cache.
+fn decode_url(payload: Vec<u8>) -> Result<ProductContent, ApiError> {
+    let url_string = String::from_utf8(payload)?;
+    if url_string.is_empty() {
+        return Err(ApiError::BadRequest("URL string is empty".to_string()));
+    }
+
+    let url = ensure_protocol(url_string.trim());
+    url::Url::parse(&url).map_err(|e| ApiError::BadRequest(format!("Invalid URL: {}", e)))?;
+
+    Ok(ProductContent::Page {
+        html: String::new(),
+        url,
+    })
+}
+
+static REGISTRY: [&dyn ProductHandler; 10] = [
+    &KnowledgeHandler {
+        kind: ProductKind::KnowledgeText,
+        description: "Plain text the agent retrieves passages from",
+        payload_format: "UTF-8 text",
+        decode: decode_text,
+    },
+    &KnowledgeHandler {
+        kind: ProductKind::KnowledgeHtml,
+        description: "An HTML document, retrieved from as its visible text",
+        payload_format: "UTF-8 HTML",
+        decode: decode_html,
+    },
+    &KnowledgeHandler {
+        kind: ProductKind::KnowledgePdf,
+        description: "A PDF, retrieved from as the text of its pages",
+        payload_format: "PDF file bytes",
+        decode: decode_pdf,
+    },
+    &KnowledgeHandler {
+        kind: ProductKind::KnowledgeDocx,
+        description: "A Word document, retrieved from as its paragraphs",
+        payload_format: "DOCX file bytes",
+        decode: decode_docx,
+    },
+    &KnowledgeHandler {
+        kind: ProductKind::KnowledgeMarkdown,
+        description: "Markdown, retrieved from as rendered text",
+        payload_format: "UTF-8 Markdown (CommonMark)",
+        decode: decode_markdown,
+    },
+    &KnowledgeHandler {
+        kind: ProductKind::KnowledgeCsv,
+        description: "A table, retrieved from one row at a time",
+        payload_format: "UTF-8 CSV (RFC 4180) with a header row",
+        decode: decode_csv,
+    },
+    &KnowledgeHandler {
+        kind: ProductKind::KnowledgeJson,
+        description: "Structured data, retrieved from as path: value lines",
+        payload_format: "JSON document",
+        decode: decode_json,
+    },
+    &KnowledgeHandler {
+        kind: ProductKind::KnowledgeUrl,
+        description: "A web page, fetched on use and revalidated while cached",
+        payload_format: "UTF-8 URL, https:// assumed when no scheme is given",
+        decode: decode_url,
+    },
+    &PromptsetHandler,
+    &HttpToolHandler,
+];
+
+/// Handlers of every product kind, in [`ProductKind::ALL`] order.
+pub fn registry() -> &'static [&'static dyn ProductHandler] {
+    &REGISTRY
+}
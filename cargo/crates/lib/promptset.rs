@@ -0,0 +1,263 @@
+use crate::lib::{error::ApiError, template};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Newest promptset schema version. Plain string arrays predate versioning and
+/// parse as version 0.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PromptMessage {
+    pub role: Role,
+    /// May contain `{{variable}}` placeholders.
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PromptVariable {
+    pub description: Option<String>,
+    /// Used when neither the project nor the request sets the variable.
+    pub default: Option<Value>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// The instructions behind a `promptset` product.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PromptSet {
+    pub version: u32,
+    /// Sets with a higher priority come first in the preamble. Ties keep the
+    /// order the products are enabled in.
+    #[serde(default)]
+    pub priority: i64,
+    #[serde(default)]
+    pub variables: BTreeMap<String, PromptVariable>,
+    /// System messages become instructions, user and assistant messages
+    /// few-shot examples, in the order given.
+    pub messages: Vec<PromptMessage>,
+}
+
+impl PromptSet {
+    /// Parses a versioned promptset, or a legacy array of strings taken as
+    /// system messages without templating.
+    pub fn parse(payload: &[u8]) -> Result<Self, ApiError> {
+        let invalid =
+            |e: serde_json::Error| ApiError::BadRequest(format!("Failed to parse prompts: {}", e));
+
+        let value: Value = serde_json::from_slice(payload).map_err(invalid)?;
+        if value.is_array() {
+            let prompts: Vec<String> = serde_json::from_value(value).map_err(invalid)?;
+            return Ok(PromptSet {
+                version: 0,
+                priority: 0,
+                variables: BTreeMap::new(),
+                messages: prompts
+                    .into_iter()
+                    .map(|content| PromptMessage {
+                        role: Role::System,
+                        content,
+                    })
+                    .collect(),
+            });
+        }
+
+        let set: PromptSet = serde_json::from_value(value).map_err(invalid)?;
+        if set.version == 0 || set.version > VERSION {
+            return Err(ApiError::BadRequest(format!(
+                "Unsupported promptset version {} (latest is {})",
+                set.version, VERSION
+            )));
+        }
+        Ok(set)
+    }
+
+    /// Fills the set's placeholders from `variables`, falling back to the
+    /// declared defaults.
+    fn render(&self, product: &str, variables: &Map<String, Value>) -> Result<String, ApiError> {
+        let mut out = String::new();
+        if self.version == 0 {
+            for message in &self.messages {
+                out.push_str(&message.content);
+                out.push('\n');
+            }
+            return Ok(out);
+        }
+
+        let mut data = Map::new();
+        for (name, variable) in &self.variables {
+            match (variables.get(name), &variable.default) {
+                (Some(value), _) | (None, Some(value)) => {
+                    data.insert(name.clone(), value.clone());
+                }
+                (None, None) if variable.required => {
+                    return Err(ApiError::BadRequest(format!(
+                        "Prompt product '{}' requires the variable '{}'",
+                        product, name
+                    )));
+                }
+                (None, None) => {}
+            }
+        }
+        for (name, value) in variables {
+            data.entry(name.clone()).or_insert_with(|| value.clone());
+        }
+        let data = Value::Object(data);
+
+        let mut in_example = false;
+        for message in &self.messages {
+            let content = template::render(&message.content, &data);
+            let speaker = match message.role {
+                Role::System => {
+                    in_example = false;
+                    out.push_str(&content);
+                    out.push('\n');
+                    continue;
+                }
+                Role::User => "User",
+                Role::Assistant => "Assistant",
+            };
+            if !in_example {
+                out.push_str("Example exchange:\n");
+                in_example = true;
+            }
+            out.push_str(&format!("{}: {}\n", speaker, content));
+        }
+        Ok(out)
+    }
+}
+
+/// Builds the preamble from the enabled prompt products, highest priority first.
+/// `variables` holds the project's prompt variables overridden by the request's
+/// metadata.
+pub fn preamble(
+    sets: &[(String, PromptSet)],
+    variables: &Map<String, Value>,
+) -> Result<String, ApiError> {
+    let mut ordered: Vec<&(String, PromptSet)> = sets.iter().collect();
+    ordered.sort_by_key(|(_, set)| std::cmp::Reverse(set.priority));
+
+    let mut out = String::new();
+    for (product, set) in ordered {
+        out.push_str(&set.render(product, variables)?);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(value: Value) -> PromptSet {
+        PromptSet::parse(value.to_string().as_bytes()).unwrap()
+    }
+
+    fn variables(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn reads_legacy_arrays_as_untemplated_system_messages() {
+        let set = parse(json!(["Be brief.", "Greet {{name}}."]));
+        assert_eq!(set.version, 0);
+        assert!(set.messages.iter().all(|m| m.role == Role::System));
+
+        let sets = [("legacy".to_string(), set)];
+        assert_eq!(
+            preamble(&sets, &variables(json!({ "name": "Ada" }))).unwrap(),
+            "Be brief.\nGreet {{name}}.\n"
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_versions_and_unknown_fields() {
+        for payload in [
+            json!({ "version": 0, "messages": [] }),
+            json!({ "version": VERSION + 1, "messages": [] }),
+            json!({ "version": 1, "messages": [], "temperature": 0.2 }),
+            json!({ "version": 1, "messages": [{ "role": "tool", "content": "x" }] }),
+            json!([1, 2]),
+        ] {
+            assert!(PromptSet::parse(payload.to_string().as_bytes()).is_err(), "{}", payload);
+        }
+    }
+
+    #[test]
+    fn requires_declared_variables() {
+        let sets = [(
+            "support".to_string(),
+            parse(json!({
+                "version": 1,
+                "variables": { "company": { "required": true } },
+                "messages": [{ "role": "system", "content": "You work for {{company}}." }]
+            })),
+        )];
+
+        let error = preamble(&sets, &Map::new()).unwrap_err().to_string();
+        assert!(error.contains("'support' requires the variable 'company'"), "{}", error);
+
+        assert_eq!(
+            preamble(&sets, &variables(json!({ "company": "Acme" }))).unwrap(),
+            "You work for Acme.\n"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_defaults_and_renders_examples() {
+        let sets = [(
+            "tone".to_string(),
+            parse(json!({
+                "version": 1,
+                "variables": { "tone": { "default": "friendly" } },
+                "messages": [
+                    { "role": "system", "content": "Be {{tone}}." },
+                    { "role": "user", "content": "Hi" },
+                    { "role": "assistant", "content": "Hello {{name}}!" },
+                    { "role": "system", "content": "Sign off as {{signature}}." }
+                ]
+            })),
+        )];
+
+        assert_eq!(
+            preamble(&sets, &variables(json!({ "signature": "Haithe" }))).unwrap(),
+            "Be friendly.\nExample exchange:\nUser: Hi\nAssistant: Hello !\nSign off as Haithe.\n"
+        );
+        assert!(
+            preamble(&sets, &variables(json!({ "tone": "formal" })))
+                .unwrap()
+                .starts_with("Be formal.\n")
+        );
+    }
+
+    #[test]
+    fn orders_sets_by_priority_keeping_ties_in_order() {
+        let set = |priority: i64, content: &str| {
+            parse(json!({
+                "version": 1,
+                "priority": priority,
+                "messages": [{ "role": "system", "content": content }]
+            }))
+        };
+        let sets = [
+            ("a".to_string(), set(0, "A")),
+            ("b".to_string(), set(5, "B")),
+            ("c".to_string(), set(0, "C")),
+            ("d".to_string(), parse(json!(["D"]))),
+            ("e".to_string(), set(-1, "E")),
+        ];
+
+        assert_eq!(preamble(&sets, &Map::new()).unwrap(), "B\nA\nC\nD\nE\n");
+    }
+}
@@ -0,0 +1,353 @@
+use crate::lib::db::usage::{self, Since};
+use crate::lib::db::{self, Db};
+use crate::lib::extractors::ApiCaller;
+use crate::lib::{error::ApiError, respond, state::AppState};
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpRequest, web};
+use chrono::{Duration as ChronoDuration, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct RateLimit {
+    pub id: i64,
+    pub org_id: i64,
+    pub scope: String,
+    pub target: String,
+    pub requests_per_minute: Option<i64>,
+    pub max_concurrent: Option<i64>,
+    pub daily_spend_limit: Option<i64>,
+    pub updated_at: String,
+}
+
+impl RateLimit {
+    fn counter_key(&self) -> String {
+        format!("{}:{}:{}", self.org_id, self.scope, self.target)
+    }
+}
+
+/// In-memory request counters, keyed by `org_id:scope:target`.
+#[derive(Default)]
+pub struct RateLimiter {
+    windows: HashMap<String, VecDeque<Instant>>,
+    concurrent: HashMap<String, i64>,
+    last_sweep: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Requests seen in the last minute and, if any, how long until the oldest one expires.
+    fn window_usage(&mut self, key: &str, now: Instant) -> (i64, Duration) {
+        let Some(window) = self.windows.get_mut(key) else {
+            return (0, WINDOW);
+        };
+        while window
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= WINDOW)
+        {
+            window.pop_front();
+        }
+
+        let Some(oldest) = window.front() else {
+            self.windows.remove(key);
+            return (0, WINDOW);
+        };
+
+        let reset = WINDOW.saturating_sub(now.duration_since(*oldest));
+        (window.len() as i64, reset)
+    }
+
+    /// Drops the windows of keys that have not been hit for a minute, such as
+    /// limits that were since removed, at most once a minute.
+    fn sweep(&mut self, now: Instant) {
+        if self
+            .last_sweep
+            .is_some_and(|last| now.duration_since(last) < WINDOW)
+        {
+            return;
+        }
+        self.last_sweep = Some(now);
+        self.windows.retain(|_, window| {
+            window
+                .back()
+                .is_some_and(|t| now.duration_since(*t) < WINDOW)
+        });
+    }
+
+    fn concurrent_usage(&self, key: &str) -> i64 {
+        self.concurrent.get(key).copied().unwrap_or(0)
+    }
+
+    fn release(&mut self, keys: &[String]) {
+        for key in keys {
+            if let Some(count) = self.concurrent.get_mut(key) {
+                *count -= 1;
+                if *count <= 0 {
+                    self.concurrent.remove(key);
+                }
+            }
+        }
+    }
+}
+
+/// Decrements the concurrency counters once the request has finished, even if it errored.
+/// It rides in the request's extensions, so handlers that keep working after the
+/// response has started, such as streamed replies, can take it along.
+pub struct ConcurrencyGuard {
+    state: web::Data<AppState>,
+    keys: Vec<String>,
+}
+
+impl ConcurrencyGuard {
+    pub fn take(req: &HttpRequest) -> Option<Self> {
+        req.extensions_mut().remove::<Self>()
+    }
+}
+
+impl Drop for ConcurrencyGuard {
+    fn drop(&mut self) {
+        if let Ok(mut limiter) = self.state.rate_limiter.lock() {
+            limiter.release(&self.keys);
+        }
+    }
+}
+
+struct Rejection {
+    message: String,
+    retry_after: u64,
+    limit: Option<i64>,
+}
+
+struct Allowance {
+    limit: i64,
+    remaining: i64,
+    reset: u64,
+}
+
+pub async fn limits_for_caller(
+    db: &Db,
+    org_id: i64,
+    project_uid: &str,
+    wallet_address: &str,
+) -> Result<Vec<RateLimit>, sqlx::Error> {
+    db::query_as::<RateLimit>(
+        "SELECT * FROM rate_limits WHERE org_id = ? AND (
+            scope = 'org'
+            OR (scope = 'project' AND target = ?)
+            OR (scope = 'key' AND target = ?)
+        )",
+    )
+    .bind(org_id)
+    .bind(project_uid)
+    .bind(wallet_address.to_lowercase())
+    .fetch_all(db)
+    .await
+}
+
+/// Spend since UTC midnight for the scope a limit applies to.
+pub async fn spent_today(db: &Db, limit: &RateLimit) -> Result<i64, sqlx::Error> {
+    match limit.scope.as_str() {
+        "project" => usage::project_uid_spent(db, limit.org_id, &limit.target, Since::Today).await,
+        "key" => usage::wallet_spent(db, limit.org_id, &limit.target, Since::Today).await,
+        _ => usage::org_spent(db, limit.org_id, Since::Today).await,
+    }
+}
+
+fn seconds_until_utc_midnight() -> u64 {
+    let now = Utc::now();
+    let tomorrow = (now + ChronoDuration::days(1))
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc();
+    (tomorrow - now).num_seconds().max(1) as u64
+}
+
+fn scope_label(limit: &RateLimit) -> &'static str {
+    match limit.scope.as_str() {
+        "project" => "project",
+        "key" => "API key",
+        _ => "organization",
+    }
+}
+
+fn too_many_requests<B>(req: ServiceRequest, rejection: Rejection) -> ServiceResponse<EitherBody<B>> {
+    let mut res = respond::err(&rejection.message, 429);
+    let headers = res.headers_mut();
+    headers.insert(
+        HeaderName::from_static("retry-after"),
+        HeaderValue::from(rejection.retry_after),
+    );
+    if let Some(limit) = rejection.limit {
+        headers.insert(
+            HeaderName::from_static("x-ratelimit-limit"),
+            HeaderValue::from(limit),
+        );
+        headers.insert(
+            HeaderName::from_static("x-ratelimit-remaining"),
+            HeaderValue::from(0),
+        );
+        headers.insert(
+            HeaderName::from_static("x-ratelimit-reset"),
+            HeaderValue::from(rejection.retry_after),
+        );
+    }
+
+    req.into_response(res).map_into_right_body()
+}
+
+/// Enforces the org, project and key level limits configured in `rate_limits`
+/// for requests made by an `ApiCaller`.
+pub async fn enforce(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let caller = req.extract::<ApiCaller>().await?;
+
+    let Some(state) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+
+    let org_id: i64 = db::query_scalar("SELECT id FROM organizations WHERE organization_uid = ?")
+        .bind(&caller.org_uid)
+        .fetch_one(&state.db)
+        .await
+        .map_err(ApiError::from)?;
+
+    let limits = limits_for_caller(
+        &state.db,
+        org_id,
+        &caller.project_uid,
+        &caller.wallet_address,
+    )
+    .await
+    .map_err(ApiError::from)?;
+
+    if limits.is_empty() {
+        return Ok(next.call(req).await?.map_into_left_body());
+    }
+
+    for limit in &limits {
+        if let Some(daily_spend_limit) = limit.daily_spend_limit {
+            let spent = spent_today(&state.db, limit)
+                .await
+                .map_err(ApiError::from)?;
+            if spent >= daily_spend_limit {
+                return Ok(too_many_requests(
+                    req,
+                    Rejection {
+                        message: format!(
+                            "Daily spend limit of {} reached for this {}",
+                            daily_spend_limit,
+                            scope_label(limit)
+                        ),
+                        retry_after: seconds_until_utc_midnight(),
+                        limit: None,
+                    },
+                ));
+            }
+        }
+    }
+
+    let mut allowance: Option<Allowance> = None;
+    let mut concurrency_keys = Vec::new();
+
+    {
+        let mut limiter = state.rate_limiter.lock().unwrap();
+        let now = Instant::now();
+        limiter.sweep(now);
+
+        // Check every level before counting the request against any of them
+        for limit in &limits {
+            let key = limit.counter_key();
+
+            if let Some(rpm) = limit.requests_per_minute {
+                let (used, reset) = limiter.window_usage(&key, now);
+                if used >= rpm {
+                    drop(limiter);
+                    return Ok(too_many_requests(
+                        req,
+                        Rejection {
+                            message: format!(
+                                "Rate limit of {} requests per minute exceeded for this {}",
+                                rpm,
+                                scope_label(limit)
+                            ),
+                            retry_after: reset.as_secs().max(1),
+                            limit: Some(rpm),
+                        },
+                    ));
+                }
+
+                let remaining = rpm - used - 1;
+                if allowance.as_ref().is_none_or(|a| remaining < a.remaining) {
+                    allowance = Some(Allowance {
+                        limit: rpm,
+                        remaining,
+                        reset: reset.as_secs().max(1),
+                    });
+                }
+            }
+
+            if let Some(max_concurrent) = limit.max_concurrent {
+                if limiter.concurrent_usage(&key) >= max_concurrent {
+                    drop(limiter);
+                    return Ok(too_many_requests(
+                        req,
+                        Rejection {
+                            message: format!(
+                                "Too many concurrent requests for this {} (limit {})",
+                                scope_label(limit),
+                                max_concurrent
+                            ),
+                            retry_after: 1,
+                            limit: None,
+                        },
+                    ));
+                }
+            }
+        }
+
+        for limit in &limits {
+            let key = limit.counter_key();
+            if limit.requests_per_minute.is_some() {
+                limiter.windows.entry(key.clone()).or_default().push_back(now);
+            }
+            if limit.max_concurrent.is_some() {
+                *limiter.concurrent.entry(key.clone()).or_insert(0) += 1;
+                concurrency_keys.push(key);
+            }
+        }
+    }
+
+    req.extensions_mut().insert(ConcurrencyGuard {
+        state: state.clone(),
+        keys: concurrency_keys,
+    });
+
+    let mut res = next.call(req).await?;
+
+    if let Some(allowance) = allowance {
+        let headers = res.headers_mut();
+        headers.insert(
+            HeaderName::from_static("x-ratelimit-limit"),
+            HeaderValue::from(allowance.limit),
+        );
+        headers.insert(
+            HeaderName::from_static("x-ratelimit-remaining"),
+            HeaderValue::from(allowance.remaining.max(0)),
+        );
+        headers.insert(
+            HeaderName::from_static("x-ratelimit-reset"),
+            HeaderValue::from(allowance.reset),
+        );
+    }
+
+    Ok(res.map_into_left_body())
+}
@@ -0,0 +1,361 @@
+use crate::lib::config::{self, EmbedderKind};
+use crate::lib::db::{self, Db};
+use crate::lib::error::ApiError;
+use crate::lib::fetch;
+use crate::lib::knowledge::ProductContent;
+use serde::Serialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+/// Target chunk length in characters, and how much consecutive chunks share.
+const CHUNK_CHARS: usize = 1200;
+const CHUNK_OVERLAP: usize = 200;
+
+const LOCAL_DIMENSIONS: usize = 384;
+const OPENAI_MODEL: &str = "text-embedding-3-small";
+const OPENAI_BATCH: usize = 64;
+
+/// Turns text into vectors. Vectors from different embedders are never compared,
+/// chunks are stored per embedder name.
+pub enum Embedder {
+    Local,
+    OpenAi { api_key: String },
+}
+
+/// A retrieved chunk, returned to callers as a citation.
+#[derive(Debug, Clone, Serialize)]
+pub struct Citation {
+    /// The `[n]` the model was told to cite the passage by.
+    pub index: usize,
+    pub product_id: i64,
+    pub product_name: String,
+    pub chunk_index: i64,
+    pub score: f32,
+    pub text: String,
+}
+
+/// Decrypted knowledge of one product, ready to be indexed.
+pub struct Document {
+    pub product_id: i64,
+    pub product_name: String,
+    pub text: String,
+}
+
+impl Document {
+    /// Text of knowledge content, `None` for content that is not retrieved from.
+    pub fn from_content(
+        product_id: i64,
+        product_name: &str,
+        content: &ProductContent,
+    ) -> Option<Self> {
+        let text = match content {
+            ProductContent::Text(text) => text.clone(),
+            ProductContent::Html { html, .. } | ProductContent::Page { html, .. } => {
+                html_to_text(html)
+            }
+            ProductContent::Prompts(_) | ProductContent::Tool(_) => return None,
+        };
+
+        Some(Document {
+            product_id,
+            product_name: product_name.to_string(),
+            text,
+        })
+    }
+}
+
+impl Embedder {
+    pub fn from_config() -> Self {
+        let config = config::get();
+        match (config.embedder, &config.providers.openai_api_key) {
+            (EmbedderKind::Openai, Some(api_key)) => Embedder::OpenAi {
+                api_key: api_key.expose().to_string(),
+            },
+            _ => Embedder::Local,
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Embedder::Local => format!("local-hash-{}", LOCAL_DIMENSIONS),
+            Embedder::OpenAi { .. } => format!("openai:{}", OPENAI_MODEL),
+        }
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ApiError> {
+        match self {
+            Embedder::Local => Ok(texts.iter().map(|text| hash_embedding(text)).collect()),
+            Embedder::OpenAi { api_key } => {
+                let client = fetch::trusted_client();
+                let mut vectors = Vec::with_capacity(texts.len());
+
+                for batch in texts.chunks(OPENAI_BATCH) {
+                    let response: Value = client
+                        .post("https://api.openai.com/v1/embeddings")
+                        .bearer_auth(api_key)
+                        .json(&json!({ "model": OPENAI_MODEL, "input": batch }))
+                        .send()
+                        .await?
+                        .error_for_status()?
+                        .json()
+                        .await?;
+
+                    let data = response["data"].as_array().ok_or_else(|| {
+                        ApiError::Internal("Embedding response has no data".to_string())
+                    })?;
+                    for item in data {
+                        let vector = item["embedding"]
+                            .as_array()
+                            .ok_or_else(|| {
+                                ApiError::Internal("Embedding response is malformed".to_string())
+                            })?
+                            .iter()
+                            .filter_map(Value::as_f64)
+                            .map(|v| v as f32)
+                            .collect();
+                        vectors.push(vector);
+                    }
+                }
+
+                Ok(vectors)
+            }
+        }
+    }
+}
+
+/// FNV-1a, stable across builds so stored local vectors stay valid.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Signed feature hashing of words and word pairs, L2 normalised.
+fn hash_embedding(text: &str) -> Vec<f32> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+
+    let mut vector = vec![0f32; LOCAL_DIMENSIONS];
+    let pairs = words
+        .windows(2)
+        .map(|pair| format!("{} {}", pair[0], pair[1]));
+    for feature in words.iter().cloned().chain(pairs) {
+        let hash = fnv1a(feature.as_bytes());
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        vector[(hash % LOCAL_DIMENSIONS as u64) as usize] += sign;
+    }
+
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|v| v * v).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Splits text into windows of about [`CHUNK_CHARS`] on word boundaries, each
+/// sharing roughly [`CHUNK_OVERLAP`] characters with the one before.
+pub fn chunk(text: &str) -> Vec<String> {
+    let words: Vec<(usize, usize)> = text
+        .split_whitespace()
+        .map(|word| {
+            let start = word.as_ptr() as usize - text.as_ptr() as usize;
+            (start, start + word.len())
+        })
+        .collect();
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < words.len() {
+        let begin = words[start].0;
+        let mut end = start + 1;
+        while end < words.len() && words[end].1 - begin <= CHUNK_CHARS {
+            end += 1;
+        }
+
+        let finish = words[end - 1].1;
+        chunks.push(text[begin..finish].to_string());
+        if end == words.len() {
+            break;
+        }
+
+        let mut next = end;
+        while next > start + 1 && finish - words[next - 1].0 < CHUNK_OVERLAP {
+            next -= 1;
+        }
+        start = next;
+    }
+
+    chunks
+}
+
+/// Visible text of an HTML page, without scripts, styles or markup.
+fn html_to_text(html: &str) -> String {
+    // ASCII lowercasing keeps byte offsets, so positions in `lower` index `html`
+    let lower = html.to_ascii_lowercase();
+    let mut text = String::with_capacity(html.len());
+    let mut pos = 0;
+
+    while let Some(open) = html[pos..].find('<').map(|i| pos + i) {
+        text.push_str(&html[pos..open]);
+        text.push(' ');
+
+        // Skip the contents of script and style elements along with the tags
+        let tag_start = ["script", "style"]
+            .iter()
+            .find(|name| lower[open + 1..].starts_with(*name))
+            .and_then(|name| lower[open..].find(&format!("</{}", name)))
+            .map_or(open, |i| open + i);
+
+        pos = html[tag_start..]
+            .find('>')
+            .map_or(html.len(), |i| tag_start + i + 1);
+    }
+    text.push_str(&html[pos..]);
+
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Chunks and embeds a product's text, unless the same text is already indexed
+/// for this embedder.
+pub async fn index(db: &Db, embedder: &Embedder, document: &Document) -> Result<(), ApiError> {
+    let content_hash = hex::encode(Sha256::digest(document.text.as_bytes()));
+    let embedder_name = embedder.name();
+
+    let indexed: Option<String> = db::query_scalar(
+        "SELECT content_hash FROM knowledge_chunks WHERE product_id = ? AND embedder = ? LIMIT 1",
+    )
+    .bind(document.product_id)
+    .bind(&embedder_name)
+    .fetch_optional(db)
+    .await?;
+
+    if indexed.as_deref() == Some(content_hash.as_str()) {
+        return Ok(());
+    }
+
+    let chunks = chunk(&document.text);
+    let vectors = embedder.embed(&chunks).await?;
+    tracing::info!(
+        product_id = document.product_id,
+        chunks = chunks.len(),
+        embedder = %embedder_name,
+        "Indexing product knowledge"
+    );
+
+    let mut tx = db.begin().await?;
+    db::query("DELETE FROM knowledge_chunks WHERE product_id = ? AND embedder = ?")
+        .bind(document.product_id)
+        .bind(&embedder_name)
+        .execute(&mut tx)
+        .await?;
+
+    for (chunk_index, (content, vector)) in chunks.iter().zip(&vectors).enumerate() {
+        db::query(
+            "INSERT INTO knowledge_chunks (product_id, embedder, content_hash, chunk_index, content, embedding)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT (product_id, embedder, chunk_index) DO UPDATE SET
+                 content_hash = excluded.content_hash,
+                 content = excluded.content,
+                 embedding = excluded.embedding",
+        )
+        .bind(document.product_id)
+        .bind(&embedder_name)
+        .bind(&content_hash)
+        .bind(chunk_index as i64)
+        .bind(content)
+        .bind(json!(vector).to_string())
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// The `top_k` chunks of the given products most similar to `query`.
+pub async fn retrieve(
+    db: &Db,
+    embedder: &Embedder,
+    documents: &[Document],
+    query: &str,
+    top_k: usize,
+) -> Result<Vec<Citation>, ApiError> {
+    if documents.is_empty() || top_k == 0 {
+        return Ok(Vec::new());
+    }
+
+    let query_vector = embedder
+        .embed(&[query.to_string()])
+        .await?
+        .pop()
+        .unwrap_or_default();
+    let embedder_name = embedder.name();
+
+    let mut scored = Vec::new();
+    for document in documents {
+        let chunks = db::query_as::<(i64, String, String)>(
+            "SELECT chunk_index, content, embedding FROM knowledge_chunks WHERE product_id = ? AND embedder = ?",
+        )
+        .bind(document.product_id)
+        .bind(&embedder_name)
+        .fetch_all(db)
+        .await?;
+
+        for (chunk_index, text, embedding) in chunks {
+            let vector: Vec<f32> = serde_json::from_str(&embedding)
+                .map_err(|e| ApiError::Internal(format!("Stored embedding is malformed: {}", e)))?;
+            scored.push(Citation {
+                index: 0,
+                product_id: document.product_id,
+                product_name: document.product_name.clone(),
+                chunk_index,
+                score: cosine(&query_vector, &vector),
+                text,
+            });
+        }
+    }
+
+    scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+    scored.truncate(top_k);
+    for (i, citation) in scored.iter_mut().enumerate() {
+        citation.index = i + 1;
+    }
+
+    Ok(scored)
+}
+
+/// Knowledge text handed to the agent, numbered so answers can cite it.
+pub fn context(citations: &[Citation]) -> String {
+    let mut context = String::from(
+        "Relevant excerpts from the project's knowledge products. When you use one, cite it by its number, e.g. [1].\n",
+    );
+    for citation in citations {
+        context.push_str(&format!(
+            "\n[{}] {}\n{}\n",
+            citation.index, citation.product_name, citation.text
+        ));
+    }
+    context
+}
@@ -0,0 +1,170 @@
+use crate::lib::error::ApiError;
+use crate::lib::models::Model;
+use rand::Rng;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Longest wait between two attempts on a model.
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+/// Weight of the newest sample in a model's average latency.
+const LATENCY_WEIGHT: f64 = 0.2;
+
+/// How a project orders the models a completion may run on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// The requested model, then the fallbacks as listed.
+    Ordered,
+    /// Cheapest `price_per_call` first.
+    Cost,
+    /// Lowest average latency seen by this server first.
+    Latency,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 3] = [Strategy::Ordered, Strategy::Cost, Strategy::Latency];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Strategy::Ordered => "ordered",
+            Strategy::Cost => "cost",
+            Strategy::Latency => "latency",
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Strategy::ALL
+            .into_iter()
+            .find(|strategy| strategy.as_str() == s)
+            .ok_or_else(|| {
+                ApiError::BadRequest(format!(
+                    "Unknown routing strategy '{}', expected ordered, cost or latency",
+                    s
+                ))
+            })
+    }
+}
+
+fn latencies() -> &'static Mutex<HashMap<String, f64>> {
+    static LATENCIES: OnceLock<Mutex<HashMap<String, f64>>> = OnceLock::new();
+    LATENCIES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Folds a successful call's duration into the model's average latency.
+pub fn record_latency(model: &str, elapsed: Duration) {
+    let sample = elapsed.as_secs_f64();
+    let mut latencies = latencies().lock().unwrap();
+    latencies
+        .entry(model.to_string())
+        .and_modify(|average| *average += LATENCY_WEIGHT * (sample - *average))
+        .or_insert(sample);
+}
+
+/// The requested model and the project's fallbacks, in the order they are
+/// tried. Fallbacks that are inactive, listed twice or not enrolled for the
+/// organization are left out.
+pub fn candidates<'a>(
+    models: &'a [Model],
+    requested: &'a Model,
+    fallback_ids: &[i64],
+    enrolled: &[u64],
+    strategy: Strategy,
+) -> Vec<&'a Model> {
+    let mut candidates = vec![requested];
+    for id in fallback_ids {
+        let Some(model) = models.iter().find(|m| m.id as i64 == *id) else {
+            continue;
+        };
+        if model.is_active
+            && enrolled.contains(&model.id)
+            && !candidates.iter().any(|c| c.id == model.id)
+        {
+            candidates.push(model);
+        }
+    }
+
+    match strategy {
+        Strategy::Ordered => {}
+        Strategy::Cost => candidates.sort_by_key(|m| m.price_per_call),
+        Strategy::Latency => {
+            // Models without measurements keep their order, after the measured ones
+            let latencies = latencies().lock().unwrap();
+            candidates.sort_by(|a, b| {
+                let a = latencies.get(&a.name).copied().unwrap_or(f64::INFINITY);
+                let b = latencies.get(&b.name).copied().unwrap_or(f64::INFINITY);
+                a.total_cmp(&b)
+            });
+        }
+    }
+    candidates
+}
+
+/// Wait before retry number `retry` (from 1): `base` doubled on every retry,
+/// capped at 10 seconds, with jitter so clients do not retry in lockstep.
+pub fn backoff(base: Duration, retry: u32) -> Duration {
+    let delay = base
+        .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+        .min(MAX_BACKOFF);
+    delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+}
+
+/// Whether a failed model call may go through if made again: rate limits,
+/// timeouts, dropped connections and provider server errors. Anything else,
+/// such as an invalid request, a prompt over the context length or a rejected
+/// key, fails the same way every time. Providers only report these in the
+/// error text, so it is matched on that.
+pub fn is_transient(error: &str) -> bool {
+    const PHRASES: &[&str] = &[
+        "rate limit",
+        "rate_limit",
+        "too many requests",
+        "timed out",
+        "timeout",
+        "overloaded",
+        "unavailable",
+        "connection",
+        "server error",
+    ];
+
+    let error = error.to_lowercase();
+    PHRASES.iter().any(|phrase| error.contains(phrase))
+        || error
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter_map(|token| token.parse::<u16>().ok())
+            .any(|status| status == 429 || (500..600).contains(&status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_only_transient_errors() {
+        for error in [
+            "HTTP status 429: Too Many Requests",
+            "Rate limit reached for gpt-4o",
+            "request timed out",
+            "error sending request: connection reset by peer",
+            "status 503 Service Unavailable",
+            "ProviderError: 500",
+            "Anthropic API is overloaded",
+        ] {
+            assert!(is_transient(error), "{}", error);
+        }
+
+        for error in [
+            "HTTP status 400: invalid_request_error",
+            "This model's maximum context length is 128000 tokens",
+            "401 Unauthorized: Incorrect API key provided",
+            "403 Forbidden",
+            "model_not_found",
+        ] {
+            assert!(!is_transient(error), "{}", error);
+        }
+    }
+}
@@ -1,5 +1,7 @@
+use crate::lib::db::Db;
+use crate::lib::knowledge::KnowledgeCache;
+use crate::lib::ratelimit::RateLimiter;
 use alith::WindowBufferMemory;
-use sqlx::SqlitePool;
 use std::collections::HashMap;
 use std::sync::Mutex;
 use tokio::task::JoinHandle;
@@ -14,8 +16,10 @@ pub struct DiscordBotHandle {
 
 pub struct AppState {
     pub nonce_registry: Mutex<HashMap<String, String>>,
-    pub db: SqlitePool,
+    pub db: Db,
     pub window_buffer_memory: Mutex<HashMap<String, WindowBufferMemory>>,
     pub discord_bots: Mutex<HashMap<String, DiscordBotHandle>>,
     pub telegram_bots: Mutex<HashMap<String, TelegramBotHandle>>,
+    pub rate_limiter: Mutex<RateLimiter>,
+    pub knowledge_cache: KnowledgeCache,
 }
@@ -1,7 +1,9 @@
+use crate::lib::{db, metrics};
 use crate::lib::llm::{LlmResponseParams, generate_llm_response};
 use crate::lib::models::get_model_by_id;
 use crate::lib::state::AppState;
 use crate::lib::state::TelegramBotHandle;
+use crate::lib::webhooks;
 use actix_web::web;
 use anyhow::Error;
 use serde_json::json;
@@ -41,10 +43,27 @@ async fn start_bot(
                         temperature: 0.7,
                         n: 1,
                         org_uid,
-                        project_uid,
+                        project_uid: project_uid.clone(),
+                        wallet_address: None,
+                        metadata: serde_json::Map::from_iter([(
+                            "platform".to_string(),
+                            json!("telegram"),
+                        )]),
+                        invocation_id: None,
+                        timeout: None,
                     };
 
-                    match generate_llm_response(params, state.get_ref()).await {
+                    let result = generate_llm_response(params, state.get_ref()).await;
+                    metrics::inc(
+                        "haithe_bot_messages_total",
+                        &[
+                            ("platform", "telegram"),
+                            ("project_uid", &project_uid),
+                            ("status", if result.is_ok() { "ok" } else { "error" }),
+                        ],
+                    );
+
+                    match result {
                         Ok(response) => {
                             if let Some(choice) = response.choices.first() {
                                 if let Some(content) = choice
@@ -53,7 +72,7 @@ async fn start_bot(
                                     .and_then(|c| c.as_str())
                                 {
                                     if let Err(e) = bot.send_message(msg.chat.id, content).await {
-                                        eprintln!("Failed to send message: {}", e);
+                                        tracing::error!(error = %e, "Failed to send Telegram message");
                                     }
                                 } else {
                                     if let Err(e) = bot
@@ -63,7 +82,7 @@ async fn start_bot(
                                         )
                                         .await
                                     {
-                                        eprintln!("Failed to send message: {}", e);
+                                        tracing::error!(error = %e, "Failed to send Telegram message");
                                     }
                                 }
                             } else {
@@ -74,7 +93,7 @@ async fn start_bot(
                                     )
                                     .await
                                 {
-                                    eprintln!("Failed to send message: {}", e);
+                                    tracing::error!(error = %e, "Failed to send Telegram message");
                                 }
                             }
                         }
@@ -82,8 +101,16 @@ async fn start_bot(
                             if let Err(send_err) =
                                 bot.send_message(msg.chat.id, format!("Error: {}", e)).await
                             {
-                                eprintln!("Failed to send error message: {}", send_err);
+                                tracing::error!(error = %send_err, "Failed to send Telegram error message");
                             }
+
+                            webhooks::emit_for_project_uid(
+                                &state.db,
+                                &project_uid,
+                                "bot.error",
+                                json!({ "platform": "telegram", "error": e.to_string() }),
+                            )
+                            .await;
                         }
                     }
                 } else {
@@ -96,10 +123,27 @@ async fn start_bot(
                         temperature: 0.7,
                         n: 1,
                         org_uid,
-                        project_uid,
+                        project_uid: project_uid.clone(),
+                        wallet_address: None,
+                        metadata: serde_json::Map::from_iter([(
+                            "platform".to_string(),
+                            json!("telegram"),
+                        )]),
+                        invocation_id: None,
+                        timeout: None,
                     };
 
-                    match generate_llm_response(params, state.get_ref()).await {
+                    let result = generate_llm_response(params, state.get_ref()).await;
+                    metrics::inc(
+                        "haithe_bot_messages_total",
+                        &[
+                            ("platform", "telegram"),
+                            ("project_uid", &project_uid),
+                            ("status", if result.is_ok() { "ok" } else { "error" }),
+                        ],
+                    );
+
+                    match result {
                         Ok(response) => {
                             if let Some(choice) = response.choices.first() {
                                 if let Some(content) = choice
@@ -108,7 +152,7 @@ async fn start_bot(
                                     .and_then(|c| c.as_str())
                                 {
                                     if let Err(e) = bot.send_message(msg.chat.id, content).await {
-                                        eprintln!("Failed to send message: {}", e);
+                                        tracing::error!(error = %e, "Failed to send Telegram message");
                                     }
                                 } else {
                                     if let Err(e) = bot
@@ -118,7 +162,7 @@ async fn start_bot(
                                         )
                                         .await
                                     {
-                                        eprintln!("Failed to send message: {}", e);
+                                        tracing::error!(error = %e, "Failed to send Telegram message");
                                     }
                                 }
                             } else {
@@ -129,7 +173,7 @@ async fn start_bot(
                                     )
                                     .await
                                 {
-                                    eprintln!("Failed to send message: {}", e);
+                                    tracing::error!(error = %e, "Failed to send Telegram message");
                                 }
                             }
                         }
@@ -137,8 +181,16 @@ async fn start_bot(
                             if let Err(send_err) =
                                 bot.send_message(msg.chat.id, format!("Error: {}", e)).await
                             {
-                                eprintln!("Failed to send error message: {}", send_err);
+                                tracing::error!(error = %send_err, "Failed to send Telegram error message");
                             }
+
+                            webhooks::emit_for_project_uid(
+                                &state.db,
+                                &project_uid,
+                                "bot.error",
+                                json!({ "platform": "telegram", "error": e.to_string() }),
+                            )
+                            .await;
                         }
                     }
                 }
@@ -173,7 +225,7 @@ pub async fn sync_bots(state: web::Data<AppState>) -> Result<(), Error> {
         default_model_id: Option<i64>,
     }
 
-    let rows: Vec<Row> = sqlx::query_as::<_, Row>(
+    let rows: Vec<Row> = db::query_as::<Row>(
         r#"
         SELECT pr.teloxide_token as token,
                o.organization_uid as org_uid,
@@ -0,0 +1,76 @@
+use serde_json::Value;
+
+/// Looks up a dotted path such as `user.name` or `items.0.id` in a JSON value.
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |current, segment| match current {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+}
+
+fn stringify(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Replaces every `{{path}}` placeholder with the matching field of `data`.
+/// `{{.}}` inserts the whole value; unknown paths render as empty strings.
+pub fn render(template: &str, data: &Value) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+
+        let Some(end) = after.find("}}") else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+
+        let path = after[..end].trim();
+        let value = if path == "." {
+            Some(data)
+        } else {
+            lookup(data, path)
+        };
+        if let Some(value) = value {
+            out.push_str(&stringify(value));
+        }
+
+        rest = &after[end + 2..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn renders_paths_into_placeholders() {
+        let data = json!({
+            "user": { "name": "Ada" },
+            "items": [{ "id": 1 }, { "id": "b" }],
+            "count": 3,
+            "note": null
+        });
+        assert_eq!(
+            render("Hi {{ user.name }}, item {{items.1.id}} {{missing}}!{{count}}{{note}} {{", &data),
+            "Hi Ada, item b !3 {{"
+        );
+        assert_eq!(render("{{items.0}}", &data), r#"{"id":1}"#);
+        assert_eq!(render("got {{.}}", &json!("x")), "got x");
+        assert_eq!(lookup(&data, "items.2"), None);
+        assert_eq!(lookup(&data, "user.name.first"), None);
+    }
+}
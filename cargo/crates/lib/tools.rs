@@ -0,0 +1,228 @@
+use crate::lib::fetch::{self, host_matches};
+use crate::lib::{config, error::ApiError, metrics};
+use alith::{Tool, ToolDefinition, ToolError};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// The endpoint behind a `tool:http` product, as described by its decrypted payload.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpToolSpec {
+    /// Function name shown to the model, `[A-Za-z0-9_-]{1,64}`.
+    pub name: String,
+    pub description: String,
+    pub url: String,
+    /// `GET` sends the arguments as the query string, `POST` as a JSON body.
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// Wraps the arguments in a JSON-RPC 2.0 request for this method and
+    /// returns its `result`.
+    pub rpc_method: Option<String>,
+    /// JSON schema of the arguments.
+    #[serde(default = "empty_schema")]
+    pub parameters: Value,
+    /// Sent with every call, e.g. the creator's API key.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Capped by `TOOL_TIMEOUT_SECS`.
+    pub timeout_secs: Option<u64>,
+}
+
+fn default_method() -> String {
+    "POST".to_string()
+}
+
+fn empty_schema() -> Value {
+    json!({ "type": "object", "properties": {} })
+}
+
+fn allowed_url(raw: &str) -> Result<url::Url, String> {
+    let url = url::Url::parse(raw).map_err(|e| format!("Invalid tool URL: {}", e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("Tool URL must be http(s): {}", raw));
+    }
+
+    match url.host_str() {
+        Some(host) if host_matches(&config::get().tool_allowed_hosts, host) => Ok(url),
+        Some(host) => Err(format!("Tool host '{}' is not in TOOL_ALLOWED_HOSTS", host)),
+        None => Err(format!("Tool URL has no host: {}", raw)),
+    }
+}
+
+impl HttpToolSpec {
+    pub fn parse(payload: &[u8]) -> Result<Self, ApiError> {
+        let mut spec: HttpToolSpec = serde_json::from_slice(payload)
+            .map_err(|e| ApiError::BadRequest(format!("Failed to parse tool spec: {}", e)))?;
+        spec.method = spec.method.to_uppercase();
+
+        let valid_name = !spec.name.is_empty()
+            && spec.name.len() <= 64
+            && spec
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid_name {
+            return Err(ApiError::BadRequest(format!(
+                "Tool name '{}' must be 1-64 letters, digits, '_' or '-'",
+                spec.name
+            )));
+        }
+        if !matches!(spec.method.as_str(), "GET" | "POST") {
+            return Err(ApiError::BadRequest(format!(
+                "Tool method must be GET or POST, got '{}'",
+                spec.method
+            )));
+        }
+        if spec.rpc_method.is_some() && spec.method != "POST" {
+            return Err(ApiError::BadRequest(
+                "JSON-RPC tools must use POST".to_string(),
+            ));
+        }
+        if !spec.parameters.is_object() {
+            return Err(ApiError::BadRequest(
+                "Tool parameters must be a JSON schema object".to_string(),
+            ));
+        }
+        allowed_url(&spec.url).map_err(ApiError::BadRequest)?;
+
+        Ok(spec)
+    }
+}
+
+/// A tool product handed to the agent. Clones share their call counters, so the
+/// completion can bill the calls made through the agent's copy.
+#[derive(Clone)]
+pub struct HttpTool {
+    spec: Arc<HttpToolSpec>,
+    pub address: String,
+    pub creator: String,
+    pub price_per_call: u64,
+    attempts: Arc<AtomicU64>,
+    calls: Arc<AtomicU64>,
+}
+
+impl HttpTool {
+    pub fn new(spec: HttpToolSpec, address: String, creator: String, price_per_call: u64) -> Self {
+        HttpTool {
+            spec: Arc::new(spec),
+            address,
+            creator,
+            price_per_call,
+            attempts: Arc::new(AtomicU64::new(0)),
+            calls: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Successful calls so far, the ones the creator is paid for.
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    async fn call(&self, args: Value) -> Result<Value, String> {
+        let url = allowed_url(&self.spec.url)?;
+        let limit = config::get().tool_timeout_secs;
+        let timeout = Duration::from_secs(self.spec.timeout_secs.map_or(limit, |t| t.min(limit)));
+
+        // Redirects could lead off the tool allowlist, so none are followed
+        let fetcher = fetch::direct();
+        let mut req = match (&self.spec.rpc_method, self.spec.method.as_str()) {
+            (Some(method), _) => fetcher.post(url.as_str()).map(|req| {
+                req.json(&json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": method,
+                    "params": args,
+                }))
+            }),
+            (None, "GET") => fetcher.get(url.as_str()).map(|req| req.query(&args)),
+            (None, _) => fetcher.post(url.as_str()).map(|req| req.json(&args)),
+        }
+        .map_err(|e| e.to_string())?;
+        for (name, value) in &self.spec.headers {
+            req = req.header(name, value);
+        }
+
+        let res = fetcher
+            .send(req.timeout(timeout), fetch::JSON_TYPES)
+            .await
+            .map_err(|e| e.to_string())?;
+        let body: Value = serde_json::from_slice(&res.body)
+            .map_err(|e| format!("Tool returned invalid JSON: {}", e))?;
+
+        if self.spec.rpc_method.is_none() {
+            return Ok(body);
+        }
+        match (body.get("result"), body.get("error")) {
+            (_, Some(error)) if !error.is_null() => {
+                Err(format!("Tool returned an error: {}", error))
+            }
+            (Some(result), _) => Ok(result.clone()),
+            _ => Err("Tool returned neither result nor error".to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for HttpTool {
+    fn name(&self) -> &str {
+        &self.spec.name
+    }
+
+    fn description(&self) -> &str {
+        &self.spec.description
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.spec.name.clone(),
+            description: self.spec.description.clone(),
+            parameters: self.spec.parameters.clone(),
+        }
+    }
+
+    async fn run(&self, input: &str) -> Result<String, ToolError> {
+        let max_calls = config::get().tool_max_calls;
+        if self.attempts.fetch_add(1, Ordering::SeqCst) >= max_calls {
+            return Err(ToolError::Unknown(format!(
+                "Tool '{}' may be called at most {} times per completion",
+                self.spec.name, max_calls
+            )));
+        }
+
+        let args = if input.trim().is_empty() {
+            json!({})
+        } else {
+            serde_json::from_str(input)
+                .map_err(|e| ToolError::Unknown(format!("Invalid tool arguments: {}", e)))?
+        };
+
+        let started = Instant::now();
+        let result = self.call(args).await;
+        let status = if result.is_ok() { "ok" } else { "error" };
+        metrics::inc(
+            "haithe_tool_calls_total",
+            &[("product", &self.address), ("status", status)],
+        );
+        metrics::observe(
+            "haithe_tool_call_duration_seconds",
+            &[("product", &self.address)],
+            started.elapsed(),
+        );
+
+        match result {
+            Ok(value) => {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                Ok(value.to_string())
+            }
+            Err(e) => {
+                tracing::warn!(product = %self.address, tool = %self.spec.name, error = %e, "Tool call failed");
+                Err(ToolError::Unknown(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,447 @@
+use crate::lib::db::{self, Db, Executor, projects};
+use crate::lib::fetch::{self, FetchError};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::{Value, json};
+use sha2::Sha256;
+use sqlx::FromRow;
+use std::time::Duration;
+use tracing::Instrument;
+
+pub const EVENTS: &[&str] = &[
+    "completion.finished",
+    "budget.threshold_crossed",
+    "product.enabled",
+    "product.disabled",
+    "member.added",
+    "member.updated",
+    "member.removed",
+    "bot.error",
+];
+
+const MAX_ATTEMPTS: i64 = 5;
+const BASE_BACKOFF_SECS: u64 = 2;
+/// How long an attempt holds its delivery before another may take it over,
+/// well past the attempt's own timeout.
+const ATTEMPT_LEASE: Duration = Duration::from_secs(60);
+/// How often pending deliveries left by earlier runs are looked for.
+const RESUME_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct WebhookEndpoint {
+    pub id: i64,
+    pub project_id: i64,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub events: String,
+    pub active: bool,
+    pub created_at: String,
+}
+
+impl WebhookEndpoint {
+    pub fn subscribes_to(&self, event: &str) -> bool {
+        self.events
+            .split(',')
+            .map(str::trim)
+            .any(|e| e == "*" || e == event)
+    }
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub endpoint_id: i64,
+    pub event: String,
+    pub payload: String,
+    pub status: String,
+    pub attempts: i64,
+    pub response_status: Option<i64>,
+    pub last_error: Option<String>,
+    pub replay_of: Option<i64>,
+    pub created_at: String,
+    pub delivered_at: Option<String>,
+    /// When a pending delivery is next due. Unset until the first attempt.
+    pub next_attempt_at: Option<String>,
+}
+
+/// Inbound trigger that runs a project's agent on a rendered prompt.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct WebhookTrigger {
+    pub id: i64,
+    pub trigger_uid: String,
+    pub project_id: i64,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub prompt_template: String,
+    pub model: String,
+    pub callback_url: Option<String>,
+    pub active: bool,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct TriggerInvocation {
+    pub id: i64,
+    pub trigger_id: i64,
+    pub idempotency_key: String,
+    pub status: String,
+    pub response: Option<String>,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub completed_at: Option<String>,
+    /// When the current run took the invocation, which it holds for
+    /// [`INVOCATION_LEASE`].
+    pub claimed_at: Option<String>,
+    /// Set before any payment is collected, after which the invocation is never rerun.
+    pub charged: bool,
+    pub usage_id: Option<i64>,
+}
+
+/// How long a pending invocation is held before another call with its key may
+/// take it over. Answering is cut off at [`TRIGGER_TIMEOUT`], well within it.
+pub const INVOCATION_LEASE: Duration = Duration::from_secs(900);
+/// Longest a trigger's agent may take to answer, before anything is charged.
+pub const TRIGGER_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Records that the invocation's completion has started paying.
+pub async fn mark_charged(db: &Db, invocation_id: i64) -> Result<(), sqlx::Error> {
+    db::query("UPDATE webhook_trigger_invocations SET charged = TRUE WHERE id = ?")
+        .bind(invocation_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// `v1` signature over `{timestamp}.{body}`, sent as `Haithe-Signature: t=..,v1=..`.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Checks a `t=..,v1=..` signature header against the body, rejecting timestamps
+/// more than `tolerance_secs` away from now.
+pub fn verify(secret: &str, header: &str, body: &[u8], tolerance_secs: i64) -> bool {
+    let mut timestamp = None;
+    let mut signature = None;
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", t)) => timestamp = t.parse::<i64>().ok(),
+            Some(("v1", v)) => signature = hex::decode(v).ok(),
+            _ => {}
+        }
+    }
+
+    let (Some(timestamp), Some(signature)) = (timestamp, signature) else {
+        return false;
+    };
+
+    if chrono::Utc::now().timestamp().abs_diff(timestamp) > tolerance_secs.unsigned_abs() {
+        return false;
+    }
+
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+pub fn generate_secret() -> String {
+    format!("whsec_{}", uuid::Uuid::new_v4().simple())
+}
+
+/// Queues `event` for every active endpoint of the project that subscribes to it.
+/// Failures are logged, never surfaced to the caller.
+pub async fn emit(db: &Db, project_id: i64, event: &str, data: Value) {
+    let endpoints = match db::query_as::<WebhookEndpoint>(
+        "SELECT * FROM webhook_endpoints WHERE project_id = ? AND active = TRUE",
+    )
+    .bind(project_id)
+    .fetch_all(db)
+    .await
+    {
+        Ok(endpoints) => endpoints,
+        Err(e) => {
+            tracing::error!(project_id, error = %e, "Failed to load webhook endpoints");
+            return;
+        }
+    };
+
+    for endpoint in endpoints.iter().filter(|e| e.subscribes_to(event)) {
+        match db::query_scalar::<i64>(
+            "INSERT INTO webhook_deliveries (endpoint_id, event, payload) VALUES (?, ?, ?) RETURNING id",
+        )
+        .bind(endpoint.id)
+        .bind(event)
+        .bind(data.to_string())
+        .fetch_one(db)
+        .await
+        {
+            Ok(delivery_id) => spawn_delivery(db.clone(), delivery_id),
+            Err(e) => tracing::error!(
+                endpoint_id = endpoint.id,
+                error = %e,
+                "Failed to queue webhook delivery"
+            ),
+        }
+    }
+}
+
+pub async fn emit_for_project_uid(db: &Db, project_uid: &str, event: &str, data: Value) {
+    match projects::id_by_uid(db, project_uid).await {
+        Ok(Some(project_id)) => emit(db, project_id, event, data).await,
+        Ok(None) => {}
+        Err(e) => tracing::error!(project_uid, error = %e, "Failed to resolve project for webhook"),
+    }
+}
+
+/// Org level events are fanned out to every project in the organization.
+pub async fn emit_for_org(db: &Db, org_id: i64, event: &str, data: Value) {
+    let project_ids: Vec<i64> = match db::query_scalar("SELECT id FROM projects WHERE org_id = ?")
+        .bind(org_id)
+        .fetch_all(db)
+        .await
+    {
+        Ok(ids) => ids,
+        Err(e) => {
+            tracing::error!(org_id, error = %e, "Failed to load projects of org for webhook");
+            return;
+        }
+    };
+
+    for project_id in project_ids {
+        emit(db, project_id, event, data.clone()).await;
+    }
+}
+
+/// Stores a fresh delivery carrying the same event and payload as an earlier one.
+/// Hand it to [`spawn_delivery`] once the row is committed.
+pub async fn replay<'c>(
+    executor: impl Into<Executor<'c>>,
+    delivery: &WebhookDelivery,
+) -> Result<WebhookDelivery, sqlx::Error> {
+    let replayed = db::query_as::<WebhookDelivery>(
+        "INSERT INTO webhook_deliveries (endpoint_id, event, payload, replay_of) VALUES (?, ?, ?, ?) RETURNING *",
+    )
+    .bind(delivery.endpoint_id)
+    .bind(&delivery.event)
+    .bind(&delivery.payload)
+    .bind(delivery.id)
+    .fetch_one(executor)
+    .await?;
+
+    Ok(replayed)
+}
+
+pub fn spawn_delivery(db: Db, delivery_id: i64) {
+    tokio::spawn(async move {
+        if let Err(e) = deliver(&db, delivery_id).await {
+            tracing::warn!(delivery_id, error = %e, "Webhook delivery failed");
+        }
+    }.in_current_span());
+}
+
+/// Picks up pending deliveries whose retry is due, including those left behind
+/// by a restart, for as long as the server runs.
+pub fn spawn_resume_worker(db: Db) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RESUME_INTERVAL);
+        loop {
+            interval.tick().await;
+            match due_deliveries(&db).await {
+                Ok(ids) => {
+                    for delivery_id in ids {
+                        spawn_delivery(db.clone(), delivery_id);
+                    }
+                }
+                Err(e) => tracing::error!(error = %e, "Failed to load pending webhook deliveries"),
+            }
+        }
+    }.in_current_span());
+}
+
+async fn due_deliveries(db: &Db) -> Result<Vec<i64>, sqlx::Error> {
+    db::query_scalar(
+        "SELECT id FROM webhook_deliveries WHERE status = 'pending' AND (next_attempt_at IS NULL OR next_attempt_at <= ?) ORDER BY id LIMIT 100",
+    )
+    .bind(db::timestamp(chrono::Utc::now()))
+    .fetch_all(db)
+    .await
+}
+
+/// Takes the delivery for one attempt if it is pending and due, so a delivery
+/// is never sent by two tasks at once.
+async fn claim_attempt(db: &Db, delivery_id: i64) -> Result<bool, sqlx::Error> {
+    let now = chrono::Utc::now();
+    let lease = chrono::Duration::from_std(ATTEMPT_LEASE).expect("lease fits");
+    let claimed = db::query(
+        "UPDATE webhook_deliveries SET next_attempt_at = ? WHERE id = ? AND status = 'pending' AND (next_attempt_at IS NULL OR next_attempt_at <= ?)",
+    )
+    .bind(db::timestamp(now + lease))
+    .bind(delivery_id)
+    .bind(db::timestamp(now))
+    .execute(db)
+    .await?;
+    Ok(claimed.rows_affected() > 0)
+}
+
+/// Makes attempts until the delivery succeeds, runs out of attempts or is taken
+/// over by another task. Every retry is scheduled in the database first, so the
+/// resume worker carries on if this task goes away.
+async fn deliver(db: &Db, delivery_id: i64) -> Result<(), sqlx::Error> {
+    let endpoint = db::query_as::<WebhookEndpoint>(
+        "SELECT e.* FROM webhook_endpoints e JOIN webhook_deliveries d ON d.endpoint_id = e.id WHERE d.id = ?",
+    )
+    .bind(delivery_id)
+    .fetch_one(db)
+    .await?;
+
+    let fetcher = fetch::client();
+
+    while claim_attempt(db, delivery_id).await? {
+        let delivery =
+            db::query_as::<WebhookDelivery>("SELECT * FROM webhook_deliveries WHERE id = ?")
+                .bind(delivery_id)
+                .fetch_one(db)
+                .await?;
+        let attempt = delivery.attempts + 1;
+
+        let data: Value = serde_json::from_str(&delivery.payload).unwrap_or(Value::Null);
+        let body = json!({
+            "id": delivery.id,
+            "event": delivery.event,
+            "project_id": endpoint.project_id,
+            "created_at": delivery.created_at,
+            "data": data,
+        })
+        .to_string();
+
+        let timestamp = chrono::Utc::now().timestamp();
+        let signature = sign(&endpoint.secret, timestamp, &body);
+
+        let result = async {
+            let req = fetcher
+                .post(&endpoint.url)?
+                .timeout(Duration::from_secs(10))
+                .header("Content-Type", "application/json")
+                .header("Haithe-Event", &delivery.event)
+                .header("Haithe-Delivery", delivery.id.to_string())
+                .header("Haithe-Timestamp", timestamp.to_string())
+                .header("Haithe-Signature", format!("t={},v1={}", timestamp, signature))
+                .body(body);
+            fetcher.send(req, &[]).await
+        }
+        .await;
+
+        let (response_status, error) = match result {
+            Ok(res) => (Some(res.status.as_u16() as i64), None),
+            Err(FetchError::Status { status, .. }) => (
+                Some(status.as_u16() as i64),
+                Some(format!("Endpoint responded with {}", status)),
+            ),
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        let status = match &error {
+            None => "succeeded",
+            Some(_) if attempt >= MAX_ATTEMPTS => "failed",
+            Some(_) => "pending",
+        };
+
+        let backoff = Duration::from_secs(BASE_BACKOFF_SECS.pow(attempt as u32));
+        let next_attempt_at = (status == "pending").then(|| {
+            db::timestamp(chrono::Utc::now() + chrono::Duration::from_std(backoff).expect("backoff fits"))
+        });
+
+        db::query(
+            "UPDATE webhook_deliveries SET attempts = ?, status = ?, response_status = ?, last_error = ?, next_attempt_at = ?, delivered_at = CASE WHEN ? = 'succeeded' THEN CURRENT_TIMESTAMP ELSE delivered_at END WHERE id = ?",
+        )
+        .bind(attempt)
+        .bind(status)
+        .bind(response_status)
+        .bind(&error)
+        .bind(&next_attempt_at)
+        .bind(status)
+        .bind(delivery.id)
+        .execute(db)
+        .await?;
+
+        if next_attempt_at.is_none() {
+            return Ok(());
+        }
+
+        // Timestamps are stored to the second, so wait past the one it is due in
+        tokio::time::sleep(backoff + Duration::from_secs(1)).await;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::db::{Backend, testing};
+
+    #[test]
+    fn verifies_signatures_within_tolerance() {
+        let body = r#"{"prompt":"hi"}"#;
+        let now = chrono::Utc::now().timestamp();
+        let header = |t: i64| format!("t={},v1={}", t, sign("whsec_test", t, body));
+
+        assert!(verify("whsec_test", &header(now), body.as_bytes(), 300));
+        assert!(!verify("whsec_other", &header(now), body.as_bytes(), 300));
+        assert!(!verify("whsec_test", &header(now - 301), body.as_bytes(), 300));
+        assert!(!verify("whsec_test", &header(now), b"{}", 300));
+        for t in [i64::MIN, i64::MAX] {
+            assert!(!verify("whsec_test", &header(t), body.as_bytes(), 300));
+        }
+    }
+
+    #[actix_web::test]
+    async fn claims_each_due_delivery_once() {
+        for backend in [Backend::Sqlite, Backend::Postgres] {
+            let db = testing::migrated(backend).await;
+            let project_id = testing::project(&db).await;
+
+            let endpoint_id: i64 = db::query_scalar(
+                "INSERT INTO webhook_endpoints (project_id, url, secret) VALUES (?, 'https://hooks.example.com', 'whsec_test') RETURNING id",
+            )
+            .bind(project_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+            let mut ids = Vec::new();
+            for (status, next_attempt_at) in [
+                ("pending", None),
+                ("pending", Some("2000-01-01 00:00:00")),
+                ("pending", Some("2999-01-01 00:00:00")),
+                ("failed", None),
+            ] {
+                let id: i64 = db::query_scalar(
+                    "INSERT INTO webhook_deliveries (endpoint_id, event, payload, status, next_attempt_at) VALUES (?, 'member.added', '{}', ?, ?) RETURNING id",
+                )
+                .bind(endpoint_id)
+                .bind(status)
+                .bind(next_attempt_at)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+                ids.push(id);
+            }
+
+            assert_eq!(due_deliveries(&db).await.unwrap(), ids[..2]);
+
+            assert!(claim_attempt(&db, ids[0]).await.unwrap());
+            assert!(!claim_attempt(&db, ids[0]).await.unwrap());
+            assert!(!claim_attempt(&db, ids[2]).await.unwrap());
+            assert!(!claim_attempt(&db, ids[3]).await.unwrap());
+
+            assert_eq!(due_deliveries(&db).await.unwrap(), ids[1..2]);
+        }
+    }
+}
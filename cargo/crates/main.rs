@@ -1,30 +1,94 @@
+use crate::lib::config::{self, Cli, Config};
+use crate::lib::db::{self, Db};
 use crate::lib::discord::sync_discord_bots;
+use crate::lib::knowledge::KnowledgeCache;
+use crate::lib::logging;
+use crate::lib::metrics;
+use crate::lib::migrations;
 use crate::lib::state;
 use crate::lib::telegram::sync_bots;
+use crate::lib::webhooks;
 use crate::routes::routes;
 use actix_cors::Cors;
 use actix_web::middleware;
-use actix_web::{App, HttpResponse, HttpServer, Responder, web};
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer, Responder, web};
 use serde_json::json;
-use sqlx::{Executor, SqlitePool};
 use state::AppState;
 use std::collections::HashMap;
-use std::fs;
 use std::sync::Mutex;
+use std::time::Duration;
 
 mod lib;
 mod macros;
 mod routes;
 mod utils;
 
+fn cors(config: &Config) -> Cors {
+    if config.cors_origins.is_empty() {
+        return Cors::default().allow_any_origin();
+    }
+
+    config
+        .cors_origins
+        .iter()
+        .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+}
+
 async fn health() -> impl Responder {
     HttpResponse::Ok().json(json!({ "status": "ok" }))
 }
 
-async fn ensure_db_migration(pool: &SqlitePool) -> Result<(), sqlx::Error> {
-    let sql = fs::read_to_string("data/migrations/up.sql").expect("Failed to read SQL file");
+async fn metrics_handler(req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    if let Some(token) = &config::get().metrics_token {
+        let expected = format!("Bearer {}", token.expose());
+        let authorized = req
+            .headers()
+            .get(actix_web::http::header::AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .is_some_and(|h| h == expected);
+        if !authorized {
+            return HttpResponse::Unauthorized().finish();
+        }
+    }
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics::render(&state.db))
+}
+
+async fn ensure_db_migration(pool: &Db) -> anyhow::Result<()> {
+    for migration in migrations::run(pool).await? {
+        tracing::info!(migration = migration.name, "Applied migration");
+    }
+
+    Ok(())
+}
+
+/// `main migrate [status]`: applies pending migrations, or lists them, then exits.
+async fn migrate_command(pool: &Db, args: &[String]) -> anyhow::Result<()> {
+    match args.first().map(String::as_str) {
+        None | Some("up") => {
+            let applied = migrations::run(pool).await?;
+            if applied.is_empty() {
+                println!("Database is up to date");
+            }
+            for migration in applied {
+                println!("Applied migration {}", migration.name);
+            }
+        }
+        Some("status") => {
+            for status in migrations::status(pool).await? {
+                let state = match (&status.applied_at, status.checksum_matches) {
+                    (None, _) => "pending".to_string(),
+                    (Some(_), Some(false)) => "MODIFIED AFTER APPLY".to_string(),
+                    (Some(applied_at), _) => format!("applied {}", applied_at),
+                };
+                println!("{:>4}  {:<40} {}", status.version, status.name, state);
+            }
+        }
+        Some(other) => anyhow::bail!("Unknown migrate command '{}', expected 'up' or 'status'", other),
+    }
 
-    pool.execute(sql.as_str()).await?;
     Ok(())
 }
 
@@ -32,18 +96,45 @@ async fn ensure_db_migration(pool: &SqlitePool) -> Result<(), sqlx::Error> {
 async fn main() -> std::io::Result<()> {
     dotenvy::dotenv().ok();
 
-    let db_url = std::env::var("DATABASE_URL").unwrap();
-    let db_path = db_url.strip_prefix("sqlite://").unwrap_or(&db_url);
-    if !std::path::Path::new(db_path).exists() {
-        println!("Database file '{}' does not exist. Creating it...", db_path);
-        std::fs::File::create(db_path).expect("Failed to create database file");
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("Invalid arguments: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match Config::load(&cli) {
+        Ok(config) => config::init(config),
+        Err(e) => {
+            eprintln!("Invalid configuration: {:#}", e);
+            std::process::exit(1);
+        }
     }
+    let config = config::get();
+    logging::init(config);
 
-    let db_pool = SqlitePool::connect(&db_url).await.unwrap();
+    let db_pool = match db::connect(config.database_url.expose()).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            tracing::error!(error = format!("{:#}", e), "Failed to connect to database");
+            std::process::exit(1);
+        }
+    };
 
-    ensure_db_migration(&db_pool)
-        .await
-        .expect("Database migration failed");
+    let args = cli.command;
+    if args.first().map(String::as_str) == Some("migrate") {
+        if let Err(e) = migrate_command(&db_pool, &args[1..]).await {
+            eprintln!("Migration failed: {:#}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Err(e) = ensure_db_migration(&db_pool).await {
+        tracing::error!(error = format!("{:#}", e), "Database migration failed");
+        std::process::exit(1);
+    }
 
     let global_app_state = web::Data::new(AppState {
         nonce_registry: Mutex::new(HashMap::new()),
@@ -51,13 +142,18 @@ async fn main() -> std::io::Result<()> {
         window_buffer_memory: Mutex::new(HashMap::new()),
         discord_bots: Mutex::new(HashMap::new()),
         telegram_bots: Mutex::new(HashMap::new()),
+        rate_limiter: Mutex::new(Default::default()),
+        knowledge_cache: KnowledgeCache::new(
+            Duration::from_secs(config.knowledge_cache_ttl_secs),
+            config.knowledge_cache_dir.clone(),
+        ),
     });
 
     {
         let state_clone = global_app_state.clone();
         tokio::spawn(async move {
             if let Err(e) = sync_bots(state_clone.clone()).await {
-                eprintln!("Failed to sync Telegram bots at startup: {}", e);
+                tracing::error!(error = %e, "Failed to sync Telegram bots at startup");
             }
         });
     }
@@ -66,23 +162,25 @@ async fn main() -> std::io::Result<()> {
         let state_clone = global_app_state.clone();
         tokio::spawn(async move {
             if let Err(e) = sync_discord_bots(state_clone.clone()).await {
-                eprintln!("Failed to sync Discord bots at startup: {}", e);
+                tracing::error!(error = %e, "Failed to sync Discord bots at startup");
             }
         });
     }
 
-    let port = std::env::var("PORT")
-        .unwrap_or_else(|_| "8080".to_string())
-        .parse::<u16>()
-        .expect("Invalid port number");
+    webhooks::spawn_resume_worker(global_app_state.db.clone());
 
-    println!("Starting server on port {}", port);
+    tracing::info!(
+        bind_address = %config.bind_address,
+        port = config.port,
+        network = ?config.network,
+        "Starting server"
+    );
     HttpServer::new(move || {
         App::new()
-            .wrap(middleware::Logger::default())
+            .wrap(middleware::from_fn(metrics::track_requests))
+            .wrap(middleware::from_fn(logging::request_span))
             .wrap(
-                Cors::default()
-                    .allow_any_origin()
+                cors(config)
                     .allowed_methods(vec!["GET", "POST", "PATCH", "DELETE", "PUT", "OPTIONS"])
                     .allowed_headers(vec![
                         actix_web::http::header::AUTHORIZATION,
@@ -91,15 +189,21 @@ async fn main() -> std::io::Result<()> {
                         actix_web::http::header::HeaderName::from_static("haithe-project"),
                         actix_web::http::header::HeaderName::from_static("openai-organization"),
                         actix_web::http::header::HeaderName::from_static("openai-project"),
+                        actix_web::http::header::HeaderName::from_static(logging::REQUEST_ID_HEADER),
+                    ])
+                    .expose_headers(vec![
+                        actix_web::http::header::HeaderName::from_static("x-next-cursor"),
+                        actix_web::http::header::HeaderName::from_static(logging::REQUEST_ID_HEADER),
                     ])
                     .supports_credentials()
                     .max_age(3600),
             )
             .app_data(global_app_state.clone())
             .route("/health", web::get().to(health))
+            .route("/metrics", web::get().to(metrics_handler))
             .configure(routes)
     })
-    .bind(("127.0.0.1", port))?
+    .bind((config.bind_address, config.port))?
     .run()
     .await?;
 
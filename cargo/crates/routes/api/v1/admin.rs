@@ -0,0 +1,18 @@
+use crate::lib::extractors::AuthUser;
+use crate::lib::{config, error::ApiError, respond};
+use actix_web::{Responder, get, web};
+
+/// Effective server configuration with secrets redacted, for wallets in `ADMIN_WALLETS`.
+#[get("/config")]
+async fn get_config_handler(user: AuthUser) -> Result<impl Responder, ApiError> {
+    let config = config::get();
+    if !config.is_admin(&user.wallet_address) {
+        return Err(ApiError::Forbidden);
+    }
+
+    Ok(respond::ok("Configuration fetched", config))
+}
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_config_handler);
+}
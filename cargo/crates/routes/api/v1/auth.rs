@@ -1,4 +1,6 @@
-use crate::lib::extractors::AuthUser;
+use crate::lib::db::{self, sessions};
+use crate::lib::audit::{self, AuditEntry};
+use crate::lib::extractors::{AuthUser, RequestMeta};
 use crate::lib::{error::ApiError, respond, state::AppState};
 use crate::{bail_internal, utils};
 use actix_web::{HttpRequest, Responder, get, post, web};
@@ -53,7 +55,7 @@ async fn post_waitlist_handler(
         return Err(ApiError::BadRequest("Invalid email address".into()));
     }
 
-    sqlx::query("INSERT INTO waitlist (email) VALUES (?)")
+    db::query("INSERT INTO waitlist (email) VALUES (?)")
         .bind(&email)
         .execute(&state.db)
         .await
@@ -91,40 +93,36 @@ async fn post_login_handler(
         return Err(ApiError::Unauthorized);
     }
 
-    let conn_info = req.connection_info(); // Keep the temporary alive
-    let ip = conn_info
-        .realip_remote_addr()
-        .unwrap_or("unknown")
-        .to_string();
-
-    let user_agent = req
-        .headers()
-        .get("User-Agent")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("unknown");
-
+    let meta = RequestMeta::new(&req);
     let token = utils::generate_jwt(&address);
 
-    sqlx::query("INSERT OR IGNORE INTO accounts (wallet_address) VALUES (?)")
-        .bind(&address)
-        .execute(&state.db)
-        .await?;
-
-    sqlx::query(
-        r#"
-        INSERT INTO sessions (wallet_address, token, ip, user_agent)
-        VALUES (?, ?, ?, ?)
-        ON CONFLICT(wallet_address)
-        DO UPDATE SET token = excluded.token, ip = excluded.ip, user_agent = excluded.user_agent, created_at = CURRENT_TIMESTAMP
-        "#,
+    let mut tx = state.db.begin().await?;
+    sessions::ensure_account(&mut tx, &address).await?;
+    sessions::start(
+        &mut tx,
+        &address,
+        &format!("Bearer {}", token),
+        &meta.ip,
+        &meta.user_agent,
     )
-    .bind(&address)
-    .bind(&format!("Bearer {}", token))
-    .bind(&ip)
-    .bind(&user_agent)
-    .execute(&state.db)
     .await?;
 
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: None,
+            actor: &address,
+            action: "auth.login",
+            target_type: "session",
+            target_id: Some(address.clone()),
+            before: None,
+            after: None,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
     Ok(respond::ok(
         "Login successful",
         serde_json::json!({
@@ -137,12 +135,27 @@ async fn post_login_handler(
 #[post("/logout")]
 async fn post_logout_handler(
     user: AuthUser,
+    meta: RequestMeta,
     state: web::Data<AppState>,
 ) -> Result<impl Responder, ApiError> {
-    sqlx::query("DELETE FROM sessions WHERE wallet_address = ?")
-        .bind(&user.wallet_address)
-        .execute(&state.db)
-        .await?;
+    let mut tx = state.db.begin().await?;
+    sessions::end(&mut tx, &user.wallet_address).await?;
+
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: None,
+            actor: &user.wallet_address,
+            action: "auth.logout",
+            target_type: "session",
+            target_id: Some(user.wallet_address.clone()),
+            before: None,
+            after: None,
+        },
+    )
+    .await?;
+    tx.commit().await?;
 
     Ok(respond::ok("Logged out", ()))
 }
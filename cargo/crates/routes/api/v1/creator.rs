@@ -1,4 +1,7 @@
-use crate::lib::{contracts, error::ApiError, extractors::AuthUser, respond, state::AppState};
+use crate::lib::db::{self, products};
+use crate::lib::audit::{self, AuditEntry};
+use crate::lib::extractors::{AuthUser, RequestMeta};
+use crate::lib::{contracts, error::ApiError, respond, state::AppState};
 use actix_web::{Responder, get, post, web};
 use ethers::abi::{Token, encode};
 use ethers::providers::Middleware;
@@ -38,19 +41,17 @@ async fn get_creator_by_address(
 ) -> Result<impl Responder, ApiError> {
     let wallet_address = path.into_inner();
 
-    let creator = sqlx::query_as::<_, CreatorDetails>(
+    let creator = db::query_as::<CreatorDetails>(
         "SELECT wallet_address, uri, created_at FROM creators WHERE wallet_address = ?",
     )
     .bind(&wallet_address)
     .fetch_optional(&state.db)
     .await
     .map_err(|e| {
-        println!("DB Error fetching creator: {:?}", e);
+        tracing::error!(error = %e, "Failed to fetch creator");
         ApiError::Internal("Failed to fetch creator details".into())
     })?;
 
-    println!("Creator details: {:?}", creator);
-
     match creator {
         Some(creator) => Ok(respond::ok(
             "Creator details fetched successfully",
@@ -67,14 +68,14 @@ async fn get_creator_products(
 ) -> Result<impl Responder, ApiError> {
     let wallet_address = path.into_inner();
 
-    let products = sqlx::query_as::<_, CreatorProduct>(
+    let products = db::query_as::<CreatorProduct>(
         "SELECT id, address, creator, name, price_per_call, category, created_at FROM products WHERE creator = ? ORDER BY created_at DESC"
     )
     .bind(&wallet_address)
     .fetch_all(&state.db)
     .await
     .map_err(|e| {
-        println!("DB Error fetching creator products: {:?}", e);
+        tracing::error!(error = %e, "Failed to fetch creator products");
         ApiError::Internal("Failed to fetch creator products".into())
     })?;
 
@@ -86,13 +87,13 @@ async fn get_creator_products(
 
 #[get("")]
 async fn get_all_creators(state: web::Data<AppState>) -> Result<impl Responder, ApiError> {
-    let creators = sqlx::query_as::<_, CreatorDetails>(
+    let creators = db::query_as::<CreatorDetails>(
         "SELECT wallet_address, uri, created_at FROM creators ORDER BY created_at DESC"
     )
     .fetch_all(&state.db)
     .await
     .map_err(|e| {
-        println!("DB Error fetching creators: {:?}", e);
+        tracing::error!(error = %e, "Failed to fetch creators");
         ApiError::Internal("Failed to fetch creators".into())
     })?;
 
@@ -105,6 +106,7 @@ async fn get_all_creators(state: web::Data<AppState>) -> Result<impl Responder,
 #[post("")]
 async fn become_creator(
     user: AuthUser,
+    meta: RequestMeta,
     state: web::Data<AppState>,
     body: web::Json<PostBecomeCreatorRequest>,
 ) -> Result<impl Responder, ApiError> {
@@ -113,31 +115,27 @@ async fn become_creator(
         .parse()
         .map_err(|_| ApiError::BadRequest("Invalid wallet address format".into()))?;
 
-    println!(
-        "Registering creator with params: address={}, uri={}, pub_key={}",
-        wallet_address, body.uri, body.pub_key
-    );
+    tracing::info!(wallet_address = ?wallet_address, uri = %body.uri, "Registering creator");
 
     let contract = contracts::get_contract_with_wallet("HaitheOrchestrator", None).await?;
 
     // Get server wallet address for logging
     let server_wallet_address = contract.client().default_sender().unwrap_or_default();
-    println!("Server wallet address: {:?}", server_wallet_address);
-
     // Check server wallet balance (the one that pays for gas)
     let server_balance = contract
         .client()
         .get_balance(server_wallet_address, None)
         .await
         .map_err(|e| {
-            println!("Failed to get server wallet balance: {:?}", e);
+            tracing::error!(error = %e, "Failed to get server wallet balance");
             ApiError::Internal("Failed to check server wallet balance".into())
         })?;
 
     let server_balance_in_ether = ethers::utils::format_ether(server_balance);
-    println!(
-        "Server wallet balance: {} wei ({} ETH)",
-        server_balance, server_balance_in_ether
+    tracing::debug!(
+        server_wallet = ?server_wallet_address,
+        balance = %server_balance_in_ether,
+        "Server wallet balance"
     );
 
     // Check if server has sufficient balance for gas fees
@@ -154,20 +152,17 @@ async fn become_creator(
         .get_balance(wallet_address, None)
         .await
         .map_err(|e| {
-            println!("Failed to get user wallet balance: {:?}", e);
+            tracing::error!(error = %e, "Failed to get user wallet balance");
             ApiError::Internal("Failed to check user wallet balance".into())
         })?;
 
     // Convert balance from wei to ether for better readability
     let balance_in_ether = ethers::utils::format_ether(wallet_balance);
-    println!(
-        "User wallet balance: {} wei ({} ETH)",
-        wallet_balance, balance_in_ether
-    );
+    tracing::debug!(balance = %balance_in_ether, "User wallet balance");
 
     // Note: User wallet balance is just for logging - server wallet pays for gas
     if wallet_balance.is_zero() {
-        println!("Note: User wallet has zero balance, but server wallet will pay for gas fees");
+        tracing::debug!("User wallet has zero balance, server wallet pays for gas");
     }
 
     let creator_identity_address: Address = contract
@@ -175,15 +170,10 @@ async fn become_creator(
         .call()
         .await
         .map_err(|e| {
-            println!("Failed to get creator identity address: {:?}", e);
+            tracing::error!(error = %e, "Failed to get creator identity address");
             ApiError::Internal("Failed to get creator identity contract".into())
         })?;
 
-    println!(
-        "Creator identity contract address: {:?}",
-        creator_identity_address
-    );
-
     let function_signature = "determineNextSeed(address)";
     let selector = &keccak256(function_signature.as_bytes())[..4];
 
@@ -200,14 +190,12 @@ async fn become_creator(
         .gas(300_000u64); // Set a reasonable gas limit for the call
 
     let call_result = provider.call(&tx_request.into(), None).await.map_err(|e| {
-        println!("Failed to call determineNextSeed: {:?}", e);
+        tracing::error!(error = %e, "Failed to call determineNextSeed");
         ApiError::Internal("Failed to determine required private key seed".into())
     })?;
 
     let pvt_key_seed_bytes = H256::from_slice(&call_result);
 
-    println!("Contract-determined pvt_key_seed: {:?}", pvt_key_seed_bytes);
-
     // Validate parameters before contract call
     if body.uri.is_empty() {
         return Err(ApiError::BadRequest("URI cannot be empty".into()));
@@ -217,8 +205,6 @@ async fn become_creator(
         return Err(ApiError::BadRequest("Public key cannot be empty".into()));
     }
 
-    println!("Calling registerAsCreator with validated parameters");
-
     let call = contract
         .method::<_, ()>(
             "registerAsCreator",
@@ -230,13 +216,13 @@ async fn become_creator(
             ),
         )
         .map_err(|e| {
-            println!("Failed to create contract method call: {:?}", e);
+            tracing::error!(error = %e, "Failed to create contract method call");
             ApiError::Internal("Failed to create contract method call".into())
         })?;
 
     // Estimate gas for the transaction
     let gas_estimate = call.estimate_gas().await.map_err(|e| {
-        println!("Failed to estimate gas: {:?}", e);
+        tracing::error!(error = %e, "Failed to estimate gas");
         // Try to provide more specific error information
         let error_msg = match e.to_string().contains("revert") {
             true => "Contract execution would revert. Check parameters and contract state.",
@@ -245,50 +231,56 @@ async fn become_creator(
         ApiError::Internal(error_msg.into())
     })?;
 
-    println!("Estimated gas: {:?}", gas_estimate);
-
     // Add a buffer to the gas estimate (50% extra for safety)
     let gas_limit = gas_estimate * 150 / 100;
 
     // Get current gas price
     let gas_price = contract.client().get_gas_price().await.map_err(|e| {
-        println!("Failed to get gas price: {:?}", e);
+        tracing::error!(error = %e, "Failed to get gas price");
         ApiError::Internal("Failed to get current gas price".into())
     })?;
 
-    println!("Current gas price: {:?}", gas_price);
-    println!("Using gas limit: {:?}", gas_limit);
+    tracing::debug!(%gas_estimate, %gas_limit, %gas_price, "Prepared registerAsCreator");
 
     let call_with_gas = call.gas(gas_limit).gas_price(gas_price);
-    let pending_tx = call_with_gas.send().await.map_err(|e| {
-        println!("Failed to send transaction: {:?}", e);
-        ApiError::Internal("Failed to send transaction".into())
-    })?;
-
-    let tx_hash = pending_tx.tx_hash();
-    println!("Transaction sent: {:?}", tx_hash);
-
-    let _receipt = pending_tx.await.map_err(|e| {
-        println!("Transaction failed: {:?}", e);
-        ApiError::Internal("Transaction failed to be mined".into())
-    })?;
-    println!("Transaction mined successfully");
-
-    sqlx::query(
-        "INSERT OR REPLACE INTO creators (wallet_address, uri, pvt_key_seed, pub_key, created_at) 
-         VALUES (?, ?, ?, ?, CURRENT_TIMESTAMP)",
+    let tx_hash = contracts::send(&call_with_gas, "HaitheOrchestrator", "registerAsCreator")
+        .await
+        .map_err(|_| ApiError::Internal("registerAsCreator transaction failed".into()))?;
+
+    let mut tx = state.db.begin().await?;
+    products::upsert_creator(
+        &mut tx,
+        &user.wallet_address,
+        &body.uri,
+        &format!("{:?}", pvt_key_seed_bytes),
+        &body.pub_key,
     )
-    .bind(&user.wallet_address)
-    .bind(&body.uri)
-    .bind(format!("{:?}", pvt_key_seed_bytes))
-    .bind(&body.pub_key)
-    .execute(&state.db)
     .await
     .map_err(|e| {
-        println!("DB Error creating creator: {:?}", e);
+        tracing::error!(error = %e, "Failed to store creator");
         ApiError::Internal("Failed to register creator".into())
     })?;
 
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: None,
+            actor: &user.wallet_address,
+            action: "creator.register",
+            target_type: "creator",
+            target_id: Some(user.wallet_address.clone()),
+            before: None,
+            after: Some(serde_json::json!({
+                "uri": body.uri,
+                "pub_key": body.pub_key,
+                "transaction_hash": format!("{:?}", tx_hash),
+            })),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
     Ok(respond::ok(
         "Creator registered successfully",
         serde_json::json!({
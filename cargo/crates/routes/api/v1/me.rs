@@ -1,5 +1,7 @@
-use crate::lib::extractors::AuthUser;
-use crate::lib::{contracts, error::ApiError, respond, state::AppState};
+use crate::lib::db::{self, sessions};
+use crate::lib::audit::{self, AuditEntry};
+use crate::lib::extractors::{AuthUser, RequestMeta};
+use crate::lib::{config, contracts, error::ApiError, respond, state::AppState};
 use actix_web::{Responder, get, post, web};
 use ethers::types::U256;
 use serde::{Deserialize, Serialize};
@@ -53,7 +55,7 @@ async fn get_orgs_handler(
     user: AuthUser,
     state: web::Data<AppState>,
 ) -> Result<impl Responder, ApiError> {
-    let orgs = sqlx::query_as::<_, Organization>(
+    let orgs = db::query_as::<Organization>(
         "SELECT * FROM organizations WHERE owner = ? 
          UNION 
          SELECT organizations.* FROM organizations 
@@ -74,7 +76,7 @@ async fn get_projects_handler(
     user: AuthUser,
     state: web::Data<AppState>,
 ) -> Result<impl Responder, ApiError> {
-    let projects = sqlx::query_as::<_, Project>(
+    let projects = db::query_as::<Project>(
         "SELECT * FROM projects WHERE id IN (SELECT project_id FROM project_members WHERE wallet_address = ?)"
     )
     .bind(&user.wallet_address)
@@ -88,28 +90,42 @@ async fn get_projects_handler(
 #[post("/api-key/disable")]
 async fn disable_api_key_handler(
     user: AuthUser,
+    meta: RequestMeta,
     state: web::Data<AppState>,
 ) -> Result<impl Responder, ApiError> {
-    sqlx::query("UPDATE accounts SET api_key_last_issued_at = NULL WHERE wallet_address = ?")
+    let mut tx = state.db.begin().await?;
+    db::query("UPDATE accounts SET api_key_last_issued_at = NULL WHERE wallet_address = ?")
         .bind(&user.wallet_address)
-        .execute(&state.db)
+        .execute(&mut tx)
         .await
         .map_err(|_| ApiError::Internal("DB error".into()))?;
 
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: None,
+            actor: &user.wallet_address,
+            action: "account.api_key.disable",
+            target_type: "account",
+            target_id: Some(user.wallet_address.clone()),
+            before: None,
+            after: Some(serde_json::json!({ "api_key_active": false })),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
     Ok(respond::ok("API key disabled", ()))
 }
 
 #[get("/api-key")]
 async fn get_api_key_handler(
     user: AuthUser,
+    meta: RequestMeta,
     state: web::Data<AppState>,
 ) -> Result<impl Responder, ApiError> {
-    let api_key_last_issued_at: Option<i64> = sqlx::query_scalar(
-        "SELECT unixepoch(api_key_last_issued_at) FROM accounts WHERE wallet_address = ?",
-    )
-    .bind(&user.wallet_address)
-    .fetch_optional(&state.db)
-    .await?;
+    let api_key_last_issued_at: Option<i64> = sessions::api_key_issued_at(&state.db, &user.wallet_address).await?;
 
     if let Some(ts) = api_key_last_issued_at {
         if ts > 0 {
@@ -117,8 +133,10 @@ async fn get_api_key_handler(
         }
     }
 
-    let tee_private_key = std::env::var("MOCK_TEE_PVT_KEY")
-        .map_err(|_| ApiError::Internal("TEE private key not configured".into()))?;
+    let tee_private_key = config::get()
+        .mock_tee_pvt_key
+        .as_ref()
+        .ok_or_else(|| ApiError::Internal("TEE private key not configured".into()))?;
 
     let timestamp = chrono::Utc::now().timestamp();
     let address = user
@@ -129,18 +147,36 @@ async fn get_api_key_handler(
 
     let message = format!("{}.{}.{}", timestamp, address, nonce);
 
-    let signed_message = crate::utils::sign_message(&tee_private_key, &message)
+    let signed_message = crate::utils::sign_message(tee_private_key.expose(), &message)
         .map_err(|_| ApiError::Internal("Failed to sign API key".into()))?;
     let signature = signed_message.strip_prefix("0x").unwrap_or("");
 
     let api_key = format!("sk-{}.{}.{}", address, nonce, signature);
 
-    sqlx::query(
+    let mut tx = state.db.begin().await?;
+    db::query(
         "UPDATE accounts SET api_key_last_issued_at = CURRENT_TIMESTAMP WHERE wallet_address = ?",
     )
     .bind(&user.wallet_address)
-    .execute(&state.db)
+    .execute(&mut tx)
+    .await?;
+
+    // The key itself is never stored, only the fact that one was issued
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: None,
+            actor: &user.wallet_address,
+            action: "account.api_key.issue",
+            target_type: "account",
+            target_id: Some(user.wallet_address.clone()),
+            before: None,
+            after: Some(serde_json::json!({ "api_key_active": true, "issued_at": timestamp })),
+        },
+    )
     .await?;
+    tx.commit().await?;
 
     Ok(respond::ok(
         "API key generated",
@@ -157,12 +193,7 @@ async fn get_api_key_last_handler(
     user: AuthUser,
     state: web::Data<AppState>,
 ) -> Result<impl Responder, ApiError> {
-    let api_key_last_issued_at: Option<i64> = sqlx::query_scalar(
-        "SELECT unixepoch(api_key_last_issued_at) FROM accounts WHERE wallet_address = ?",
-    )
-    .bind(&user.wallet_address)
-    .fetch_optional(&state.db)
-    .await?;
+    let api_key_last_issued_at: Option<i64> = sessions::api_key_issued_at(&state.db, &user.wallet_address).await?;
 
     let issued_at = api_key_last_issued_at.unwrap_or(0);
 
@@ -179,7 +210,7 @@ async fn get_faucet_handler(
     user: AuthUser,
     state: web::Data<AppState>,
 ) -> Result<impl Responder, ApiError> {
-    let last_request: Option<FaucetRequest> = sqlx::query_as::<_, FaucetRequest>(
+    let last_request: Option<FaucetRequest> = db::query_as::<FaucetRequest>(
         "SELECT * FROM faucet_requests WHERE wallet_address = ? ORDER BY requested_at DESC LIMIT 1",
     )
     .bind(&user.wallet_address)
@@ -212,13 +243,15 @@ async fn get_faucet_handler(
 #[post("/faucet")]
 async fn post_faucet_handler(
     user: AuthUser,
+    meta: RequestMeta,
     request: web::Json<FaucetPostRequest>,
     state: web::Data<AppState>,
 ) -> Result<impl Responder, ApiError> {
-    let recent_request: Option<FaucetRequest> = sqlx::query_as::<_, FaucetRequest>(
-        "SELECT * FROM faucet_requests WHERE wallet_address = ? AND requested_at > datetime('now', '-1 hour')",
+    let recent_request: Option<FaucetRequest> = db::query_as::<FaucetRequest>(
+        "SELECT * FROM faucet_requests WHERE wallet_address = ? AND requested_at > ?",
     )
     .bind(&user.wallet_address)
+    .bind(db::timestamp(chrono::Utc::now() - chrono::Duration::hours(1)))
     .fetch_optional(&state.db)
     .await
     .map_err(|_| ApiError::Internal("DB error".into()))?;
@@ -244,23 +277,37 @@ async fn post_faucet_handler(
         .method::<_, bool>("transfer", (user_address, amount))
         .map_err(|e| ApiError::Internal(format!("Failed to prepare transfer: {}", e)))?;
 
-    let pending_tx = contract_call
-        .send()
-        .await
-        .map_err(|e| ApiError::Internal(format!("Failed to send transfer: {}", e)))?;
-
-    let tx_hash = pending_tx.tx_hash();
-
-    let _receipt = pending_tx
+    let tx_hash = contracts::send(&contract_call, "tUSDT", "transfer")
         .await
         .map_err(|e| ApiError::Internal(format!("Transfer failed: {}", e)))?;
 
-    sqlx::query("INSERT INTO faucet_requests (wallet_address) VALUES (?)")
+    let mut tx = state.db.begin().await?;
+    db::query("INSERT INTO faucet_requests (wallet_address) VALUES (?)")
         .bind(&user.wallet_address)
-        .execute(&state.db)
+        .execute(&mut tx)
         .await
         .map_err(|_| ApiError::Internal("Failed to record faucet request".into()))?;
 
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: None,
+            actor: &user.wallet_address,
+            action: "account.faucet",
+            target_type: "account",
+            target_id: Some(user.wallet_address.clone()),
+            before: None,
+            after: Some(serde_json::json!({
+                "amount": "500",
+                "token": "tUSDT",
+                "transaction_hash": format!("{:?}", tx_hash),
+            })),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
     Ok(respond::ok(
         "Faucet tokens sent successfully",
         serde_json::json!({
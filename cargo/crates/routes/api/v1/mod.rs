@@ -1,5 +1,6 @@
 use actix_web::web;
 
+pub mod admin;
 pub mod auth;
 pub mod creator;
 pub mod me;
@@ -11,7 +12,8 @@ pub mod stats;
 pub mod tee;
 
 pub fn routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::scope("/auth").configure(auth::routes))
+    cfg.service(web::scope("/admin").configure(admin::routes))
+        .service(web::scope("/auth").configure(auth::routes))
         .service(web::scope("/me").configure(me::routes))
         .service(web::scope("/models").configure(models::routes))
         .service(web::scope("/orgs").configure(orgs::routes))
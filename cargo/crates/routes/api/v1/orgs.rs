@@ -1,8 +1,13 @@
 use std::path;
 
-use crate::lib::extractors::AuthUser;
+use crate::lib::db::{self, Db, orgs, sessions};
+use crate::lib::audit::{self, AuditEntry};
+use crate::lib::extractors::{AuthUser, RequestMeta};
+use crate::lib::budget::{self, Budget};
+use crate::lib::ratelimit::{self, RateLimit};
+use crate::lib::webhooks;
 use crate::lib::{contracts, error::ApiError, models::get_models, respond, state::AppState};
-use actix_web::{Responder, delete, get, patch, post, web};
+use actix_web::{HttpResponse, Responder, delete, get, patch, post, put, web};
 use ethers::types::Address;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
@@ -55,11 +60,12 @@ pub struct Project {
 
 #[post("")]
 async fn post_index_handler(
-    _: AuthUser,
+    user: AuthUser,
+    meta: RequestMeta,
     state: web::Data<AppState>,
 ) -> Result<impl Responder, ApiError> {
     let highest_orchestrator_idx: i64 =
-        sqlx::query_scalar("SELECT COALESCE(MAX(orchestrator_idx), 0) FROM organizations")
+        db::query_scalar("SELECT COALESCE(MAX(orchestrator_idx), 0) FROM organizations")
             .fetch_one(&state.db)
             .await?;
     let onchain_length: ethers::types::U256 = contracts::get_contract("HaitheOrchestrator", None)?
@@ -95,12 +101,10 @@ async fn post_index_handler(
         .call()
         .await?;
 
-        sqlx::query("INSERT OR IGNORE INTO accounts (wallet_address) VALUES (?);")
-            .bind(&format!("{:#x}", organization_owner))
-            .execute(&state.db)
-            .await?;
+        let mut tx = state.db.begin().await?;
+        sessions::ensure_account(&mut tx, &format!("{:#x}", organization_owner)).await?;
 
-        let org = sqlx::query_as::<_, Organization>(
+        let org = db::query_as::<Organization>(
             "INSERT INTO organizations (name, owner, organization_uid, orchestrator_idx, address) VALUES (?, ?, ?, ?, ?) RETURNING *",
         )
         .bind(&organization_name)
@@ -108,14 +112,26 @@ async fn post_index_handler(
         .bind(&organization_uid)
         .bind(idx)
         .bind(&format!("{:#x}", organization_address))
-        .fetch_one(&state.db)
+        .fetch_one(&mut tx)
         .await?;
 
-        sqlx::query("INSERT OR IGNORE INTO org_model_enrollments (org_id, model_id) VALUES (?, ?)")
-            .bind(org.id)
-            .bind("1")
-            .execute(&state.db)
-            .await?;
+        orgs::enroll_model(&mut tx, org.id, 1).await?;
+
+        audit::record(
+            &mut tx,
+            &meta,
+            AuditEntry {
+                org_id: Some(org.id),
+                actor: &user.wallet_address,
+                action: "org.sync",
+                target_type: "organization",
+                target_id: Some(org.id.to_string()),
+                before: None,
+                after: Some(serde_json::json!(org)),
+            },
+        )
+        .await?;
+        tx.commit().await?;
 
         synced_count += 1;
     }
@@ -137,7 +153,7 @@ async fn get_org_handler(
         return Err(ApiError::NotFound("Organization not found".to_string()));
     }
 
-    let org = sqlx::query_as::<_, Organization>("SELECT * FROM organizations WHERE id = ?")
+    let org = db::query_as::<Organization>("SELECT * FROM organizations WHERE id = ?")
         .bind(id)
         .fetch_one(&state.db)
         .await?;
@@ -153,14 +169,14 @@ async fn get_org_projects_handler(
 ) -> Result<impl Responder, ApiError> {
     let id = path.into_inner();
     let is_owner: bool =
-        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM organizations WHERE id = ? AND owner = ?)")
+        db::query_scalar("SELECT EXISTS(SELECT 1 FROM organizations WHERE id = ? AND owner = ?)")
             .bind(id)
             .bind(&user.wallet_address)
             .fetch_one(&state.db)
             .await?;
 
     let is_member: bool = if !is_owner {
-        sqlx::query_scalar(
+        db::query_scalar(
             "SELECT EXISTS(SELECT 1 FROM org_members WHERE org_id = ? AND wallet_address = ?)",
         )
         .bind(id)
@@ -175,7 +191,7 @@ async fn get_org_projects_handler(
         return Err(ApiError::Unauthorized);
     }
 
-    let projects = sqlx::query_as::<_, Project>("SELECT * FROM projects WHERE org_id = ?")
+    let projects = db::query_as::<Project>("SELECT * FROM projects WHERE org_id = ?")
         .bind(id)
         .fetch_all(&state.db)
         .await?;
@@ -191,6 +207,7 @@ struct PatchOrgQuery {
 #[patch("/{id}")]
 async fn patch_org_handler(
     user: AuthUser,
+    meta: RequestMeta,
     path: web::Path<i64>,
     query: web::Query<PatchOrgQuery>,
     state: web::Data<AppState>,
@@ -202,14 +219,36 @@ async fn patch_org_handler(
         return Err(ApiError::NotFound("Organization not found".to_string()));
     }
 
-    let org = sqlx::query_as::<_, Organization>(
+    let mut tx = state.db.begin().await?;
+    let before = db::query_as::<Organization>("SELECT * FROM organizations WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut tx)
+        .await?;
+
+    let org = db::query_as::<Organization>(
         "UPDATE organizations SET name = ? WHERE id = ? AND owner = ? RETURNING *",
     )
     .bind(&org_name)
     .bind(id)
     .bind(&user.wallet_address)
-    .fetch_one(&state.db)
+    .fetch_one(&mut tx)
+    .await?;
+
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: Some(org.id),
+            actor: &user.wallet_address,
+            action: "org.update",
+            target_type: "organization",
+            target_id: Some(org.id.to_string()),
+            before: before.map(|b| serde_json::json!(b)),
+            after: Some(serde_json::json!(org)),
+        },
+    )
     .await?;
+    tx.commit().await?;
 
     Ok(respond::ok("Organization updated", org))
 }
@@ -217,19 +256,37 @@ async fn patch_org_handler(
 #[delete("/{id}")]
 async fn delete_org_handler(
     user: AuthUser,
+    meta: RequestMeta,
     path: web::Path<i64>,
     state: web::Data<AppState>,
 ) -> Result<impl Responder, ApiError> {
     let id = path.into_inner();
 
-    let org = sqlx::query_as::<_, Organization>(
+    let mut tx = state.db.begin().await?;
+    let org = db::query_as::<Organization>(
         "DELETE FROM organizations WHERE id = ? AND owner = ? RETURNING *",
     )
     .bind(id)
     .bind(&user.wallet_address)
-    .fetch_one(&state.db)
+    .fetch_one(&mut tx)
     .await?;
 
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: Some(org.id),
+            actor: &user.wallet_address,
+            action: "org.delete",
+            target_type: "organization",
+            target_id: Some(org.id.to_string()),
+            before: Some(serde_json::json!(org)),
+            after: None,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
     Ok(respond::ok("Organization deleted", org))
 }
 
@@ -241,7 +298,7 @@ async fn get_org_members_handler(
 ) -> Result<impl Responder, ApiError> {
     let org_id = path.into_inner();
 
-    let members = sqlx::query_as::<_, OrgMember>(
+    let members = db::query_as::<OrgMember>(
         "SELECT org_id, wallet_address, role, created_at FROM org_members WHERE org_id = ? ORDER BY created_at DESC"
     )
     .bind(org_id)
@@ -254,6 +311,7 @@ async fn get_org_members_handler(
 #[post("/{id}/members")]
 async fn post_org_members_handler(
     user: AuthUser,
+    meta: RequestMeta,
     path: web::Path<i64>,
     query: web::Query<PostMemberQuery>,
     state: web::Data<AppState>,
@@ -262,7 +320,7 @@ async fn post_org_members_handler(
 
     let wallet_address = query.wallet_address.to_lowercase();
 
-    let owner_check = sqlx::query_scalar::<_, i64>(
+    let owner_check = db::query_scalar::<i64>(
         "SELECT COUNT(*) FROM organizations WHERE id = ? AND owner = ?",
     )
     .bind(org_id)
@@ -271,7 +329,7 @@ async fn post_org_members_handler(
     .await?;
 
     let admin_check = if owner_check == 0 {
-        sqlx::query_scalar::<_, i64>(
+        db::query_scalar::<i64>(
             "SELECT COUNT(*) FROM org_members WHERE org_id = ? AND wallet_address = ? AND role = 'admin'"
         )
         .bind(org_id)
@@ -292,14 +350,39 @@ async fn post_org_members_handler(
         ));
     }
 
-    let member = sqlx::query_as::<_, OrgMember>(
+    let mut tx = state.db.begin().await?;
+    let member = db::query_as::<OrgMember>(
         "INSERT INTO org_members (org_id, wallet_address, role) VALUES (?, ?, ?) RETURNING org_id, wallet_address, role, created_at"
     )
     .bind(org_id)
     .bind(&wallet_address)
     .bind(&query.role)
-    .fetch_one(&state.db)
+    .fetch_one(&mut tx)
+    .await?;
+
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor: &user.wallet_address,
+            action: "org.member.add",
+            target_type: "org_member",
+            target_id: Some(member.wallet_address.clone()),
+            before: None,
+            after: Some(serde_json::json!(member)),
+        },
+    )
     .await?;
+    tx.commit().await?;
+
+    webhooks::emit_for_org(
+        &state.db,
+        org_id,
+        "member.added",
+        serde_json::json!({ "scope": "org", "member": member }),
+    )
+    .await;
 
     Ok(respond::ok("Member added to organization", member))
 }
@@ -307,6 +390,7 @@ async fn post_org_members_handler(
 #[patch("/{id}/members")]
 async fn patch_org_members_handler(
     user: AuthUser,
+    meta: RequestMeta,
     path: web::Path<i64>,
     query: web::Query<PatchMemberQuery>,
     state: web::Data<AppState>,
@@ -315,7 +399,7 @@ async fn patch_org_members_handler(
 
     let wallet_address = query.wallet_address.to_lowercase();
 
-    let owner_check = sqlx::query_scalar::<_, i64>(
+    let owner_check = db::query_scalar::<i64>(
         "SELECT COUNT(*) FROM organizations WHERE id = ? AND owner = ?",
     )
     .bind(org_id)
@@ -324,7 +408,7 @@ async fn patch_org_members_handler(
     .await?;
 
     let admin_check = if owner_check == 0 {
-        sqlx::query_scalar::<_, i64>(
+        db::query_scalar::<i64>(
             "SELECT COUNT(*) FROM org_members WHERE org_id = ? AND wallet_address = ? AND role = 'admin'"
         )
         .bind(org_id)
@@ -339,14 +423,47 @@ async fn patch_org_members_handler(
         return Err(ApiError::Forbidden);
     }
 
-    let member = sqlx::query_as::<_, OrgMember>(
+    let mut tx = state.db.begin().await?;
+    let before = db::query_as::<OrgMember>(
+        "SELECT org_id, wallet_address, role, created_at FROM org_members WHERE org_id = ? AND wallet_address = ?"
+    )
+    .bind(org_id)
+    .bind(&wallet_address)
+    .fetch_optional(&mut tx)
+    .await?;
+
+    let member = db::query_as::<OrgMember>(
         "UPDATE org_members SET role = ? WHERE org_id = ? AND wallet_address = ? RETURNING org_id, wallet_address, role, created_at"
     )
     .bind(&query.role)
     .bind(org_id)
     .bind(&wallet_address)
-    .fetch_one(&state.db)
+    .fetch_one(&mut tx)
+    .await?;
+
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor: &user.wallet_address,
+            action: "org.member.update",
+            target_type: "org_member",
+            target_id: Some(member.wallet_address.clone()),
+            before: before.map(|b| serde_json::json!(b)),
+            after: Some(serde_json::json!(member)),
+        },
+    )
     .await?;
+    tx.commit().await?;
+
+    webhooks::emit_for_org(
+        &state.db,
+        org_id,
+        "member.updated",
+        serde_json::json!({ "scope": "org", "member": member }),
+    )
+    .await;
 
     Ok(respond::ok("Member role updated", member))
 }
@@ -354,6 +471,7 @@ async fn patch_org_members_handler(
 #[delete("/{id}/members")]
 async fn delete_org_members_handler(
     user: AuthUser,
+    meta: RequestMeta,
     path: web::Path<i64>,
     query: web::Query<DeleteMemberQuery>,
     state: web::Data<AppState>,
@@ -362,7 +480,7 @@ async fn delete_org_members_handler(
 
     let wallet_address = query.wallet_address.to_lowercase();
 
-    let owner_check = sqlx::query_scalar::<_, i64>(
+    let owner_check = db::query_scalar::<i64>(
         "SELECT COUNT(*) FROM organizations WHERE id = ? AND owner = ?",
     )
     .bind(org_id)
@@ -371,7 +489,7 @@ async fn delete_org_members_handler(
     .await?;
 
     let admin_check = if owner_check == 0 {
-        sqlx::query_scalar::<_, i64>(
+        db::query_scalar::<i64>(
             "SELECT COUNT(*) FROM org_members WHERE org_id = ? AND wallet_address = ? AND role = 'admin'"
         )
         .bind(org_id)
@@ -386,14 +504,39 @@ async fn delete_org_members_handler(
         return Err(ApiError::Forbidden);
     }
 
-    let member = sqlx::query_as::<_, OrgMember>(
+    let mut tx = state.db.begin().await?;
+    let member = db::query_as::<OrgMember>(
         "DELETE FROM org_members WHERE org_id = ? AND wallet_address = ? RETURNING org_id, wallet_address, role, created_at"
     )
     .bind(org_id)
     .bind(&wallet_address)
-    .fetch_one(&state.db)
+    .fetch_one(&mut tx)
     .await?;
 
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor: &user.wallet_address,
+            action: "org.member.remove",
+            target_type: "org_member",
+            target_id: Some(member.wallet_address.clone()),
+            before: Some(serde_json::json!(member)),
+            after: None,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    webhooks::emit_for_org(
+        &state.db,
+        org_id,
+        "member.removed",
+        serde_json::json!({ "scope": "org", "member": member }),
+    )
+    .await;
+
     Ok(respond::ok("Member removed from organization", member))
 }
 
@@ -407,11 +550,7 @@ async fn get_org_models_handler(
 
     let models = get_models();
 
-    let enabled_model_ids: Vec<u64> =
-        sqlx::query_scalar::<_, u64>("SELECT model_id FROM org_model_enrollments WHERE org_id = ?")
-            .bind(org_id)
-            .fetch_all(&state.db)
-            .await?;
+    let enabled_model_ids = orgs::enrolled_model_ids(&state.db, org_id).await?;
 
     let enabled_models = models
         .into_iter()
@@ -431,7 +570,8 @@ struct PostOrgModelsQuery {
 
 #[post("/{id}/models")]
 async fn post_org_models_handler(
-    _: AuthUser,
+    user: AuthUser,
+    meta: RequestMeta,
     query: web::Query<PostOrgModelsQuery>,
     path: web::Path<i64>,
     state: web::Data<AppState>,
@@ -446,12 +586,29 @@ async fn post_org_models_handler(
 
     let model = models.iter().find(|m| m.id == model_id).cloned();
 
-    sqlx::query("INSERT INTO org_model_enrollments (org_id, model_id) VALUES (?, ?)")
+    let mut tx = state.db.begin().await?;
+    db::query("INSERT INTO org_model_enrollments (org_id, model_id) VALUES (?, ?)")
         .bind(org_id)
-        .bind(model_id.to_string())
-        .execute(&state.db)
+        .bind(model_id)
+        .execute(&mut tx)
         .await?;
 
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor: &user.wallet_address,
+            action: "org.model.enroll",
+            target_type: "model",
+            target_id: Some(model_id.to_string()),
+            before: None,
+            after: Some(serde_json::json!(model)),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
     Ok(respond::ok("Models enrolled", serde_json::json!(model)))
 }
 
@@ -462,19 +619,37 @@ struct DeleteOrgModelsQuery {
 
 #[delete("/{id}/models")]
 async fn delete_org_models_handler(
-    _: AuthUser,
+    user: AuthUser,
+    meta: RequestMeta,
     path: web::Path<i64>,
     query: web::Query<DeleteOrgModelsQuery>,
     state: web::Data<AppState>,
 ) -> Result<impl Responder, ApiError> {
     let org_id = path.into_inner();
 
-    sqlx::query("DELETE FROM org_model_enrollments WHERE org_id = ? AND model_id = ?")
+    let mut tx = state.db.begin().await?;
+    db::query("DELETE FROM org_model_enrollments WHERE org_id = ? AND model_id = ?")
         .bind(org_id)
-        .bind(query.model_id.to_string())
-        .execute(&state.db)
+        .bind(query.model_id)
+        .execute(&mut tx)
         .await?;
 
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor: &user.wallet_address,
+            action: "org.model.unenroll",
+            target_type: "model",
+            target_id: Some(query.model_id.to_string()),
+            before: Some(serde_json::json!({ "model_id": query.model_id })),
+            after: None,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
     Ok(respond::ok(
         "Models unregistered",
         serde_json::json!({"id" : org_id}),
@@ -489,7 +664,7 @@ async fn get_org_expenditure_handler(
 ) -> Result<impl Responder, ApiError> {
     let org_id = path.into_inner();
 
-    let owner_check = sqlx::query_scalar::<_, i64>(
+    let owner_check = db::query_scalar::<i64>(
         "SELECT COUNT(*) FROM organizations WHERE id = ? AND owner = ?",
     )
     .bind(org_id)
@@ -498,7 +673,7 @@ async fn get_org_expenditure_handler(
     .await?;
 
     let admin_check = if owner_check == 0 {
-        sqlx::query_scalar::<_, i64>(
+        db::query_scalar::<i64>(
             "SELECT COUNT(*) FROM org_members WHERE org_id = ? AND wallet_address = ? AND role = 'admin'"
         )
         .bind(org_id)
@@ -513,14 +688,26 @@ async fn get_org_expenditure_handler(
         return Err(ApiError::Forbidden);
     }
 
-    let expenditure: i64 = sqlx::query_scalar("SELECT expenditure FROM organizations WHERE id = ?")
+    let expenditure: i64 = db::query_scalar("SELECT expenditure FROM organizations WHERE id = ?")
         .bind(org_id)
         .fetch_one(&state.db)
         .await?;
 
+    let budgets = db::query_as::<Budget>(
+        "SELECT * FROM budgets WHERE org_id = ? ORDER BY project_id IS NOT NULL, project_id, period",
+    )
+    .bind(org_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut budget_usage = Vec::with_capacity(budgets.len());
+    for b in &budgets {
+        budget_usage.push(budget::usage(&state.db, b).await?);
+    }
+
     Ok(respond::ok(
         "Organization expenditure fetched",
-        serde_json::json!({"expenditure": expenditure}),
+        serde_json::json!({"expenditure": expenditure, "budgets": budget_usage}),
     ))
 }
 
@@ -532,7 +719,7 @@ async fn get_org_balance_handler(
 ) -> Result<impl Responder, ApiError> {
     let org_id = path.into_inner();
 
-    let owner_check = sqlx::query_scalar::<_, i64>(
+    let owner_check = db::query_scalar::<i64>(
         "SELECT COUNT(*) FROM organizations WHERE id = ? AND owner = ?",
     )
     .bind(org_id)
@@ -541,7 +728,7 @@ async fn get_org_balance_handler(
     .await?;
 
     let admin_check = if owner_check == 0 {
-        sqlx::query_scalar::<_, i64>(
+        db::query_scalar::<i64>(
             "SELECT COUNT(*) FROM org_members WHERE org_id = ? AND wallet_address = ? AND role = 'admin'"
         )
         .bind(org_id)
@@ -557,7 +744,7 @@ async fn get_org_balance_handler(
     }
 
     let org_address =
-        sqlx::query_scalar::<_, String>("SELECT address FROM organizations WHERE id = ?")
+        db::query_scalar::<String>("SELECT address FROM organizations WHERE id = ?")
             .bind(org_id)
             .fetch_one(&state.db)
             .await?;
@@ -577,6 +764,570 @@ async fn get_org_balance_handler(
     ))
 }
 
+async fn ensure_org_admin(
+    wallet_address: &str,
+    org_id: i64,
+    db: &Db,
+) -> Result<(), ApiError> {
+    let owner_check = db::query_scalar::<i64>(
+        "SELECT COUNT(*) FROM organizations WHERE id = ? AND owner = ?",
+    )
+    .bind(org_id)
+    .bind(wallet_address)
+    .fetch_one(db)
+    .await?;
+
+    let admin_check = if owner_check == 0 {
+        db::query_scalar::<i64>(
+            "SELECT COUNT(*) FROM org_members WHERE org_id = ? AND wallet_address = ? AND role = 'admin'"
+        )
+        .bind(org_id)
+        .bind(wallet_address)
+        .fetch_one(db)
+        .await?
+    } else {
+        0
+    };
+
+    if owner_check == 0 && admin_check == 0 {
+        return Err(ApiError::Forbidden);
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct GetAuditQuery {
+    actor: Option<String>,
+    action: Option<String>,
+    target_type: Option<String>,
+    target_id: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+    before_id: Option<i64>,
+    limit: Option<i64>,
+    format: Option<String>,
+}
+
+async fn fetch_audit_events(
+    org_id: i64,
+    query: &GetAuditQuery,
+    max_limit: i64,
+    db: &Db,
+) -> Result<Vec<audit::AuditEvent>, ApiError> {
+    let mut conditions = vec!["org_id = ?"];
+    let mut binds: Vec<String> = Vec::new();
+
+    if let Some(ref actor) = query.actor {
+        conditions.push("actor = ?");
+        binds.push(actor.to_lowercase());
+    }
+
+    if let Some(ref action) = query.action {
+        // `org.member` matches `org.member` as well as `org.member.add` etc.
+        conditions.push("(action = ? OR action LIKE ?)");
+        binds.push(action.clone());
+        binds.push(format!("{}.%", action));
+    }
+
+    if let Some(ref target_type) = query.target_type {
+        conditions.push("target_type = ?");
+        binds.push(target_type.clone());
+    }
+
+    if let Some(ref target_id) = query.target_id {
+        conditions.push("target_id = ?");
+        binds.push(target_id.clone());
+    }
+
+    if let Some(ref since) = query.since {
+        conditions.push("created_at >= ?");
+        binds.push(db::parse_timestamp(since).ok_or_else(|| {
+            ApiError::BadRequest("since must be an RFC 3339 timestamp or a date".to_string())
+        })?);
+    }
+
+    if let Some(ref until) = query.until {
+        conditions.push("created_at <= ?");
+        binds.push(db::parse_timestamp(until).ok_or_else(|| {
+            ApiError::BadRequest("until must be an RFC 3339 timestamp or a date".to_string())
+        })?);
+    }
+
+    if query.before_id.is_some() {
+        conditions.push("id < ?");
+    }
+
+    let sql = format!(
+        "SELECT * FROM audit_events WHERE {} ORDER BY id DESC LIMIT ?",
+        conditions.join(" AND ")
+    );
+
+    let mut query_builder = db::query_as::<audit::AuditEvent>(&sql).bind(org_id);
+
+    for value in &binds {
+        query_builder = query_builder.bind(value);
+    }
+
+    if let Some(before_id) = query.before_id {
+        query_builder = query_builder.bind(before_id);
+    }
+
+    let limit = query.limit.unwrap_or(100).clamp(1, max_limit);
+    query_builder = query_builder.bind(limit);
+
+    Ok(query_builder.fetch_all(db).await?)
+}
+
+#[get("/{id}/audit")]
+async fn get_org_audit_handler(
+    user: AuthUser,
+    path: web::Path<i64>,
+    query: web::Query<GetAuditQuery>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let org_id = path.into_inner();
+
+    ensure_org_admin(&user.wallet_address, org_id, &state.db).await?;
+
+    let events = fetch_audit_events(org_id, &query, 1000, &state.db).await?;
+    let next_before_id = events.last().map(|e| e.id);
+
+    Ok(respond::ok(
+        "Audit events fetched",
+        serde_json::json!({
+            "events": events.iter().map(|e| e.to_json()).collect::<Vec<_>>(),
+            "next_before_id": next_before_id,
+        }),
+    ))
+}
+
+#[get("/{id}/audit/export")]
+async fn get_org_audit_export_handler(
+    user: AuthUser,
+    path: web::Path<i64>,
+    query: web::Query<GetAuditQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let org_id = path.into_inner();
+
+    ensure_org_admin(&user.wallet_address, org_id, &state.db).await?;
+
+    let events = fetch_audit_events(org_id, &query, 10_000, &state.db).await?;
+
+    match query.format.as_deref().unwrap_or("csv") {
+        "csv" => Ok(HttpResponse::Ok()
+            .content_type("text/csv")
+            .insert_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"org-{}-audit.csv\"", org_id),
+            ))
+            .body(audit::to_csv(&events))),
+        "jsonl" => {
+            let body = events
+                .iter()
+                .map(|e| e.to_json().to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            Ok(HttpResponse::Ok()
+                .content_type("application/x-ndjson")
+                .insert_header((
+                    "Content-Disposition",
+                    format!("attachment; filename=\"org-{}-audit.jsonl\"", org_id),
+                ))
+                .body(body))
+        }
+        _ => Err(ApiError::BadRequest(
+            "format must be 'csv' or 'jsonl'".to_string(),
+        )),
+    }
+}
+
+#[get("/{id}/rate-limits")]
+async fn get_org_rate_limits_handler(
+    user: AuthUser,
+    path: web::Path<i64>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let org_id = path.into_inner();
+
+    ensure_org_admin(&user.wallet_address, org_id, &state.db).await?;
+
+    let limits = db::query_as::<RateLimit>(
+        "SELECT * FROM rate_limits WHERE org_id = ? ORDER BY scope, target",
+    )
+    .bind(org_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut result = Vec::with_capacity(limits.len());
+    for limit in limits {
+        let spent_today = ratelimit::spent_today(&state.db, &limit).await?;
+        result.push(serde_json::json!({
+            "limit": limit,
+            "spent_today": spent_today,
+        }));
+    }
+
+    Ok(respond::ok("Rate limits fetched", result))
+}
+
+#[derive(Deserialize)]
+struct PutRateLimitBody {
+    scope: String,
+    target: Option<String>,
+    requests_per_minute: Option<i64>,
+    max_concurrent: Option<i64>,
+    daily_spend_limit: Option<i64>,
+}
+
+/// Normalises the target of a limit: empty for the org itself, the project uid
+/// (which must belong to the org) or the wallet address behind an API key.
+async fn rate_limit_target(
+    org_id: i64,
+    scope: &str,
+    target: Option<&str>,
+    db: &Db,
+) -> Result<String, ApiError> {
+    match scope {
+        "org" => Ok(String::new()),
+        "project" => {
+            let project_uid = target
+                .map(|t| t.strip_prefix("proj-").unwrap_or(t).to_string())
+                .ok_or_else(|| ApiError::BadRequest("target project_uid is required".into()))?;
+
+            let belongs = db::query_scalar::<i64>(
+                "SELECT COUNT(*) FROM projects WHERE project_uid = ? AND org_id = ?",
+            )
+            .bind(&project_uid)
+            .bind(org_id)
+            .fetch_one(db)
+            .await?;
+
+            if belongs == 0 {
+                return Err(ApiError::NotFound("Project not found in organization".into()));
+            }
+
+            Ok(project_uid)
+        }
+        "key" => target
+            .map(|t| t.to_lowercase())
+            .filter(|t| !t.is_empty())
+            .ok_or_else(|| ApiError::BadRequest("target wallet address is required".into())),
+        _ => Err(ApiError::BadRequest(
+            "scope must be 'org', 'project' or 'key'".to_string(),
+        )),
+    }
+}
+
+#[put("/{id}/rate-limits")]
+async fn put_org_rate_limits_handler(
+    user: AuthUser,
+    meta: RequestMeta,
+    path: web::Path<i64>,
+    body: web::Json<PutRateLimitBody>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let org_id = path.into_inner();
+
+    ensure_org_admin(&user.wallet_address, org_id, &state.db).await?;
+
+    if body.requests_per_minute.is_some_and(|v| v <= 0)
+        || body.max_concurrent.is_some_and(|v| v <= 0)
+        || body.daily_spend_limit.is_some_and(|v| v < 0)
+    {
+        return Err(ApiError::BadRequest(
+            "Limits must be positive numbers".to_string(),
+        ));
+    }
+
+    let target = rate_limit_target(org_id, &body.scope, body.target.as_deref(), &state.db).await?;
+
+    let mut tx = state.db.begin().await?;
+    let before = db::query_as::<RateLimit>(
+        "SELECT * FROM rate_limits WHERE org_id = ? AND scope = ? AND target = ?",
+    )
+    .bind(org_id)
+    .bind(&body.scope)
+    .bind(&target)
+    .fetch_optional(&mut tx)
+    .await?;
+
+    let limit = db::query_as::<RateLimit>(
+        r#"
+        INSERT INTO rate_limits (org_id, scope, target, requests_per_minute, max_concurrent, daily_spend_limit)
+        VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT(org_id, scope, target)
+        DO UPDATE SET requests_per_minute = excluded.requests_per_minute,
+                      max_concurrent = excluded.max_concurrent,
+                      daily_spend_limit = excluded.daily_spend_limit,
+                      updated_at = CURRENT_TIMESTAMP
+        RETURNING *
+        "#,
+    )
+    .bind(org_id)
+    .bind(&body.scope)
+    .bind(&target)
+    .bind(body.requests_per_minute)
+    .bind(body.max_concurrent)
+    .bind(body.daily_spend_limit)
+    .fetch_one(&mut tx)
+    .await?;
+
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor: &user.wallet_address,
+            action: "org.rate_limit.update",
+            target_type: "rate_limit",
+            target_id: Some(limit.id.to_string()),
+            before: before.map(|b| serde_json::json!(b)),
+            after: Some(serde_json::json!(limit)),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(respond::ok("Rate limit saved", limit))
+}
+
+#[derive(Deserialize)]
+struct DeleteRateLimitQuery {
+    scope: String,
+    target: Option<String>,
+}
+
+#[delete("/{id}/rate-limits")]
+async fn delete_org_rate_limits_handler(
+    user: AuthUser,
+    meta: RequestMeta,
+    path: web::Path<i64>,
+    query: web::Query<DeleteRateLimitQuery>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let org_id = path.into_inner();
+
+    ensure_org_admin(&user.wallet_address, org_id, &state.db).await?;
+
+    let target =
+        rate_limit_target(org_id, &query.scope, query.target.as_deref(), &state.db).await?;
+
+    let mut tx = state.db.begin().await?;
+    let limit = db::query_as::<RateLimit>(
+        "DELETE FROM rate_limits WHERE org_id = ? AND scope = ? AND target = ? RETURNING *",
+    )
+    .bind(org_id)
+    .bind(&query.scope)
+    .bind(&target)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Rate limit not found".into()))?;
+
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor: &user.wallet_address,
+            action: "org.rate_limit.delete",
+            target_type: "rate_limit",
+            target_id: Some(limit.id.to_string()),
+            before: Some(serde_json::json!(limit)),
+            after: None,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(respond::ok("Rate limit removed", limit))
+}
+
+#[get("/{id}/budgets")]
+async fn get_org_budgets_handler(
+    user: AuthUser,
+    path: web::Path<i64>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let org_id = path.into_inner();
+
+    ensure_org_admin(&user.wallet_address, org_id, &state.db).await?;
+
+    let budgets = db::query_as::<Budget>(
+        "SELECT * FROM budgets WHERE org_id = ? ORDER BY project_id IS NOT NULL, project_id, period",
+    )
+    .bind(org_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut result = Vec::with_capacity(budgets.len());
+    for b in &budgets {
+        result.push(budget::usage(&state.db, b).await?);
+    }
+
+    Ok(respond::ok("Budgets fetched", result))
+}
+
+#[derive(Deserialize)]
+struct PutBudgetBody {
+    project_id: Option<i64>,
+    period: String,
+    hard_cap: Option<i64>,
+    soft_threshold: Option<i64>,
+    alert_channel: Option<String>,
+    alert_target: Option<String>,
+}
+
+#[put("/{id}/budgets")]
+async fn put_org_budgets_handler(
+    user: AuthUser,
+    meta: RequestMeta,
+    path: web::Path<i64>,
+    body: web::Json<PutBudgetBody>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let org_id = path.into_inner();
+
+    ensure_org_admin(&user.wallet_address, org_id, &state.db).await?;
+
+    if !matches!(body.period.as_str(), "monthly" | "total") {
+        return Err(ApiError::BadRequest(
+            "period must be 'monthly' or 'total'".to_string(),
+        ));
+    }
+
+    if body.hard_cap.is_some_and(|v| v < 0) || body.soft_threshold.is_some_and(|v| v < 0) {
+        return Err(ApiError::BadRequest(
+            "Budget amounts cannot be negative".to_string(),
+        ));
+    }
+
+    if let Some(ref channel) = body.alert_channel {
+        if !matches!(channel.as_str(), "webhook" | "telegram" | "discord") {
+            return Err(ApiError::BadRequest(
+                "alert_channel must be 'webhook', 'telegram' or 'discord'".to_string(),
+            ));
+        }
+        if body.alert_target.as_deref().unwrap_or("").is_empty() {
+            return Err(ApiError::BadRequest(
+                "alert_target is required when alert_channel is set".to_string(),
+            ));
+        }
+    }
+
+    if let Some(project_id) = body.project_id {
+        let belongs = db::query_scalar::<i64>(
+            "SELECT COUNT(*) FROM projects WHERE id = ? AND org_id = ?",
+        )
+        .bind(project_id)
+        .bind(org_id)
+        .fetch_one(&state.db)
+        .await?;
+
+        if belongs == 0 {
+            return Err(ApiError::NotFound("Project not found in organization".into()));
+        }
+    }
+
+    let mut tx = state.db.begin().await?;
+    let before = db::query_as::<Budget>(
+        "SELECT * FROM budgets WHERE org_id = ? AND project_id IS NOT DISTINCT FROM ? AND period = ?",
+    )
+    .bind(org_id)
+    .bind(body.project_id)
+    .bind(&body.period)
+    .fetch_optional(&mut tx)
+    .await?;
+
+    let saved = match before {
+        Some(ref existing) => {
+            db::query_as::<Budget>(
+                "UPDATE budgets SET hard_cap = ?, soft_threshold = ?, alert_channel = ?, alert_target = ?, last_alert_period = NULL, updated_at = CURRENT_TIMESTAMP WHERE id = ? RETURNING *",
+            )
+            .bind(body.hard_cap)
+            .bind(body.soft_threshold)
+            .bind(&body.alert_channel)
+            .bind(&body.alert_target)
+            .bind(existing.id)
+            .fetch_one(&mut tx)
+            .await?
+        }
+        None => {
+            db::query_as::<Budget>(
+                "INSERT INTO budgets (org_id, project_id, period, hard_cap, soft_threshold, alert_channel, alert_target) VALUES (?, ?, ?, ?, ?, ?, ?) RETURNING *",
+            )
+            .bind(org_id)
+            .bind(body.project_id)
+            .bind(&body.period)
+            .bind(body.hard_cap)
+            .bind(body.soft_threshold)
+            .bind(&body.alert_channel)
+            .bind(&body.alert_target)
+            .fetch_one(&mut tx)
+            .await?
+        }
+    };
+
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor: &user.wallet_address,
+            action: "org.budget.update",
+            target_type: "budget",
+            target_id: Some(saved.id.to_string()),
+            before: before.map(|b| serde_json::json!(b)),
+            after: Some(serde_json::json!(saved)),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(respond::ok("Budget saved", budget::usage(&state.db, &saved).await?))
+}
+
+#[delete("/{id}/budgets/{budget_id}")]
+async fn delete_org_budget_handler(
+    user: AuthUser,
+    meta: RequestMeta,
+    path: web::Path<(i64, i64)>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let (org_id, budget_id) = path.into_inner();
+
+    ensure_org_admin(&user.wallet_address, org_id, &state.db).await?;
+
+    let mut tx = state.db.begin().await?;
+    let removed = db::query_as::<Budget>(
+        "DELETE FROM budgets WHERE id = ? AND org_id = ? RETURNING *",
+    )
+    .bind(budget_id)
+    .bind(org_id)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Budget not found".into()))?;
+
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: Some(org_id),
+            actor: &user.wallet_address,
+            action: "org.budget.delete",
+            target_type: "budget",
+            target_id: Some(removed.id.to_string()),
+            before: Some(serde_json::json!(removed)),
+            after: None,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(respond::ok("Budget removed", removed))
+}
+
 pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(post_index_handler)
         .service(get_org_handler)
@@ -591,5 +1342,13 @@ pub fn routes(cfg: &mut web::ServiceConfig) {
         .service(post_org_models_handler)
         .service(delete_org_models_handler)
         .service(get_org_expenditure_handler)
-        .service(get_org_balance_handler);
+        .service(get_org_balance_handler)
+        .service(get_org_audit_handler)
+        .service(get_org_audit_export_handler)
+        .service(get_org_rate_limits_handler)
+        .service(put_org_rate_limits_handler)
+        .service(delete_org_rate_limits_handler)
+        .service(get_org_budgets_handler)
+        .service(put_org_budgets_handler)
+        .service(delete_org_budget_handler);
 }
@@ -1,5 +1,8 @@
-use crate::lib::extractors::AuthUser;
-use crate::lib::{contracts, error::ApiError, respond, state::AppState};
+use crate::lib::db::{self, sessions};
+use crate::lib::audit::{self, AuditEntry};
+use crate::lib::extractors::{AuthUser, RequestMeta};
+use crate::lib::product_kinds::{self, ProductKind};
+use crate::lib::{config, contracts, error::ApiError, fetch, respond, state::AppState, webhooks};
 use actix_web::{Responder, delete, get, post, patch, web};
 use alith::data::crypto::{DecodeRsaPublicKey, Pkcs1v15Encrypt, RsaPublicKey};
 use alith::lazai::{ProofRequest, U256};
@@ -24,11 +27,12 @@ pub struct Product {
 
 #[post("")]
 async fn post_index_handler(
-    _: AuthUser,
+    user: AuthUser,
+    meta: RequestMeta,
     state: web::Data<AppState>,
 ) -> Result<impl Responder, ApiError> {
     let highest_orchestrator_idx: i64 =
-        sqlx::query_scalar("SELECT COALESCE(MAX(orchestrator_idx), 0) FROM products")
+        db::query_scalar("SELECT COALESCE(MAX(orchestrator_idx), 0) FROM products")
             .fetch_one(&state.db)
             .await?;
     let onchain_length: ethers::types::U256 = contracts::get_contract("HaitheOrchestrator", None)?
@@ -37,12 +41,59 @@ async fn post_index_handler(
         .await?;
 
     let mut synced_count: u32 = 0;
+    let mut updated_count: u32 = 0;
+    let mut rejected = Vec::new();
+
+    // Products can point at new content on-chain, refresh the stored URIs so cached
+    // knowledge of a changed product is dropped
+    let known_products: Vec<(i64, String, String)> =
+        db::query_as("SELECT id, address, uri FROM products")
+            .fetch_all(&state.db)
+            .await?;
+
+    for (product_id, address, uri) in known_products {
+        let onchain_uri: String = contracts::get_contract("HaitheProduct", Some(&address))?
+            .method::<_, String>("uri", ())?
+            .call()
+            .await?;
+
+        if onchain_uri == uri {
+            continue;
+        }
+
+        let mut tx = state.db.begin().await?;
+        db::query("UPDATE products SET uri = ? WHERE id = ?")
+            .bind(&onchain_uri)
+            .bind(product_id)
+            .execute(&mut tx)
+            .await?;
+        state.knowledge_cache.invalidate(&address);
+        tracing::info!(address = %address, "Product URI changed on-chain");
+
+        audit::record(
+            &mut tx,
+            &meta,
+            AuditEntry {
+                org_id: None,
+                actor: &user.wallet_address,
+                action: "product.sync",
+                target_type: "product",
+                target_id: Some(product_id.to_string()),
+                before: Some(serde_json::json!({ "uri": uri })),
+                after: Some(serde_json::json!({ "uri": onchain_uri })),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        updated_count += 1;
+    }
 
     let alith_client = alith::lazai::Client::new_testnet()?;
 
     // iterate from highest_orchestrator_idx + 1 to onchain_length
     for idx in (highest_orchestrator_idx + 1)..=onchain_length.as_u64() as i64 {
-        println!("Syncing product with orchestrator index: {}", idx);
+        tracing::info!(orchestrator_idx = idx, "Syncing product");
         // Convert to 0-based index for Solidity array access
         let solidity_idx = ethers::types::U256::from((idx - 1) as u64);
         let product_address: ethers::types::Address =
@@ -81,6 +132,16 @@ async fn post_index_handler(
                 .call()
                 .await?;
 
+        // Only products of a known kind can be enabled and charged for
+        if product_category.parse::<ProductKind>().is_err() {
+            tracing::warn!(address = %format!("{:#x}", product_address), category = %product_category, "Rejected product of unknown category");
+            rejected.push(serde_json::json!({
+                "address": format!("{:#x}", product_address),
+                "category": product_category,
+            }));
+            continue;
+        }
+
         let product_price_per_call: ethers::types::U256 =
             contracts::get_contract("HaitheProduct", Some(&format!("{:#x}", product_address)))?
                 .method::<_, ethers::types::U256>("pricePerCall", ())?
@@ -88,12 +149,10 @@ async fn post_index_handler(
                 .await?;
 
         // Ensure creator exists in accounts table
-        sqlx::query("INSERT OR IGNORE INTO accounts (wallet_address) VALUES (?);")
-            .bind(&format!("{:#x}", product_creator))
-            .execute(&state.db)
-            .await?;
+        let mut tx = state.db.begin().await?;
+        sessions::ensure_account(&mut tx, &format!("{:#x}", product_creator)).await?;
 
-        sqlx::query_as::<_, Product>(
+        let product = db::query_as::<Product>(
             "INSERT INTO products (address, orchestrator_idx, creator, name, uri, encrypted_key, price_per_call, category) VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING *",
         )
         .bind(&format!("{:#x}", product_address))
@@ -104,13 +163,37 @@ async fn post_index_handler(
         .bind(&product_encrypted_key)
         .bind(product_price_per_call.as_u64() as i64)
         .bind(&product_category)
-        .fetch_one(&state.db)
+        .fetch_one(&mut tx)
+        .await?;
+
+        audit::record(
+            &mut tx,
+            &meta,
+            AuditEntry {
+                org_id: None,
+                actor: &user.wallet_address,
+                action: "product.sync",
+                target_type: "product",
+                target_id: Some(product.id.to_string()),
+                before: None,
+                after: Some(serde_json::json!({
+                    "address": product.address,
+                    "creator": product.creator,
+                    "name": product.name,
+                    "uri": product.uri,
+                    "price_per_call": product.price_per_call,
+                    "category": product_category,
+                })),
+            },
+        )
         .await?;
+        tx.commit().await?;
+
         synced_count += 1;
 
         // Try to handle alith client operations, but continue if they fail
         if let Err(e) = async {
-            let tee_secret = std::env::var("TEE_SECRET")?;
+            let tee_secret = config::get().tee_secret.expose();
 
             let mut file_id = alith_client
                 .get_file_id_by_url(product_uri.as_str())
@@ -137,7 +220,7 @@ async fn post_index_handler(
                 pub_key.encrypt(&mut rng, Pkcs1v15Encrypt, tee_secret.as_bytes())?;
             let encryption_key = hex::encode(encryption_key);
             let encryption_seed = "default_seed"; // You may want to generate this dynamically
-            let _ = reqwest::Client::new()
+            let _ = fetch::trusted_client()
                 .post(format!("{node_url}/proof"))
                 .json(
                     &ProofRequest::builder()
@@ -157,16 +240,17 @@ async fn post_index_handler(
         }
         .await
         {
-            println!(
-                "Warning: Failed to process alith operations for product {}: {}",
-                product_name, e
-            );
+            tracing::warn!(product = %product_name, error = %e, "Failed to process alith operations for product");
         }
     }
 
     Ok(respond::ok(
         "Products synced successfully",
-        serde_json::json!({ "count": synced_count }),
+        serde_json::json!({
+            "count": synced_count,
+            "updated": updated_count,
+            "rejected": rejected,
+        }),
     ))
 }
 
@@ -178,6 +262,7 @@ struct PostEnableQuery {
 #[post("/{id}/enable")]
 async fn post_enable_handler(
     user: AuthUser,
+    meta: RequestMeta,
     query: web::Query<PostEnableQuery>,
     path: web::Path<i64>,
     state: web::Data<AppState>,
@@ -186,7 +271,7 @@ async fn post_enable_handler(
     let product_id = path.into_inner();
 
     // check permission
-    let has_permission = sqlx::query_scalar::<_, i64>(
+    let has_permission = db::query_scalar::<bool>(
         r#"
         SELECT EXISTS(
             SELECT 1
@@ -210,18 +295,48 @@ async fn post_enable_handler(
     .fetch_one(&state.db)
     .await?;
 
-    if has_permission == 0 {
+    if !has_permission {
         return Err(ApiError::BadRequest(
             "You do not have permission to enable this product for the project".to_string(),
         ));
     }
 
-    sqlx::query("INSERT INTO project_products_enabled (project_id, product_id) VALUES (?, ?)")
+    let mut tx = state.db.begin().await?;
+    db::query("INSERT INTO project_products_enabled (project_id, product_id) VALUES (?, ?)")
         .bind(project_id)
         .bind(product_id)
-        .execute(&state.db)
+        .execute(&mut tx)
+        .await?;
+
+    let org_id: Option<i64> = db::query_scalar("SELECT org_id FROM projects WHERE id = ?")
+        .bind(project_id)
+        .fetch_optional(&mut tx)
         .await?;
 
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id,
+            actor: &user.wallet_address,
+            action: "product.enable",
+            target_type: "project_product",
+            target_id: Some(format!("{}:{}", project_id, product_id)),
+            before: Some(serde_json::json!({ "enabled": false })),
+            after: Some(serde_json::json!({ "enabled": true })),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    webhooks::emit(
+        &state.db,
+        project_id,
+        "product.enabled",
+        serde_json::json!({ "project_id": project_id, "product_id": product_id }),
+    )
+    .await;
+
     Ok(respond::ok(
         "Products enabled successfully",
         serde_json::json!({}),
@@ -236,6 +351,7 @@ struct PostDisableQuery {
 #[delete("/{id}/disable")]
 async fn delete_disable_handler(
     user: AuthUser,
+    meta: RequestMeta,
     query: web::Query<PostDisableQuery>,
     path: web::Path<i64>,
     state: web::Data<AppState>,
@@ -244,7 +360,7 @@ async fn delete_disable_handler(
     let product_id = path.into_inner();
 
     // check permission
-    let has_permission = sqlx::query_scalar::<_, i64>(
+    let has_permission = db::query_scalar::<bool>(
         r#"
         SELECT EXISTS(
             SELECT 1
@@ -268,18 +384,49 @@ async fn delete_disable_handler(
     .fetch_one(&state.db)
     .await?;
 
-    if has_permission == 0 {
+    if !has_permission {
         return Err(ApiError::BadRequest(
             "You do not have permission to disable this product for the project".to_string(),
         ));
     }
 
-    sqlx::query("DELETE FROM project_products_enabled WHERE project_id = ? AND product_id = ?")
+    let mut tx = state.db.begin().await?;
+    let result =
+        db::query("DELETE FROM project_products_enabled WHERE project_id = ? AND product_id = ?")
+            .bind(project_id)
+            .bind(product_id)
+            .execute(&mut tx)
+            .await?;
+
+    let org_id: Option<i64> = db::query_scalar("SELECT org_id FROM projects WHERE id = ?")
         .bind(project_id)
-        .bind(product_id)
-        .execute(&state.db)
+        .fetch_optional(&mut tx)
         .await?;
 
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id,
+            actor: &user.wallet_address,
+            action: "product.disable",
+            target_type: "project_product",
+            target_id: Some(format!("{}:{}", project_id, product_id)),
+            before: Some(serde_json::json!({ "enabled": result.rows_affected() > 0 })),
+            after: Some(serde_json::json!({ "enabled": false })),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    webhooks::emit(
+        &state.db,
+        project_id,
+        "product.disabled",
+        serde_json::json!({ "project_id": project_id, "product_id": product_id }),
+    )
+    .await;
+
     Ok(respond::ok(
         "Product disabled successfully",
         serde_json::json!({}),
@@ -301,7 +448,7 @@ pub struct ProductSummary {
 
 #[get("")]
 async fn get_all_products(state: web::Data<AppState>) -> Result<impl Responder, ApiError> {
-    let products = sqlx::query_as::<_, ProductSummary>(
+    let products = db::query_as::<ProductSummary>(
         "SELECT id, address, creator, name, price_per_call, category, description, photo_url, created_at FROM products ORDER BY created_at DESC"
     )
     .fetch_all(&state.db)
@@ -316,6 +463,19 @@ async fn get_all_products(state: web::Data<AppState>) -> Result<impl Responder,
     ))
 }
 
+#[get("/categories")]
+async fn get_categories() -> Result<impl Responder, ApiError> {
+    let categories: Vec<serde_json::Value> = product_kinds::registry()
+        .iter()
+        .map(|handler| handler.describe())
+        .collect();
+
+    Ok(respond::ok(
+        "Product categories fetched successfully",
+        serde_json::json!({ "categories": categories }),
+    ))
+}
+
 #[get("/{id}")]
 async fn get_product_by_id(
     path: web::Path<i64>,
@@ -323,7 +483,7 @@ async fn get_product_by_id(
 ) -> Result<impl Responder, ApiError> {
     let product_id = path.into_inner();
 
-    let product = sqlx::query_as::<_, ProductSummary>(
+    let product = db::query_as::<ProductSummary>(
         "SELECT id, address, creator, name, price_per_call, category, description, photo_url, created_at FROM products WHERE id = ?"
     )
     .bind(product_id)
@@ -351,6 +511,7 @@ struct PatchProductRequest {
 #[patch("/{id}")]
 async fn patch_product_handler(
     user: AuthUser,
+    meta: RequestMeta,
     path: web::Path<i64>,
     body: web::Json<PatchProductRequest>,
     state: web::Data<AppState>,
@@ -358,7 +519,7 @@ async fn patch_product_handler(
     let product_id = path.into_inner();
 
     // Check if user is the creator of the product
-    let is_creator = sqlx::query_scalar::<_, i64>(
+    let is_creator = db::query_scalar::<bool>(
         "SELECT EXISTS(SELECT 1 FROM products WHERE id = ? AND creator = ?) as \"exists!\""
     )
     .bind(product_id)
@@ -366,12 +527,18 @@ async fn patch_product_handler(
     .fetch_one(&state.db)
     .await?;
 
-    if is_creator == 0 {
+    if !is_creator {
         return Err(ApiError::BadRequest(
             "You can only update products you created".to_string(),
         ));
     }
 
+    let before: (Option<String>, Option<String>) =
+        db::query_as("SELECT description, photo_url FROM products WHERE id = ?")
+            .bind(product_id)
+            .fetch_one(&state.db)
+            .await?;
+
     // Build dynamic update query based on provided fields
     let mut query_parts = Vec::new();
     let mut bind_values = Vec::new();
@@ -398,7 +565,7 @@ async fn patch_product_handler(
         query_parts.join(", ")
     );
 
-    let mut query = sqlx::query(&update_query);
+    let mut query = db::query(&update_query);
     
     // Bind all the update values
     for value in &bind_values {
@@ -408,23 +575,46 @@ async fn patch_product_handler(
     // Bind the WHERE clause values
     query = query.bind(product_id).bind(&user.wallet_address);
 
-    let rows_affected = query.execute(&state.db).await?.rows_affected();
+    let mut tx = state.db.begin().await?;
+    let rows_affected = query.execute(&mut tx).await?.rows_affected();
 
     if rows_affected == 0 {
         return Err(ApiError::NotFound("Product not found or you don't have permission to update it".into()));
     }
 
     // Fetch the updated product
-    let updated_product = sqlx::query_as::<_, ProductSummary>(
-        "SELECT id, address, creator, name, price_per_call, category, created_at FROM products WHERE id = ?"
+    let updated_product = db::query_as::<ProductSummary>(
+        "SELECT id, address, creator, name, price_per_call, category, description, photo_url, created_at FROM products WHERE id = ?"
     )
     .bind(product_id)
-    .fetch_one(&state.db)
+    .fetch_one(&mut tx)
     .await
     .map_err(|e| {
         ApiError::Internal("Failed to fetch updated product".into())
     })?;
 
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: None,
+            actor: &user.wallet_address,
+            action: "product.update",
+            target_type: "product",
+            target_id: Some(product_id.to_string()),
+            before: Some(serde_json::json!({
+                "description": before.0,
+                "photo_url": before.1,
+            })),
+            after: Some(serde_json::json!({
+                "description": updated_product.description,
+                "photo_url": updated_product.photo_url,
+            })),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
     Ok(respond::ok(
         "Product updated successfully",
         serde_json::json!({ "product": updated_product }),
@@ -433,6 +623,7 @@ async fn patch_product_handler(
 
 pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(get_all_products)
+        .service(get_categories)
         .service(get_product_by_id)
         .service(post_index_handler)
         .service(post_enable_handler)
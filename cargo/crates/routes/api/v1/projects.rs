@@ -1,7 +1,12 @@
+use crate::lib::db::{self, Db, products, projects};
+use crate::lib::audit::{self, AuditEntry};
 use crate::lib::discord::sync_discord_bots;
-use crate::lib::extractors::AuthUser;
+use crate::lib::extractors::{AuthUser, RequestMeta};
 use crate::lib::telegram::sync_bots;
-use crate::lib::{error::ApiError, respond, state::AppState};
+use crate::lib::models::get_models;
+use crate::lib::routing::Strategy;
+use crate::lib::webhooks::{self, TriggerInvocation, WebhookDelivery, WebhookEndpoint, WebhookTrigger};
+use crate::lib::{error::ApiError, llm, respond, state::AppState};
 use actix_web::{Responder, delete, get, patch, post, put, web};
 use serde::{Deserialize, Serialize};
 use serenity::http::Http;
@@ -22,6 +27,20 @@ pub struct Project {
     pub default_model_id: Option<i64>,
     pub teloxide_token: Option<String>,
     pub discord_token: Option<String>,
+    pub auto_title_enabled: bool,
+    pub summary_enabled: bool,
+    pub system_prompt: Option<String>,
+    pub persona_name: Option<String>,
+    pub max_tokens: Option<i64>,
+    pub min_temperature: Option<f32>,
+    pub max_temperature: Option<f32>,
+    /// JSON array of strings.
+    pub stop_sequences: String,
+    /// JSON array of model ids.
+    pub fallback_model_ids: String,
+    pub max_retries: i64,
+    pub retry_backoff_ms: i64,
+    pub routing_strategy: String,
 }
 
 #[derive(Debug, Clone, FromRow, Serialize)]
@@ -37,12 +56,39 @@ struct CreateProjectQuery {
     name: String,
 }
 
+/// Upper bound for a project's `max_tokens`.
+const MAX_TOKENS_LIMIT: i64 = 65536;
+const MAX_STOP_SEQUENCES: usize = 4;
+const MAX_RETRY_BACKOFF_MS: i64 = 10_000;
+
 #[derive(Deserialize)]
 struct UpdateProjectQuery {
     name: Option<String>,
     search_enabled: Option<bool>,
     memory_enabled: Option<bool>,
     default_model_id: Option<i64>,
+    auto_title_enabled: Option<bool>,
+    summary_enabled: Option<bool>,
+    /// Empty to go back to the platform's generic prompt.
+    system_prompt: Option<String>,
+    /// Empty to go back to "Haithe Agent".
+    persona_name: Option<String>,
+    /// 0 to go back to the default of 1024.
+    max_tokens: Option<i64>,
+    /// Negative to drop the lower bound.
+    min_temperature: Option<f32>,
+    /// Negative to drop the upper bound.
+    max_temperature: Option<f32>,
+    /// JSON array of at most 4 strings.
+    stop_sequences: Option<String>,
+    /// JSON array of model ids, tried in order when the requested model fails.
+    fallback_model_ids: Option<String>,
+    /// Retries per model before moving to the next one.
+    max_retries: Option<i64>,
+    /// Wait before the first retry, doubled on every further one.
+    retry_backoff_ms: Option<i64>,
+    /// `ordered`, `cost` or `latency`.
+    routing_strategy: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -72,12 +118,38 @@ struct PutProjectDiscordBody {
     discord_token: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct PutPromptVariablesBody {
+    variables: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct PostWebhookBody {
+    url: String,
+    events: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct PostTriggerBody {
+    name: String,
+    prompt_template: String,
+    model: String,
+    callback_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GetDeliveriesQuery {
+    status: Option<String>,
+    limit: Option<i64>,
+    before_id: Option<i64>,
+}
+
 async fn can_manage_org(
     user_wallet: &str,
     org_id: i64,
-    db: &sqlx::SqlitePool,
+    db: &Db,
 ) -> Result<bool, sqlx::Error> {
-    let owner_check = sqlx::query_scalar::<_, i64>(
+    let owner_check = db::query_scalar::<i64>(
         "SELECT COUNT(*) FROM organizations WHERE id = ? AND owner = ?",
     )
     .bind(org_id)
@@ -89,7 +161,7 @@ async fn can_manage_org(
         return Ok(true);
     }
 
-    let admin_check = sqlx::query_scalar::<_, i64>(
+    let admin_check = db::query_scalar::<i64>(
         "SELECT COUNT(*) FROM org_members WHERE org_id = ? AND wallet_address = ? AND role = 'admin'"
     )
     .bind(org_id)
@@ -103,9 +175,9 @@ async fn can_manage_org(
 async fn can_manage_project(
     user_wallet: &str,
     project_id: i64,
-    db: &sqlx::SqlitePool,
+    db: &Db,
 ) -> Result<bool, sqlx::Error> {
-    let org_id: Option<i64> = sqlx::query_scalar("SELECT org_id FROM projects WHERE id = ?")
+    let org_id: Option<i64> = db::query_scalar("SELECT org_id FROM projects WHERE id = ?")
         .bind(project_id)
         .fetch_optional(db)
         .await?;
@@ -119,7 +191,7 @@ async fn can_manage_project(
         return Ok(true);
     }
 
-    let project_admin_check = sqlx::query_scalar::<_, i64>(
+    let project_admin_check = db::query_scalar::<i64>(
         "SELECT COUNT(*) FROM project_members WHERE project_id = ? AND wallet_address = ? AND role = 'admin'"
     )
     .bind(project_id)
@@ -130,9 +202,37 @@ async fn can_manage_project(
     Ok(project_admin_check > 0)
 }
 
+/// Project snapshot for the audit log, with bot tokens reduced to whether they are set.
+fn audit_project(project: &Project) -> serde_json::Value {
+    serde_json::json!({
+        "id": project.id,
+        "org_id": project.org_id,
+        "project_uid": project.project_uid,
+        "name": project.name,
+        "search_enabled": project.search_enabled,
+        "memory_enabled": project.memory_enabled,
+        "default_model_id": project.default_model_id,
+        "auto_title_enabled": project.auto_title_enabled,
+        "summary_enabled": project.summary_enabled,
+        "system_prompt": project.system_prompt,
+        "persona_name": project.persona_name,
+        "max_tokens": project.max_tokens,
+        "min_temperature": project.min_temperature,
+        "max_temperature": project.max_temperature,
+        "stop_sequences": project.stop_sequences,
+        "fallback_model_ids": project.fallback_model_ids,
+        "max_retries": project.max_retries,
+        "retry_backoff_ms": project.retry_backoff_ms,
+        "routing_strategy": project.routing_strategy,
+        "teloxide_token_configured": project.teloxide_token.is_some(),
+        "discord_token_configured": project.discord_token.is_some(),
+    })
+}
+
 #[post("")]
 async fn create_project_handler(
     user: AuthUser,
+    meta: RequestMeta,
     query: web::Query<CreateProjectQuery>,
     state: web::Data<AppState>,
 ) -> Result<impl Responder, ApiError> {
@@ -141,7 +241,7 @@ async fn create_project_handler(
     }
 
     let org_exists =
-        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM organizations WHERE id = ?")
+        db::query_scalar::<i64>("SELECT COUNT(*) FROM organizations WHERE id = ?")
             .bind(&query.org_id)
             .fetch_one(&state.db)
             .await?;
@@ -155,16 +255,33 @@ async fn create_project_handler(
     // Set default model to gemini-2.0-flash (model ID 1) if none specified
     let default_model_id = Some(1i64);
 
-    let project = sqlx::query_as::<_, Project>(
-        "INSERT INTO projects (org_id, name, project_uid, default_model_id) VALUES (?, ?, ?, ?) RETURNING id, org_id, project_uid, name, created_at, search_enabled, memory_enabled, default_model_id, teloxide_token, discord_token",
+    let mut tx = state.db.begin().await?;
+    let project = db::query_as::<Project>(
+        "INSERT INTO projects (org_id, name, project_uid, default_model_id) VALUES (?, ?, ?, ?) RETURNING id, org_id, project_uid, name, created_at, search_enabled, memory_enabled, default_model_id, teloxide_token, discord_token, auto_title_enabled, summary_enabled, system_prompt, persona_name, max_tokens, min_temperature, max_temperature, stop_sequences, fallback_model_ids, max_retries, retry_backoff_ms, routing_strategy",
     )
     .bind(&query.org_id)
     .bind(&query.name)
     .bind(&project_uid)
     .bind(default_model_id)
-    .fetch_one(&state.db)
+    .fetch_one(&mut tx)
     .await?;
 
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: Some(project.org_id),
+            actor: &user.wallet_address,
+            action: "project.create",
+            target_type: "project",
+            target_id: Some(project.id.to_string()),
+            before: None,
+            after: Some(audit_project(&project)),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
     Ok(respond::ok("Project created", project))
 }
 
@@ -176,8 +293,8 @@ async fn get_project_handler(
 ) -> Result<impl Responder, ApiError> {
     let project_id = path.into_inner();
 
-    let project = sqlx::query_as::<_, Project>(
-        "SELECT id, org_id, project_uid, name, created_at, search_enabled, memory_enabled, default_model_id, teloxide_token, discord_token FROM projects WHERE id = ?"
+    let project = db::query_as::<Project>(
+        "SELECT id, org_id, project_uid, name, created_at, search_enabled, memory_enabled, default_model_id, teloxide_token, discord_token, auto_title_enabled, summary_enabled, system_prompt, persona_name, max_tokens, min_temperature, max_temperature, stop_sequences, fallback_model_ids, max_retries, retry_backoff_ms, routing_strategy FROM projects WHERE id = ?"
     )
     .bind(project_id)
     .fetch_one(&state.db)
@@ -190,6 +307,7 @@ async fn get_project_handler(
 #[patch("/{id}")]
 async fn update_project_handler(
     user: AuthUser,
+    meta: RequestMeta,
     path: web::Path<i64>,
     query: web::Query<UpdateProjectQuery>,
     state: web::Data<AppState>,
@@ -223,18 +341,159 @@ async fn update_project_handler(
         any_updates = true;
     }
 
+    if query.auto_title_enabled.is_some() {
+        update_parts.push("auto_title_enabled = ?");
+        any_updates = true;
+    }
+
+    if query.summary_enabled.is_some() {
+        update_parts.push("summary_enabled = ?");
+        any_updates = true;
+    }
+
+    if query.system_prompt.is_some() {
+        update_parts.push("system_prompt = ?");
+        any_updates = true;
+    }
+
+    if query.persona_name.is_some() {
+        update_parts.push("persona_name = ?");
+        any_updates = true;
+    }
+
+    if let Some(max_tokens) = query.max_tokens {
+        if !(0..=MAX_TOKENS_LIMIT).contains(&max_tokens) {
+            return Err(ApiError::BadRequest(format!(
+                "max_tokens must be between 0 and {}",
+                MAX_TOKENS_LIMIT
+            )));
+        }
+        update_parts.push("max_tokens = ?");
+        any_updates = true;
+    }
+
+    for (field, value) in [
+        ("min_temperature", query.min_temperature),
+        ("max_temperature", query.max_temperature),
+    ] {
+        if value.is_some_and(|t| t > 2.0 || t.is_nan()) {
+            return Err(ApiError::BadRequest(format!(
+                "{} must be between 0 and 2, or negative to clear it",
+                field
+            )));
+        }
+    }
+
+    if query.min_temperature.is_some() {
+        update_parts.push("min_temperature = ?");
+        any_updates = true;
+    }
+
+    if query.max_temperature.is_some() {
+        update_parts.push("max_temperature = ?");
+        any_updates = true;
+    }
+
+    let stop_sequences = match &query.stop_sequences {
+        Some(raw) => {
+            let stops: Vec<String> = serde_json::from_str(raw).map_err(|_| {
+                ApiError::BadRequest("stop_sequences must be a JSON array of strings".to_string())
+            })?;
+            if stops.len() > MAX_STOP_SEQUENCES || stops.iter().any(|stop| stop.is_empty()) {
+                return Err(ApiError::BadRequest(format!(
+                    "stop_sequences takes at most {} non-empty strings",
+                    MAX_STOP_SEQUENCES
+                )));
+            }
+            update_parts.push("stop_sequences = ?");
+            any_updates = true;
+            Some(serde_json::Value::from(stops).to_string())
+        }
+        None => None,
+    };
+
+    let fallback_model_ids = match &query.fallback_model_ids {
+        Some(raw) => {
+            let ids: Vec<i64> = serde_json::from_str(raw).map_err(|_| {
+                ApiError::BadRequest("fallback_model_ids must be a JSON array of model ids".to_string())
+            })?;
+            let models = get_models();
+            if let Some(id) = ids.iter().find(|&&id| !models.iter().any(|m| m.id as i64 == id)) {
+                return Err(ApiError::BadRequest(format!("Invalid fallback model {}", id)));
+            }
+            update_parts.push("fallback_model_ids = ?");
+            any_updates = true;
+            Some(serde_json::Value::from(ids).to_string())
+        }
+        None => None,
+    };
+
+    if let Some(max_retries) = query.max_retries {
+        if !(0..=llm::MAX_RETRIES).contains(&max_retries) {
+            return Err(ApiError::BadRequest(format!(
+                "max_retries must be between 0 and {}",
+                llm::MAX_RETRIES
+            )));
+        }
+        update_parts.push("max_retries = ?");
+        any_updates = true;
+    }
+
+    if let Some(retry_backoff_ms) = query.retry_backoff_ms {
+        if !(0..=MAX_RETRY_BACKOFF_MS).contains(&retry_backoff_ms) {
+            return Err(ApiError::BadRequest(format!(
+                "retry_backoff_ms must be between 0 and {}",
+                MAX_RETRY_BACKOFF_MS
+            )));
+        }
+        update_parts.push("retry_backoff_ms = ?");
+        any_updates = true;
+    }
+
+    if let Some(ref routing_strategy) = query.routing_strategy {
+        routing_strategy.parse::<Strategy>()?;
+        update_parts.push("routing_strategy = ?");
+        any_updates = true;
+    }
+
     if !any_updates {
         return Err(ApiError::BadRequest(
             "No fields provided to update".to_string(),
         ));
     }
 
+    let mut tx = state.db.begin().await?;
+    let before = db::query_as::<Project>(
+        "SELECT id, org_id, project_uid, name, created_at, search_enabled, memory_enabled, default_model_id, teloxide_token, discord_token, auto_title_enabled, summary_enabled, system_prompt, persona_name, max_tokens, min_temperature, max_temperature, stop_sequences, fallback_model_ids, max_retries, retry_backoff_ms, routing_strategy FROM projects WHERE id = ?"
+    )
+    .bind(project_id)
+    .fetch_optional(&mut tx)
+    .await?;
+
+    if let Some(before) = &before {
+        let min = match query.min_temperature {
+            Some(t) => Some(t).filter(|&t| t >= 0.0),
+            None => before.min_temperature,
+        };
+        let max = match query.max_temperature {
+            Some(t) => Some(t).filter(|&t| t >= 0.0),
+            None => before.max_temperature,
+        };
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                return Err(ApiError::BadRequest(
+                    "min_temperature must not exceed max_temperature".to_string(),
+                ));
+            }
+        }
+    }
+
     let sql = format!(
-        "UPDATE projects SET {} WHERE id = ? RETURNING id, org_id, project_uid, name, created_at, search_enabled, memory_enabled, default_model_id, teloxide_token, discord_token",
+        "UPDATE projects SET {} WHERE id = ? RETURNING id, org_id, project_uid, name, created_at, search_enabled, memory_enabled, default_model_id, teloxide_token, discord_token, auto_title_enabled, summary_enabled, system_prompt, persona_name, max_tokens, min_temperature, max_temperature, stop_sequences, fallback_model_ids, max_retries, retry_backoff_ms, routing_strategy",
         update_parts.join(", ")
     );
 
-    let mut query_builder = sqlx::query_as::<_, Project>(&sql);
+    let mut query_builder = db::query_as::<Project>(&sql);
 
     if let Some(ref name) = query.name {
         query_builder = query_builder.bind(name);
@@ -252,19 +511,84 @@ async fn update_project_handler(
         query_builder = query_builder.bind(default_model_id);
     }
 
+    if let Some(auto_title_enabled) = query.auto_title_enabled {
+        query_builder = query_builder.bind(auto_title_enabled);
+    }
+
+    if let Some(summary_enabled) = query.summary_enabled {
+        query_builder = query_builder.bind(summary_enabled);
+    }
+
+    if let Some(ref system_prompt) = query.system_prompt {
+        query_builder = query_builder.bind(Some(system_prompt).filter(|p| !p.trim().is_empty()));
+    }
+
+    if let Some(ref persona_name) = query.persona_name {
+        query_builder = query_builder.bind(Some(persona_name).filter(|n| !n.trim().is_empty()));
+    }
+
+    if let Some(max_tokens) = query.max_tokens {
+        query_builder = query_builder.bind(Some(max_tokens).filter(|&t| t > 0));
+    }
+
+    if let Some(min_temperature) = query.min_temperature {
+        query_builder = query_builder.bind(Some(min_temperature).filter(|&t| t >= 0.0));
+    }
+
+    if let Some(max_temperature) = query.max_temperature {
+        query_builder = query_builder.bind(Some(max_temperature).filter(|&t| t >= 0.0));
+    }
+
+    if let Some(ref stop_sequences) = stop_sequences {
+        query_builder = query_builder.bind(stop_sequences);
+    }
+
+    if let Some(ref fallback_model_ids) = fallback_model_ids {
+        query_builder = query_builder.bind(fallback_model_ids);
+    }
+
+    if let Some(max_retries) = query.max_retries {
+        query_builder = query_builder.bind(max_retries);
+    }
+
+    if let Some(retry_backoff_ms) = query.retry_backoff_ms {
+        query_builder = query_builder.bind(retry_backoff_ms);
+    }
+
+    if let Some(ref routing_strategy) = query.routing_strategy {
+        query_builder = query_builder.bind(routing_strategy);
+    }
+
     query_builder = query_builder.bind(project_id);
 
     let project = query_builder
-        .fetch_one(&state.db)
+        .fetch_one(&mut tx)
         .await
         .map_err(|_| ApiError::NotFound("Project not found".to_string()))?;
 
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: Some(project.org_id),
+            actor: &user.wallet_address,
+            action: "project.update",
+            target_type: "project",
+            target_id: Some(project.id.to_string()),
+            before: before.as_ref().map(audit_project),
+            after: Some(audit_project(&project)),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
     Ok(respond::ok("Project updated", project))
 }
 
 #[delete("/{id}")]
 async fn delete_project_handler(
     user: AuthUser,
+    meta: RequestMeta,
     path: web::Path<i64>,
     state: web::Data<AppState>,
 ) -> Result<impl Responder, ApiError> {
@@ -274,14 +598,31 @@ async fn delete_project_handler(
         return Err(ApiError::Forbidden);
     }
 
-    let project = sqlx::query_as::<_, Project>(
-        "DELETE FROM projects WHERE id = ? RETURNING id, org_id, project_uid, name, created_at, search_enabled, memory_enabled, default_model_id, teloxide_token, discord_token"
+    let mut tx = state.db.begin().await?;
+    let project = db::query_as::<Project>(
+        "DELETE FROM projects WHERE id = ? RETURNING id, org_id, project_uid, name, created_at, search_enabled, memory_enabled, default_model_id, teloxide_token, discord_token, auto_title_enabled, summary_enabled, system_prompt, persona_name, max_tokens, min_temperature, max_temperature, stop_sequences, fallback_model_ids, max_retries, retry_backoff_ms, routing_strategy"
     )
     .bind(project_id)
-    .fetch_one(&state.db)
+    .fetch_one(&mut tx)
     .await
     .map_err(|_| ApiError::NotFound("Project not found".to_string()))?;
 
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id: Some(project.org_id),
+            actor: &user.wallet_address,
+            action: "project.delete",
+            target_type: "project",
+            target_id: Some(project.id.to_string()),
+            before: Some(audit_project(&project)),
+            after: None,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
     Ok(respond::ok("Project deleted", project))
 }
 
@@ -293,7 +634,7 @@ async fn get_project_members_handler(
 ) -> Result<impl Responder, ApiError> {
     let project_id = path.into_inner();
 
-    let project_exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM projects WHERE id = ?")
+    let project_exists = db::query_scalar::<i64>("SELECT COUNT(*) FROM projects WHERE id = ?")
         .bind(project_id)
         .fetch_one(&state.db)
         .await?;
@@ -302,7 +643,7 @@ async fn get_project_members_handler(
         return Err(ApiError::NotFound("Project not found".to_string()));
     }
 
-    let members = sqlx::query_as::<_, ProjectMember>(
+    let members = db::query_as::<ProjectMember>(
         "SELECT project_id, wallet_address, role FROM project_members WHERE project_id = ? ORDER BY role DESC"
     )
     .bind(project_id)
@@ -315,6 +656,7 @@ async fn get_project_members_handler(
 #[post("/{id}/members")]
 async fn add_project_member_handler(
     user: AuthUser,
+    meta: RequestMeta,
     path: web::Path<i64>,
     query: web::Query<AddMemberQuery>,
     state: web::Data<AppState>,
@@ -322,7 +664,7 @@ async fn add_project_member_handler(
     let project_id = path.into_inner();
     let wallet_address = query.wallet_address.to_lowercase();
 
-    let project_exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM projects WHERE id = ?")
+    let project_exists = db::query_scalar::<i64>("SELECT COUNT(*) FROM projects WHERE id = ?")
         .bind(project_id)
         .fetch_one(&state.db)
         .await?;
@@ -341,21 +683,48 @@ async fn add_project_member_handler(
         ));
     }
 
-    let member = sqlx::query_as::<_, ProjectMember>(
+    let mut tx = state.db.begin().await?;
+    let member = db::query_as::<ProjectMember>(
         "INSERT INTO project_members (project_id, wallet_address, role) VALUES (?, ?, ?) RETURNING project_id, wallet_address, role"
     )
     .bind(project_id)
     .bind(&wallet_address)
     .bind(&query.role)
-    .fetch_one(&state.db)
+    .fetch_one(&mut tx)
     .await?;
 
+    let org_id = projects::org_id(&mut tx, project_id).await?;
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id,
+            actor: &user.wallet_address,
+            action: "project.member.add",
+            target_type: "project_member",
+            target_id: Some(format!("{}:{}", project_id, member.wallet_address)),
+            before: None,
+            after: Some(serde_json::json!(member)),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    webhooks::emit(
+        &state.db,
+        project_id,
+        "member.added",
+        serde_json::json!({ "scope": "project", "member": member }),
+    )
+    .await;
+
     Ok(respond::ok("Member added to project", member))
 }
 
 #[patch("/{id}/members")]
 async fn update_project_member_handler(
     user: AuthUser,
+    meta: RequestMeta,
     path: web::Path<i64>,
     query: web::Query<UpdateMemberQuery>,
     state: web::Data<AppState>,
@@ -373,22 +742,57 @@ async fn update_project_member_handler(
         ));
     }
 
-    let member = sqlx::query_as::<_, ProjectMember>(
+    let mut tx = state.db.begin().await?;
+    let before = db::query_as::<ProjectMember>(
+        "SELECT project_id, wallet_address, role FROM project_members WHERE project_id = ? AND wallet_address = ?"
+    )
+    .bind(project_id)
+    .bind(&wallet_address)
+    .fetch_optional(&mut tx)
+    .await?;
+
+    let member = db::query_as::<ProjectMember>(
         "UPDATE project_members SET role = ? WHERE project_id = ? AND wallet_address = ? RETURNING project_id, wallet_address, role"
     )
     .bind(&query.role)
     .bind(project_id)
     .bind(&wallet_address)
-    .fetch_one(&state.db)
+    .fetch_one(&mut tx)
     .await
     .map_err(|_| ApiError::NotFound("Project member not found".to_string()))?;
 
+    let org_id = projects::org_id(&mut tx, project_id).await?;
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id,
+            actor: &user.wallet_address,
+            action: "project.member.update",
+            target_type: "project_member",
+            target_id: Some(format!("{}:{}", project_id, member.wallet_address)),
+            before: before.map(|b| serde_json::json!(b)),
+            after: Some(serde_json::json!(member)),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    webhooks::emit(
+        &state.db,
+        project_id,
+        "member.updated",
+        serde_json::json!({ "scope": "project", "member": member }),
+    )
+    .await;
+
     Ok(respond::ok("Member role updated", member))
 }
 
 #[delete("/{id}/members")]
 async fn remove_project_member_handler(
     user: AuthUser,
+    meta: RequestMeta,
     path: web::Path<i64>,
     query: web::Query<RemoveMemberQuery>,
     state: web::Data<AppState>,
@@ -400,15 +804,41 @@ async fn remove_project_member_handler(
         return Err(ApiError::Forbidden);
     }
 
-    let member = sqlx::query_as::<_, ProjectMember>(
+    let mut tx = state.db.begin().await?;
+    let member = db::query_as::<ProjectMember>(
         "DELETE FROM project_members WHERE project_id = ? AND wallet_address = ? RETURNING project_id, wallet_address, role"
     )
     .bind(project_id)
     .bind(&wallet_address)
-    .fetch_one(&state.db)
+    .fetch_one(&mut tx)
     .await
     .map_err(|_| ApiError::NotFound("Project member not found".to_string()))?;
 
+    let org_id = projects::org_id(&mut tx, project_id).await?;
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id,
+            actor: &user.wallet_address,
+            action: "project.member.remove",
+            target_type: "project_member",
+            target_id: Some(format!("{}:{}", project_id, member.wallet_address)),
+            before: Some(serde_json::json!(member)),
+            after: None,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    webhooks::emit(
+        &state.db,
+        project_id,
+        "member.removed",
+        serde_json::json!({ "scope": "project", "member": member }),
+    )
+    .await;
+
     Ok(respond::ok("Member removed from project", member))
 }
 
@@ -420,7 +850,7 @@ async fn get_project_products_handler(
 ) -> Result<impl Responder, ApiError> {
     let project_id = path.into_inner();
 
-    let product_ids = sqlx::query_scalar::<_, i64>(
+    let product_ids = db::query_scalar::<i64>(
         "SELECT product_id FROM project_products_enabled WHERE project_id = ?",
     )
     .bind(project_id)
@@ -442,7 +872,7 @@ async fn get_project_price_per_call_handler(
 ) -> Result<impl Responder, ApiError> {
     let project_id = path.into_inner();
 
-    let project_exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM projects WHERE id = ?")
+    let project_exists = db::query_scalar::<i64>("SELECT COUNT(*) FROM projects WHERE id = ?")
         .bind(project_id)
         .fetch_one(&state.db)
         .await?;
@@ -451,17 +881,7 @@ async fn get_project_price_per_call_handler(
         return Err(ApiError::NotFound("Project not found".to_string()));
     }
 
-    let total_price: Option<i64> = sqlx::query_scalar(
-        "SELECT SUM(p.price_per_call) 
-         FROM products p 
-         INNER JOIN project_products_enabled ppe ON p.id = ppe.product_id 
-         WHERE ppe.project_id = ?",
-    )
-    .bind(project_id)
-    .fetch_one(&state.db)
-    .await?;
-
-    let total_price = total_price.unwrap_or(0);
+    let total_price = products::enabled_price_per_call(&state.db, project_id).await?;
 
     Ok(respond::ok(
         "Total price per call calculated",
@@ -472,6 +892,7 @@ async fn get_project_price_per_call_handler(
 #[put("/{id}/telegram")]
 async fn put_project_telegram_handler(
     user: AuthUser,
+    meta: RequestMeta,
     path: web::Path<i64>,
     body: web::Json<PutProjectTelegramBody>,
     state: web::Data<AppState>,
@@ -488,17 +909,41 @@ async fn put_project_telegram_handler(
         .map(|s| s.trim().to_string())
         .filter(|s| !s.is_empty());
 
-    sqlx::query("UPDATE projects SET teloxide_token = ? WHERE id = ?")
+    let was_configured: Option<Option<String>> =
+        db::query_scalar("SELECT teloxide_token FROM projects WHERE id = ?")
+            .bind(project_id)
+            .fetch_optional(&state.db)
+            .await?;
+    let was_configured = was_configured.flatten().is_some_and(|t| !t.is_empty());
+    let is_configured = token_opt.is_some();
+
+    let mut tx = state.db.begin().await?;
+    db::query("UPDATE projects SET teloxide_token = ? WHERE id = ?")
         .bind(token_opt)
         .bind(project_id)
-        .execute(&state.db)
+        .execute(&mut tx)
         .await?;
 
+    let org_id = projects::org_id(&mut tx, project_id).await?;
+    // Bot tokens are secrets, only their presence is recorded
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id,
+            actor: &user.wallet_address,
+            action: "project.telegram.update",
+            target_type: "project",
+            target_id: Some(project_id.to_string()),
+            before: Some(serde_json::json!({ "teloxide_token_configured": was_configured })),
+            after: Some(serde_json::json!({ "teloxide_token_configured": is_configured })),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
     if let Err(e) = sync_bots(state.clone()).await {
-        eprintln!(
-            "Failed to sync Telegram bots after project token update: {}",
-            e
-        );
+        tracing::error!(error = %e, "Failed to sync Telegram bots after project token update");
     }
 
     Ok(respond::ok("Telegram token updated", serde_json::json!({})))
@@ -522,7 +967,7 @@ async fn get_project_telegram_info_handler(
         org_uid: String,
         teloxide_token: Option<String>,
     }
-    let row = sqlx::query_as::<_, Row>(
+    let row = db::query_as::<Row>(
         r#"SELECT pr.project_uid as project_uid, o.organization_uid as org_uid, pr.teloxide_token
            FROM projects pr
            JOIN organizations o ON o.id = pr.org_id
@@ -586,6 +1031,7 @@ async fn get_project_telegram_info_handler(
 #[put("/{id}/discord")]
 async fn put_project_discord_handler(
     user: AuthUser,
+    meta: RequestMeta,
     path: web::Path<i64>,
     body: web::Json<PutProjectDiscordBody>,
     state: web::Data<AppState>,
@@ -602,17 +1048,41 @@ async fn put_project_discord_handler(
         .map(|s| s.trim().to_string())
         .filter(|s| !s.is_empty());
 
-    sqlx::query("UPDATE projects SET discord_token = ? WHERE id = ?")
+    let was_configured: Option<Option<String>> =
+        db::query_scalar("SELECT discord_token FROM projects WHERE id = ?")
+            .bind(project_id)
+            .fetch_optional(&state.db)
+            .await?;
+    let was_configured = was_configured.flatten().is_some_and(|t| !t.is_empty());
+    let is_configured = token_opt.is_some();
+
+    let mut tx = state.db.begin().await?;
+    db::query("UPDATE projects SET discord_token = ? WHERE id = ?")
         .bind(token_opt)
         .bind(project_id)
-        .execute(&state.db)
+        .execute(&mut tx)
         .await?;
 
+    let org_id = projects::org_id(&mut tx, project_id).await?;
+    // Bot tokens are secrets, only their presence is recorded
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id,
+            actor: &user.wallet_address,
+            action: "project.discord.update",
+            target_type: "project",
+            target_id: Some(project_id.to_string()),
+            before: Some(serde_json::json!({ "discord_token_configured": was_configured })),
+            after: Some(serde_json::json!({ "discord_token_configured": is_configured })),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
     if let Err(e) = sync_discord_bots(state.clone()).await {
-        eprintln!(
-            "Failed to sync Discord bots after project token update: {}",
-            e
-        );
+        tracing::error!(error = %e, "Failed to sync Discord bots after project token update");
     }
 
     Ok(respond::ok("Discord token updated", serde_json::json!({})))
@@ -636,7 +1106,7 @@ async fn get_project_discord_info_handler(
         org_uid: String,
         discord_token: Option<String>,
     }
-    let row = sqlx::query_as::<_, Row>(
+    let row = db::query_as::<Row>(
         r#"SELECT pr.project_uid as project_uid, o.organization_uid as org_uid, pr.discord_token
            FROM projects pr
            JOIN organizations o ON o.id = pr.org_id
@@ -703,6 +1173,496 @@ async fn get_project_discord_info_handler(
     ))
 }
 
+#[get("/{id}/prompt-variables")]
+async fn get_project_prompt_variables_handler(
+    user: AuthUser,
+    path: web::Path<i64>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let project_id = path.into_inner();
+
+    if !can_manage_project(&user.wallet_address, project_id, &state.db).await? {
+        return Err(ApiError::Forbidden);
+    }
+
+    let variables: serde_json::Value =
+        serde_json::from_str(&projects::prompt_variables(&state.db, project_id).await?)
+            .map_err(|e| ApiError::Internal(format!("Invalid prompt variables: {}", e)))?;
+
+    Ok(respond::ok(
+        "Prompt variables",
+        serde_json::json!({ "variables": variables }),
+    ))
+}
+
+/// Replaces the values promptset products' `{{variable}}` placeholders are filled
+/// with. Request metadata overrides them per completion.
+#[put("/{id}/prompt-variables")]
+async fn put_project_prompt_variables_handler(
+    user: AuthUser,
+    meta: RequestMeta,
+    path: web::Path<i64>,
+    body: web::Json<PutPromptVariablesBody>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let project_id = path.into_inner();
+
+    if !can_manage_project(&user.wallet_address, project_id, &state.db).await? {
+        return Err(ApiError::Forbidden);
+    }
+
+    let before: serde_json::Value =
+        serde_json::from_str(&projects::prompt_variables(&state.db, project_id).await?)
+            .map_err(|e| ApiError::Internal(format!("Invalid prompt variables: {}", e)))?;
+    let variables = serde_json::Value::Object(body.into_inner().variables);
+
+    let mut tx = state.db.begin().await?;
+    db::query("UPDATE projects SET prompt_variables = ? WHERE id = ?")
+        .bind(variables.to_string())
+        .bind(project_id)
+        .execute(&mut tx)
+        .await?;
+
+    let org_id = projects::org_id(&mut tx, project_id).await?;
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id,
+            actor: &user.wallet_address,
+            action: "project.prompt_variables.update",
+            target_type: "project",
+            target_id: Some(project_id.to_string()),
+            before: Some(before),
+            after: Some(variables.clone()),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(respond::ok(
+        "Prompt variables updated",
+        serde_json::json!({ "variables": variables }),
+    ))
+}
+
+async fn project_webhook(
+    project_id: i64,
+    webhook_id: i64,
+    db: &Db,
+) -> Result<WebhookEndpoint, ApiError> {
+    db::query_as::<WebhookEndpoint>(
+        "SELECT * FROM webhook_endpoints WHERE id = ? AND project_id = ?",
+    )
+    .bind(webhook_id)
+    .bind(project_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Webhook not found".to_string()))
+}
+
+#[get("/{id}/webhooks")]
+async fn get_project_webhooks_handler(
+    user: AuthUser,
+    path: web::Path<i64>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let project_id = path.into_inner();
+
+    if !can_manage_project(&user.wallet_address, project_id, &state.db).await? {
+        return Err(ApiError::Forbidden);
+    }
+
+    let endpoints = db::query_as::<WebhookEndpoint>(
+        "SELECT * FROM webhook_endpoints WHERE project_id = ? ORDER BY id",
+    )
+    .bind(project_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(respond::ok(
+        "Project webhooks retrieved",
+        serde_json::json!({ "webhooks": endpoints, "events": webhooks::EVENTS }),
+    ))
+}
+
+#[post("/{id}/webhooks")]
+async fn post_project_webhook_handler(
+    user: AuthUser,
+    meta: RequestMeta,
+    path: web::Path<i64>,
+    body: web::Json<PostWebhookBody>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let project_id = path.into_inner();
+
+    if !can_manage_project(&user.wallet_address, project_id, &state.db).await? {
+        return Err(ApiError::Forbidden);
+    }
+
+    let url = body.url.trim();
+    if !(url.starts_with("https://") || url.starts_with("http://")) {
+        return Err(ApiError::BadRequest(
+            "Webhook URL must be an http(s) URL".to_string(),
+        ));
+    }
+
+    let events = match &body.events {
+        Some(events) if !events.is_empty() => {
+            if let Some(unknown) = events
+                .iter()
+                .find(|e| e.as_str() != "*" && !webhooks::EVENTS.contains(&e.as_str()))
+            {
+                return Err(ApiError::BadRequest(format!(
+                    "Unknown webhook event '{}'",
+                    unknown
+                )));
+            }
+            events.join(",")
+        }
+        _ => "*".to_string(),
+    };
+
+    let secret = webhooks::generate_secret();
+
+    let mut tx = state.db.begin().await?;
+    let endpoint = db::query_as::<WebhookEndpoint>(
+        "INSERT INTO webhook_endpoints (project_id, url, secret, events) VALUES (?, ?, ?, ?) RETURNING *",
+    )
+    .bind(project_id)
+    .bind(url)
+    .bind(&secret)
+    .bind(&events)
+    .fetch_one(&mut tx)
+    .await?;
+
+    let org_id = projects::org_id(&mut tx, project_id).await?;
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id,
+            actor: &user.wallet_address,
+            action: "project.webhook.create",
+            target_type: "webhook_endpoint",
+            target_id: Some(endpoint.id.to_string()),
+            before: None,
+            after: Some(serde_json::json!(endpoint)),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    // The signing secret is only ever returned here
+    Ok(respond::ok(
+        "Webhook created",
+        serde_json::json!({ "webhook": endpoint, "secret": secret }),
+    ))
+}
+
+#[delete("/{id}/webhooks/{webhook_id}")]
+async fn delete_project_webhook_handler(
+    user: AuthUser,
+    meta: RequestMeta,
+    path: web::Path<(i64, i64)>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let (project_id, webhook_id) = path.into_inner();
+
+    if !can_manage_project(&user.wallet_address, project_id, &state.db).await? {
+        return Err(ApiError::Forbidden);
+    }
+
+    let endpoint = project_webhook(project_id, webhook_id, &state.db).await?;
+
+    let mut tx = state.db.begin().await?;
+    db::query("DELETE FROM webhook_endpoints WHERE id = ?")
+        .bind(webhook_id)
+        .execute(&mut tx)
+        .await?;
+
+    let org_id = projects::org_id(&mut tx, project_id).await?;
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id,
+            actor: &user.wallet_address,
+            action: "project.webhook.delete",
+            target_type: "webhook_endpoint",
+            target_id: Some(webhook_id.to_string()),
+            before: Some(serde_json::json!(endpoint)),
+            after: None,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(respond::ok("Webhook deleted", endpoint))
+}
+
+#[get("/{id}/webhooks/{webhook_id}/deliveries")]
+async fn get_project_webhook_deliveries_handler(
+    user: AuthUser,
+    path: web::Path<(i64, i64)>,
+    query: web::Query<GetDeliveriesQuery>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let (project_id, webhook_id) = path.into_inner();
+
+    if !can_manage_project(&user.wallet_address, project_id, &state.db).await? {
+        return Err(ApiError::Forbidden);
+    }
+
+    project_webhook(project_id, webhook_id, &state.db).await?;
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+
+    let deliveries = db::query_as::<WebhookDelivery>(
+        "SELECT * FROM webhook_deliveries
+         WHERE endpoint_id = ? AND (? IS NULL OR status = ?) AND (? IS NULL OR id < ?)
+         ORDER BY id DESC LIMIT ?",
+    )
+    .bind(webhook_id)
+    .bind(&query.status)
+    .bind(&query.status)
+    .bind(query.before_id)
+    .bind(query.before_id)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await?;
+
+    let next_before_id = if deliveries.len() as i64 == limit {
+        deliveries.last().map(|d| d.id)
+    } else {
+        None
+    };
+
+    Ok(respond::ok(
+        "Webhook deliveries retrieved",
+        serde_json::json!({ "deliveries": deliveries, "next_before_id": next_before_id }),
+    ))
+}
+
+#[post("/{id}/webhooks/{webhook_id}/deliveries/{delivery_id}/replay")]
+async fn replay_project_webhook_delivery_handler(
+    user: AuthUser,
+    meta: RequestMeta,
+    path: web::Path<(i64, i64, i64)>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let (project_id, webhook_id, delivery_id) = path.into_inner();
+
+    if !can_manage_project(&user.wallet_address, project_id, &state.db).await? {
+        return Err(ApiError::Forbidden);
+    }
+
+    project_webhook(project_id, webhook_id, &state.db).await?;
+
+    let delivery = db::query_as::<WebhookDelivery>(
+        "SELECT * FROM webhook_deliveries WHERE id = ? AND endpoint_id = ?",
+    )
+    .bind(delivery_id)
+    .bind(webhook_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Delivery not found".to_string()))?;
+
+    let mut tx = state.db.begin().await?;
+    let replayed = webhooks::replay(&mut tx, &delivery).await?;
+
+    let org_id = projects::org_id(&mut tx, project_id).await?;
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id,
+            actor: &user.wallet_address,
+            action: "project.webhook.replay",
+            target_type: "webhook_delivery",
+            target_id: Some(delivery_id.to_string()),
+            before: None,
+            after: Some(serde_json::json!({ "replayed_as": replayed.id })),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    webhooks::spawn_delivery(state.db.clone(), replayed.id);
+
+    Ok(respond::ok("Webhook delivery queued for replay", replayed))
+}
+
+#[get("/{id}/triggers")]
+async fn get_project_triggers_handler(
+    user: AuthUser,
+    path: web::Path<i64>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let project_id = path.into_inner();
+
+    if !can_manage_project(&user.wallet_address, project_id, &state.db).await? {
+        return Err(ApiError::Forbidden);
+    }
+
+    let triggers = db::query_as::<WebhookTrigger>(
+        "SELECT * FROM webhook_triggers WHERE project_id = ? ORDER BY id",
+    )
+    .bind(project_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(respond::ok("Project triggers retrieved", triggers))
+}
+
+#[post("/{id}/triggers")]
+async fn post_project_trigger_handler(
+    user: AuthUser,
+    meta: RequestMeta,
+    path: web::Path<i64>,
+    body: web::Json<PostTriggerBody>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let project_id = path.into_inner();
+
+    if !can_manage_project(&user.wallet_address, project_id, &state.db).await? {
+        return Err(ApiError::Forbidden);
+    }
+
+    if body.name.trim().is_empty() || body.prompt_template.trim().is_empty() {
+        return Err(ApiError::BadRequest(
+            "Name and prompt template are required".to_string(),
+        ));
+    }
+
+    if !get_models().iter().any(|m| m.name == body.model) {
+        return Err(ApiError::BadRequest("Invalid model".to_string()));
+    }
+
+    let callback_url = body
+        .callback_url
+        .as_ref()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+
+    if callback_url
+        .as_ref()
+        .is_some_and(|u| !(u.starts_with("https://") || u.starts_with("http://")))
+    {
+        return Err(ApiError::BadRequest(
+            "Callback URL must be an http(s) URL".to_string(),
+        ));
+    }
+
+    let secret = webhooks::generate_secret();
+
+    let mut tx = state.db.begin().await?;
+    let trigger = db::query_as::<WebhookTrigger>(
+        "INSERT INTO webhook_triggers (trigger_uid, project_id, name, secret, prompt_template, model, callback_url) VALUES (?, ?, ?, ?, ?, ?, ?) RETURNING *",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(project_id)
+    .bind(body.name.trim())
+    .bind(&secret)
+    .bind(&body.prompt_template)
+    .bind(&body.model)
+    .bind(&callback_url)
+    .fetch_one(&mut tx)
+    .await?;
+
+    let org_id = projects::org_id(&mut tx, project_id).await?;
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id,
+            actor: &user.wallet_address,
+            action: "project.trigger.create",
+            target_type: "webhook_trigger",
+            target_id: Some(trigger.trigger_uid.clone()),
+            before: None,
+            after: Some(serde_json::json!(trigger)),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    // The signing secret is only ever returned here
+    Ok(respond::ok(
+        "Trigger created",
+        serde_json::json!({ "trigger": trigger, "secret": secret }),
+    ))
+}
+
+#[delete("/{id}/triggers/{trigger_id}")]
+async fn delete_project_trigger_handler(
+    user: AuthUser,
+    meta: RequestMeta,
+    path: web::Path<(i64, i64)>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let (project_id, trigger_id) = path.into_inner();
+
+    if !can_manage_project(&user.wallet_address, project_id, &state.db).await? {
+        return Err(ApiError::Forbidden);
+    }
+
+    let mut tx = state.db.begin().await?;
+    let trigger = db::query_as::<WebhookTrigger>(
+        "DELETE FROM webhook_triggers WHERE id = ? AND project_id = ? RETURNING *",
+    )
+    .bind(trigger_id)
+    .bind(project_id)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Trigger not found".to_string()))?;
+
+    let org_id = projects::org_id(&mut tx, project_id).await?;
+    audit::record(
+        &mut tx,
+        &meta,
+        AuditEntry {
+            org_id,
+            actor: &user.wallet_address,
+            action: "project.trigger.delete",
+            target_type: "webhook_trigger",
+            target_id: Some(trigger.trigger_uid.clone()),
+            before: Some(serde_json::json!(trigger)),
+            after: None,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(respond::ok("Trigger deleted", trigger))
+}
+
+#[get("/{id}/triggers/{trigger_id}/invocations")]
+async fn get_project_trigger_invocations_handler(
+    user: AuthUser,
+    path: web::Path<(i64, i64)>,
+    state: web::Data<AppState>,
+) -> Result<impl Responder, ApiError> {
+    let (project_id, trigger_id) = path.into_inner();
+
+    if !can_manage_project(&user.wallet_address, project_id, &state.db).await? {
+        return Err(ApiError::Forbidden);
+    }
+
+    let invocations = db::query_as::<TriggerInvocation>(
+        "SELECT i.* FROM webhook_trigger_invocations i
+         JOIN webhook_triggers t ON t.id = i.trigger_id
+         WHERE i.trigger_id = ? AND t.project_id = ?
+         ORDER BY i.id DESC LIMIT 100",
+    )
+    .bind(trigger_id)
+    .bind(project_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(respond::ok("Trigger invocations retrieved", invocations))
+}
+
 pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(create_project_handler)
         .service(get_project_handler)
@@ -717,5 +1677,16 @@ pub fn routes(cfg: &mut web::ServiceConfig) {
         .service(get_project_telegram_info_handler)
         .service(put_project_telegram_handler)
         .service(get_project_discord_info_handler)
-        .service(put_project_discord_handler);
+        .service(put_project_discord_handler)
+        .service(get_project_prompt_variables_handler)
+        .service(put_project_prompt_variables_handler)
+        .service(get_project_webhooks_handler)
+        .service(post_project_webhook_handler)
+        .service(delete_project_webhook_handler)
+        .service(get_project_webhook_deliveries_handler)
+        .service(replay_project_webhook_delivery_handler)
+        .service(get_project_triggers_handler)
+        .service(post_project_trigger_handler)
+        .service(delete_project_trigger_handler)
+        .service(get_project_trigger_invocations_handler);
 }
@@ -1,5 +1,6 @@
+use crate::lib::db;
 use crate::lib::extractors::AuthUser;
-use crate::lib::{contracts, error::ApiError, respond, state::AppState};
+use crate::lib::{config, contracts, error::ApiError, fetch, respond, state::AppState};
 use actix_web::{Responder, delete, get, post, web};
 use alith::data::crypto::{DecodeRsaPublicKey, Pkcs1v15Encrypt, RsaPublicKey};
 use alith::lazai::{ProofRequest, U256};
@@ -76,22 +77,17 @@ struct ServerStats {
 
 async fn txn_count(address: &str) -> Result<u64, Box<dyn std::error::Error>> {
     let url = format!(
-        "https://hyperion-testnet-explorer-api.metisdevops.link/api/v2/addresses/{}/counters",
+        "{}/api/v2/addresses/{}/counters",
+        config::get().explorer_api_url.trim_end_matches('/'),
         address
     );
 
-    println!(
-        "Fetching transaction count for address: {} from URL: {}",
-        address, url
-    );
-
-    let client = reqwest::Client::new();
+    let client = fetch::trusted_client();
     let res = client.get(&url).send().await?;
 
-    println!("Response status: {}", res.status());
-
+    let status = res.status();
     let response_text = res.text().await?;
-    println!("Response body: {}", response_text);
+    tracing::debug!(address, status = status.as_u16(), "Fetched transaction count");
 
     let data: TransactionCounterResponse = serde_json::from_str(&response_text)?;
 
@@ -112,25 +108,19 @@ async fn get_index_handler(state: web::Data<AppState>) -> Result<impl Responder,
     // Get server transaction count
     match txn_count("0xcd4E9682172f67f2eA8cf0a9eCF199F3b78e57aD").await {
         Ok(count) => {
-            println!("Server transaction count: {}", count);
             response.server.transactions_count = count;
         }
-        Err(e) => println!("Warning: Failed to get server transaction count: {}", e),
+        Err(e) => tracing::warn!(error = %e, "Failed to get server transaction count"),
     }
 
     // Get organizations from database
     let orgs_query = "SELECT * FROM organizations";
-    match sqlx::query_as::<_, Organization>(orgs_query)
+    match db::query_as::<Organization>(orgs_query)
         .fetch_all(&state.db)
         .await
     {
         Ok(organizations) => {
-            println!("Found {} organizations", organizations.len());
             for org in organizations {
-                println!(
-                    "Processing organization: {} at address: {}",
-                    org.name, org.address
-                );
                 let transactions_count = txn_count(&org.address).await.unwrap_or(0);
 
                 response.contracts.push(ContractStats {
@@ -140,22 +130,17 @@ async fn get_index_handler(state: web::Data<AppState>) -> Result<impl Responder,
                 });
             }
         }
-        Err(e) => println!("Warning: Failed to fetch organizations: {}", e),
+        Err(e) => tracing::warn!(error = %e, "Failed to fetch organizations"),
     }
 
     // Get products from database
     let products_query = "SELECT * FROM products";
-    match sqlx::query_as::<_, Product>(products_query)
+    match db::query_as::<Product>(products_query)
         .fetch_all(&state.db)
         .await
     {
         Ok(products) => {
-            println!("Found {} products", products.len());
             for product in products {
-                println!(
-                    "Processing product: {} at address: {}",
-                    product.name, product.address
-                );
                 let transactions_count = txn_count(&product.address).await.unwrap_or(0);
 
                 response.contracts.push(ContractStats {
@@ -165,7 +150,7 @@ async fn get_index_handler(state: web::Data<AppState>) -> Result<impl Responder,
                 });
             }
         }
-        Err(e) => println!("Warning: Failed to fetch products: {}", e),
+        Err(e) => tracing::warn!(error = %e, "Failed to fetch products"),
     }
 
     // Add creator identity NFT contract (from orchestrator)
@@ -176,11 +161,6 @@ async fn get_index_handler(state: web::Data<AppState>) -> Result<impl Responder,
                 Ok(call) => match call.call().await {
                     Ok(creator_identity_address) => {
                         let creator_identity_addr = format!("{:#x}", creator_identity_address);
-                        println!(
-                            "Processing creator identity NFT at address: {}",
-                            creator_identity_addr
-                        );
-
                         let transactions_count =
                             txn_count(&creator_identity_addr).await.unwrap_or(0);
 
@@ -190,21 +170,13 @@ async fn get_index_handler(state: web::Data<AppState>) -> Result<impl Responder,
                             transactions_count,
                         });
                     }
-                    Err(e) => println!("Warning: Failed to get creator identity address: {}", e),
+                    Err(e) => tracing::warn!(error = %e, "Failed to get creator identity address"),
                 },
-                Err(e) => println!(
-                    "Warning: Failed to create creator identity method call: {}",
-                    e
-                ),
+                Err(e) => tracing::warn!(error = %e, "Failed to create creator identity method call"),
             }
 
             // Add orchestrator contract itself
             let orchestrator_address = format!("{:#x}", orchestrator_contract.address());
-            println!(
-                "Processing orchestrator at address: {}",
-                orchestrator_address
-            );
-
             let transactions_count = txn_count(&orchestrator_address).await.unwrap_or(0);
 
             response.contracts.push(ContractStats {
@@ -213,13 +185,11 @@ async fn get_index_handler(state: web::Data<AppState>) -> Result<impl Responder,
                 transactions_count,
             });
         }
-        Err(e) => println!("Warning: Failed to get orchestrator contract: {}", e),
+        Err(e) => tracing::warn!(error = %e, "Failed to get orchestrator contract"),
     }
 
     // Add tUSDT contract
     let tusdt_address = "0x62500ed734585c9f1f63f45a18fd81618ef5abe5";
-    println!("Processing tUSDT contract at address: {}", tusdt_address);
-
     let transactions_count = txn_count(tusdt_address).await.unwrap_or(0);
 
     response.contracts.push(ContractStats {
@@ -235,10 +205,10 @@ async fn get_index_handler(state: web::Data<AppState>) -> Result<impl Responder,
         .map(|c| c.transactions_count)
         .sum::<u64>();
 
-    println!(
-        "Final response: {} contracts, contracts transactions: {}",
-        response.contracts.len(),
-        response.transaction_count_contracts,
+    tracing::debug!(
+        contracts = response.contracts.len(),
+        transactions = response.transaction_count_contracts,
+        "Collected contract stats"
     );
 
     Ok(respond::ok("Statistics retrieved successfully", response))
@@ -3,7 +3,7 @@ use alith::tee::marlin::{AttestationRequest, MarlinClient};
 use ethers::signers::LocalWallet;
 use serde::Serialize;
 
-use crate::lib::{error::ApiError, respond};
+use crate::lib::{config, error::ApiError, respond};
 
 #[derive(Serialize)]
 struct TeeInfo {
@@ -42,7 +42,11 @@ async fn get_attest_handler() -> Result<impl Responder, ApiError> {
 
 #[get("/pub-key")]
 async fn get_pub_key_handler() -> Result<impl Responder, ApiError> {
-    let tee_pvt_key = std::env::var("MOCK_TEE_PVT_KEY").unwrap_or_default();
+    let tee_pvt_key = config::get()
+        .mock_tee_pvt_key
+        .as_ref()
+        .map(|key| key.expose())
+        .unwrap_or_default();
 
     let wallet: LocalWallet = tee_pvt_key
         .parse()
@@ -1,7 +1,18 @@
-use crate::lib::{error::ApiError, extractors::ApiCaller, respond, state::AppState};
-use actix_web::{HttpResponse, Responder, delete, get, patch, post, web};
+use crate::lib::db::{self, Db, conversations, orgs, projects};
+use crate::lib::llm::{self, LlmResponseParams, generate_llm_response};
+use crate::lib::models::{get_model_by_id, get_models};
+use crate::lib::pagination::{self, Cursor};
+use crate::lib::retrieval::Citation;
+use crate::lib::{error::ApiError, extractors::ApiCaller, ratelimit, respond, state::AppState};
+use actix_web::middleware::from_fn;
+use actix_web::web::Bytes;
+use actix_web::{
+    CustomizeResponder, HttpRequest, HttpResponse, Responder, delete, get, patch, post, put, web,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use sqlx::FromRow;
+use tracing::Instrument;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -13,7 +24,11 @@ enum MessageSender {
 #[derive(Debug, Clone, FromRow, Serialize)]
 struct Conversation {
     id: i32,
+    project_id: Option<i64>,
+    end_user_id: Option<String>,
     title: String,
+    title_source: String,
+    summary: Option<String>,
     created_at: String,
     updated_at: String,
 }
@@ -21,43 +36,167 @@ struct Conversation {
 #[derive(Debug, Clone, FromRow, Serialize)]
 struct Message {
     id: i32,
+    parent_id: Option<i32>,
     message: String,
     sender: String,
+    usage_id: Option<i64>,
     created_at: String,
 }
 
+async fn caller_project_id(api_caller: &ApiCaller, db: &Db) -> Result<i64, ApiError> {
+    projects::id_by_uid(db, &api_caller.project_uid)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Project not found".into()))
+}
+
+/// Verifies the conversation belongs to the caller within their current project.
+async fn owned_conversation(
+    api_caller: &ApiCaller,
+    id: i32,
+    db: &Db,
+) -> Result<i32, ApiError> {
+    let project_id = caller_project_id(api_caller, db).await?;
+
+    db::query_scalar::<i32>(
+        "SELECT id FROM conversations WHERE id = ? AND wallet_address = ? AND project_id = ?",
+    )
+    .bind(id)
+    .bind(&api_caller.wallet_address)
+    .bind(project_id)
+    .fetch_one(db)
+    .await
+    .map_err(|_| ApiError::NotFound("Conversation not found".into()))
+}
+
+#[derive(Deserialize)]
+struct GetConversationsQuery {
+    end_user_id: Option<String>,
+    sort: Option<String>,
+    order: Option<String>,
+    limit: Option<i64>,
+    cursor: Option<String>,
+}
+
+/// Page size for list endpoints. Without `limit` or `cursor` everything is returned,
+/// as before pagination existed.
+fn page_limit(limit: Option<i64>, cursor: Option<&str>) -> Option<i64> {
+    match (limit, cursor) {
+        (None, None) => None,
+        (limit, _) => Some(pagination::limit(limit)),
+    }
+}
+
+/// Attaches the cursor for the next page, if any, as `X-Next-Cursor`.
+fn with_next_cursor<R: Responder>(
+    responder: R,
+    next_cursor: Option<String>,
+) -> CustomizeResponder<R> {
+    let responder = responder.customize();
+    match next_cursor {
+        Some(cursor) => responder.insert_header(("X-Next-Cursor", cursor)),
+        None => responder,
+    }
+}
+
 #[get("/conversations")]
 pub async fn get_conversations_handlers(
     api_caller: ApiCaller,
     state: web::Data<AppState>,
+    query: web::Query<GetConversationsQuery>,
 ) -> Result<impl Responder, ApiError> {
-    let conversations = sqlx::query_as::<_, Conversation>(
-        "SELECT id, title, created_at, updated_at FROM conversations WHERE wallet_address = ?",
-    )
-    .bind(&api_caller.wallet_address)
-    .fetch_all(&state.db)
-    .await
-    .map_err(|_| ApiError::Internal("DB error".into()))?;
+    let project_id = caller_project_id(&api_caller, &state.db).await?;
 
-    Ok(respond::ok(
-        "conversations fetched",
-        serde_json::json!(conversations),
+    let column = match query.sort.as_deref().unwrap_or("updated_at") {
+        "updated_at" => "updated_at",
+        "created_at" => "created_at",
+        "title" => "title",
+        _ => {
+            return Err(ApiError::BadRequest(
+                "sort must be 'updated_at', 'created_at' or 'title'".into(),
+            ));
+        }
+    };
+    let direction = pagination::direction(query.order.as_deref())?;
+    let comparison = if direction == "DESC" { "<" } else { ">" };
+    let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+    let limit = page_limit(query.limit, query.cursor.as_deref());
+
+    let sql = format!(
+        "SELECT id, project_id, end_user_id, title, title_source, summary, created_at, updated_at FROM conversations
+         WHERE wallet_address = ? AND project_id = ? AND (? IS NULL OR end_user_id = ?){}
+         ORDER BY COALESCE({column}, '') {direction}, id {direction}
+         LIMIT ?",
+        if cursor.is_some() {
+            format!(" AND (COALESCE({column}, ''), id) {comparison} (?, ?)")
+        } else {
+            String::new()
+        },
+    );
+
+    let mut db_query = db::query_as::<Conversation>(&sql)
+        .bind(&api_caller.wallet_address)
+        .bind(project_id)
+        .bind(&query.end_user_id)
+        .bind(&query.end_user_id);
+    if let Some(cursor) = &cursor {
+        db_query = db_query.bind(&cursor.key).bind(cursor.id);
+    }
+
+    // One extra row tells whether another page follows
+    let mut conversations = db_query
+        .bind(limit.map_or(i64::MAX, |l| l + 1))
+        .fetch_all(&state.db)
+        .await
+        .map_err(|_| ApiError::Internal("DB error".into()))?;
+
+    let next_cursor = match limit {
+        Some(limit) if conversations.len() as i64 > limit => {
+            conversations.truncate(limit as usize);
+            conversations.last().map(|c| {
+                Cursor {
+                    key: match column {
+                        "created_at" => c.created_at.clone(),
+                        "title" => c.title.clone(),
+                        _ => c.updated_at.clone(),
+                    },
+                    id: c.id as i64,
+                }
+                .encode()
+            })
+        }
+        _ => None,
+    };
+
+    Ok(with_next_cursor(
+        respond::ok("conversations fetched", serde_json::json!(conversations)),
+        next_cursor,
     ))
 }
 
+#[derive(Deserialize)]
+struct CreateConversationBody {
+    end_user_id: Option<String>,
+}
+
 #[post("/conversations")]
 pub async fn post_conversations_handlers(
     api_caller: ApiCaller,
     state: web::Data<AppState>,
+    body: Option<web::Json<CreateConversationBody>>,
 ) -> Result<impl Responder, ApiError> {
-    let conversation = sqlx::query_as::<_, Conversation>(
-        "INSERT INTO conversations (title, wallet_address) VALUES (?, ?) RETURNING id, title, created_at, updated_at"
+    let project_id = caller_project_id(&api_caller, &state.db).await?;
+    let end_user_id = body.and_then(|b| b.into_inner().end_user_id);
+
+    let conversation = db::query_as::<Conversation>(
+        "INSERT INTO conversations (title, wallet_address, project_id, end_user_id, updated_at) VALUES (?, ?, ?, ?, CURRENT_TIMESTAMP) RETURNING id, project_id, end_user_id, title, title_source, summary, created_at, updated_at"
     )
         .bind(&format!(
             "New Conversation {}",
             uuid::Uuid::new_v4().to_string()[0..8].to_string()
         ))
         .bind(&api_caller.wallet_address)
+        .bind(project_id)
+        .bind(&end_user_id)
         .fetch_one(&state.db)
         .await?;
 
@@ -78,6 +217,14 @@ struct CreateMessageBody {
     sender: MessageSender,
 }
 
+#[derive(Deserialize)]
+struct SendMessageBody {
+    message: String,
+    model: Option<String>,
+    temperature: Option<f32>,
+    stream: Option<bool>,
+}
+
 #[patch("/conversations/{id}")]
 pub async fn patch_conversations_handlers(
     api_caller: ApiCaller,
@@ -85,13 +232,12 @@ pub async fn patch_conversations_handlers(
     path: web::Path<i32>,
     web::Json(payload): web::Json<PatchConversationBody>,
 ) -> Result<impl Responder, ApiError> {
-    let id = path.into_inner();
-    let conversation = sqlx::query_as::<_, Conversation>(
-        "UPDATE conversations SET title = ? WHERE id = ? AND wallet_address = ? RETURNING id, title, created_at, updated_at"
+    let id = owned_conversation(&api_caller, path.into_inner(), &state.db).await?;
+    let conversation = db::query_as::<Conversation>(
+        "UPDATE conversations SET title = ?, title_source = 'user', updated_at = CURRENT_TIMESTAMP WHERE id = ? RETURNING id, project_id, end_user_id, title, title_source, summary, created_at, updated_at"
     )
         .bind(&payload.title)
         .bind(id)
-        .bind(&api_caller.wallet_address)
         .fetch_one(&state.db)
         .await?;
 
@@ -107,15 +253,13 @@ pub async fn get_conversation_handler(
     state: web::Data<AppState>,
     path: web::Path<i32>,
 ) -> Result<impl Responder, ApiError> {
-    let id = path.into_inner();
-    let conversation = sqlx::query_as::<_, Conversation>(
-        "SELECT id, title, created_at, updated_at FROM conversations WHERE id = ? AND wallet_address = ?",
+    let id = owned_conversation(&api_caller, path.into_inner(), &state.db).await?;
+    let conversation = db::query_as::<Conversation>(
+        "SELECT id, project_id, end_user_id, title, title_source, summary, created_at, updated_at FROM conversations WHERE id = ?",
     )
     .bind(id)
-    .bind(&api_caller.wallet_address)
     .fetch_one(&state.db)
-    .await
-    .map_err(|_| ApiError::NotFound("Conversation not found".into()))?;
+    .await?;
 
     Ok(respond::ok(
         "conversation fetched",
@@ -129,10 +273,9 @@ pub async fn delete_conversation_handler(
     state: web::Data<AppState>,
     path: web::Path<i32>,
 ) -> Result<impl Responder, ApiError> {
-    let id = path.into_inner();
-    let result = sqlx::query("DELETE FROM conversations WHERE id = ? AND wallet_address = ?")
+    let id = owned_conversation(&api_caller, path.into_inner(), &state.db).await?;
+    let result = db::query("DELETE FROM conversations WHERE id = ?")
         .bind(id)
-        .bind(&api_caller.wallet_address)
         .execute(&state.db)
         .await?;
 
@@ -146,32 +289,264 @@ pub async fn delete_conversation_handler(
     ))
 }
 
+#[derive(Deserialize)]
+struct GetMessagesQuery {
+    branch: Option<String>,
+    order: Option<String>,
+    limit: Option<i64>,
+    cursor: Option<String>,
+}
+
 #[get("/conversations/{id}/messages")]
 pub async fn get_conversation_messages_handler(
     api_caller: ApiCaller,
     state: web::Data<AppState>,
     path: web::Path<i32>,
+    query: web::Query<GetMessagesQuery>,
 ) -> Result<impl Responder, ApiError> {
-    let id = path.into_inner();
-    // First verify the conversation belongs to this user
-    let _conversation = sqlx::query_scalar::<_, i32>(
-        "SELECT id FROM conversations WHERE id = ? AND wallet_address = ?",
+    let id = owned_conversation(&api_caller, path.into_inner(), &state.db).await?;
+
+    // Oldest first, the order a transcript is read in
+    let direction = pagination::direction(Some(query.order.as_deref().unwrap_or("asc")))?;
+    let comparison = if direction == "DESC" { "<" } else { ">" };
+    let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+    let limit = page_limit(query.limit, query.cursor.as_deref());
+
+    // Only the selected branch unless the whole tree is asked for
+    let branch_filter = match query.branch.as_deref().unwrap_or("active") {
+        "active" => {
+            " AND id IN (
+                WITH RECURSIVE branch(id) AS (
+                    SELECT active_leaf_id FROM conversations WHERE id = ?
+                    UNION ALL
+                    SELECT m.parent_id FROM agent_preview_messages m JOIN branch b ON m.id = b.id
+                    WHERE m.parent_id IS NOT NULL
+                )
+                SELECT id FROM branch
+            )"
+        }
+        "all" => "",
+        _ => {
+            return Err(ApiError::BadRequest(
+                "branch must be 'active' or 'all'".into(),
+            ));
+        }
+    };
+
+    let sql = format!(
+        "SELECT id, parent_id, message, sender, usage_id, created_at FROM agent_preview_messages
+         WHERE conversation_id = ?{branch_filter}{}
+         ORDER BY id {direction}
+         LIMIT ?",
+        if cursor.is_some() {
+            format!(" AND id {comparison} ?")
+        } else {
+            String::new()
+        },
+    );
+
+    let mut db_query = db::query_as::<Message>(&sql).bind(id);
+    if !branch_filter.is_empty() {
+        db_query = db_query.bind(id);
+    }
+    if let Some(cursor) = &cursor {
+        db_query = db_query.bind(cursor.id);
+    }
+
+    let mut messages = db_query
+        .bind(limit.map_or(i64::MAX, |l| l + 1))
+        .fetch_all(&state.db)
+        .await
+        .map_err(|_| ApiError::Internal("DB error".into()))?;
+
+    let next_cursor = match limit {
+        Some(limit) if messages.len() as i64 > limit => {
+            messages.truncate(limit as usize);
+            messages.last().map(|m| {
+                Cursor {
+                    key: String::new(),
+                    id: m.id as i64,
+                }
+                .encode()
+            })
+        }
+        _ => None,
+    };
+
+    Ok(with_next_cursor(
+        respond::ok("messages fetched", web::Json(messages)),
+        next_cursor,
+    ))
+}
+
+#[derive(Deserialize)]
+struct SearchMessagesQuery {
+    q: String,
+    limit: Option<i64>,
+    cursor: Option<String>,
+}
+
+#[get("/conversations/search")]
+pub async fn search_conversation_messages_handler(
+    api_caller: ApiCaller,
+    state: web::Data<AppState>,
+    query: web::Query<SearchMessagesQuery>,
+) -> Result<impl Responder, ApiError> {
+    let project_id = caller_project_id(&api_caller, &state.db).await?;
+
+    if query.q.trim().is_empty() {
+        return Err(ApiError::BadRequest("Search query cannot be empty".into()));
+    }
+
+    let limit = pagination::limit(query.limit);
+    let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+
+    let mut results = conversations::search_messages(
+        &state.db,
+        project_id,
+        &api_caller.wallet_address,
+        &query.q,
+        cursor.as_ref().map(|c| c.id),
+        limit + 1,
+    )
+    .await?;
+
+    let next_cursor = if results.len() as i64 > limit {
+        results.truncate(limit as usize);
+        results.last().map(|r| {
+            Cursor {
+                key: String::new(),
+                id: r.id as i64,
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+
+    Ok(with_next_cursor(
+        respond::ok("messages searched", serde_json::json!(results)),
+        next_cursor,
+    ))
+}
+
+#[derive(Deserialize)]
+struct ExportConversationQuery {
+    format: Option<String>,
+}
+
+#[get("/conversations/{id}/export")]
+pub async fn export_conversation_handler(
+    api_caller: ApiCaller,
+    state: web::Data<AppState>,
+    path: web::Path<i32>,
+    query: web::Query<ExportConversationQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let id = owned_conversation(&api_caller, path.into_inner(), &state.db).await?;
+
+    let conversation = db::query_as::<Conversation>(
+        "SELECT id, project_id, end_user_id, title, title_source, summary, created_at, updated_at FROM conversations WHERE id = ?",
     )
     .bind(id)
-    .bind(&api_caller.wallet_address)
     .fetch_one(&state.db)
-    .await
-    .map_err(|_| ApiError::NotFound("Conversation not found".into()))?;
+    .await?;
 
-    let messages = sqlx::query_as::<_, Message>(
-        "SELECT id, message, sender, created_at FROM agent_preview_messages WHERE conversation_id = ?",
+    let messages = branch_to(active_leaf(id, &state.db).await?, &state.db).await?;
+
+    let attachment = |ext: &str| {
+        (
+            "Content-Disposition",
+            format!("attachment; filename=\"conversation-{}.{}\"", id, ext),
+        )
+    };
+
+    match query.format.as_deref().unwrap_or("json") {
+        "json" => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .insert_header(attachment("json"))
+            .body(
+                json!({ "conversation": conversation, "messages": messages }).to_string(),
+            )),
+        "markdown" => {
+            let mut out = format!("# {}\n\n", conversation.title);
+            for m in &messages {
+                out.push_str(&format!(
+                    "**{}** · {}\n\n{}\n\n",
+                    if m.sender == "ai" { "Assistant" } else { "User" },
+                    m.created_at,
+                    m.message
+                ));
+            }
+
+            Ok(HttpResponse::Ok()
+                .content_type("text/markdown; charset=utf-8")
+                .insert_header(attachment("md"))
+                .body(out))
+        }
+        // OpenAI chat format, one conversation per line
+        "jsonl" => {
+            let line = json!({
+                "messages": messages
+                    .iter()
+                    .map(|m| json!({
+                        "role": if m.sender == "ai" { "assistant" } else { "user" },
+                        "content": m.message,
+                    }))
+                    .collect::<Vec<_>>()
+            });
+
+            Ok(HttpResponse::Ok()
+                .content_type("application/x-ndjson")
+                .insert_header(attachment("jsonl"))
+                .body(format!("{}\n", line)))
+        }
+        _ => Err(ApiError::BadRequest(
+            "format must be 'json', 'markdown' or 'jsonl'".into(),
+        )),
+    }
+}
+
+async fn active_leaf(conversation_id: i32, db: &Db) -> Result<Option<i32>, sqlx::Error> {
+    db::query_scalar("SELECT active_leaf_id FROM conversations WHERE id = ?")
+        .bind(conversation_id)
+        .fetch_one(db)
+        .await
+}
+
+/// Messages from the root of the tree down to `leaf_id`, oldest first.
+async fn branch_to(leaf_id: Option<i32>, db: &Db) -> Result<Vec<Message>, sqlx::Error> {
+    let Some(leaf_id) = leaf_id else {
+        return Ok(Vec::new());
+    };
+
+    db::query_as::<Message>(
+        "WITH RECURSIVE branch(id) AS (
+            SELECT CAST(? AS INTEGER)
+            UNION ALL
+            SELECT m.parent_id FROM agent_preview_messages m JOIN branch b ON m.id = b.id
+            WHERE m.parent_id IS NOT NULL
+         )
+         SELECT id, parent_id, message, sender, usage_id, created_at FROM agent_preview_messages
+         WHERE id IN (SELECT id FROM branch) ORDER BY id",
     )
-    .bind(id)
-    .fetch_all(&state.db)
+    .bind(leaf_id)
+    .fetch_all(db)
     .await
-    .map_err(|_| ApiError::Internal("DB error".into()))?;
+}
 
-    Ok(respond::ok("messages fetched", web::Json(messages)))
+async fn conversation_message(
+    conversation_id: i32,
+    message_id: i32,
+    db: &Db,
+) -> Result<Message, ApiError> {
+    db::query_as::<Message>(
+        "SELECT id, parent_id, message, sender, usage_id, created_at FROM agent_preview_messages WHERE id = ? AND conversation_id = ?",
+    )
+    .bind(message_id)
+    .bind(conversation_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Message not found".into()))
 }
 
 #[post("/conversations/{id}/messages")]
@@ -181,38 +556,685 @@ pub async fn post_conversation_messages_handler(
     path: web::Path<i32>,
     web::Json(payload): web::Json<CreateMessageBody>,
 ) -> Result<impl Responder, ApiError> {
-    let id = path.into_inner();
-    // First verify the conversation belongs to this user
-    let _conversation = sqlx::query_scalar::<_, i32>(
-        "SELECT id FROM conversations WHERE id = ? AND wallet_address = ?",
-    )
-    .bind(id)
-    .bind(&api_caller.wallet_address)
-    .fetch_one(&state.db)
-    .await
-    .map_err(|_| ApiError::NotFound("Conversation not found".into()))?;
+    let id = owned_conversation(&api_caller, path.into_inner(), &state.db).await?;
 
-    let message = sqlx::query_as::<_, Message>(
-        "INSERT INTO agent_preview_messages (conversation_id, message, sender) VALUES (?, ?, ?) RETURNING id, message, sender, created_at"
+    let mut tx = state.db.begin().await?;
+
+    // New messages continue the active branch
+    let message = db::query_as::<Message>(
+        "INSERT INTO agent_preview_messages (conversation_id, parent_id, message, sender)
+         VALUES (?, (SELECT active_leaf_id FROM conversations WHERE id = ?), ?, ?)
+         RETURNING id, parent_id, message, sender, usage_id, created_at"
     )
+        .bind(id)
         .bind(id)
         .bind(&payload.message)
         .bind(match payload.sender {
             MessageSender::User => "user",
             MessageSender::Ai => "ai",
         })
-        .fetch_one(&state.db)
+        .fetch_one(&mut tx)
+        .await?;
+
+    db::query("UPDATE conversations SET active_leaf_id = ? WHERE id = ?")
+        .bind(message.id)
+        .bind(id)
+        .execute(&mut tx)
         .await?;
 
+    tx.commit().await?;
+
     Ok(respond::ok("message created", web::Json(message)))
 }
 
+/// Model for agent replies: the one requested, else the project's default.
+async fn reply_model(
+    requested: Option<&str>,
+    project_uid: &str,
+    db: &Db,
+) -> Result<String, ApiError> {
+    if let Some(model) = requested {
+        return Ok(model.to_string());
+    }
+
+    let default_model_id: Option<i64> =
+        db::query_scalar("SELECT default_model_id FROM projects WHERE project_uid = ?")
+            .bind(project_uid)
+            .fetch_optional(db)
+            .await?
+            .flatten();
+
+    default_model_id
+        .and_then(|id| get_model_by_id(id as u64))
+        .map(|m| m.name)
+        .ok_or_else(|| {
+            ApiError::BadRequest("No model given and the project has no default model".into())
+        })
+}
+
+fn sse_event(event: &str, data: &serde_json::Value) -> Bytes {
+    Bytes::from(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+/// Messages kept verbatim in the prompt once a rolling summary covers the rest.
+const HISTORY_WINDOW: usize = 20;
+
+#[derive(Debug, Clone, FromRow)]
+struct UpkeepSettings {
+    org_id: i64,
+    project_id: i64,
+    wallet_address: String,
+    title_source: String,
+    summary: Option<String>,
+    summary_until_id: Option<i32>,
+    auto_title_enabled: bool,
+    summary_enabled: bool,
+}
+
+async fn conversation_upkeep_settings(
+    conversation_id: i32,
+    db: &Db,
+) -> Result<UpkeepSettings, sqlx::Error> {
+    db::query_as::<UpkeepSettings>(
+        "SELECT p.org_id, p.id AS project_id, c.wallet_address, c.title_source, c.summary, c.summary_until_id, p.auto_title_enabled, p.summary_enabled
+         FROM conversations c JOIN projects p ON p.id = c.project_id WHERE c.id = ?",
+    )
+    .bind(conversation_id)
+    .fetch_one(db)
+    .await
+}
+
+/// Cheapest active model the organization is enrolled in.
+async fn cheapest_model(org_id: i64, db: &Db) -> Result<Option<String>, sqlx::Error> {
+    let enrolled = orgs::enrolled_model_ids(db, org_id).await?;
+
+    Ok(get_models()
+        .into_iter()
+        .filter(|m| m.is_active && enrolled.contains(&m.id))
+        .min_by_key(|m| m.price_per_call)
+        .map(|m| m.name))
+}
+
+/// Folds into the summary once this many messages have left the history window,
+/// rather than after every reply.
+const SUMMARY_BATCH: usize = HISTORY_WINDOW / 2;
+
+fn transcript(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .map(|m| {
+            format!(
+                "{}: {}",
+                if m.sender == "ai" { "Assistant" } else { "User" },
+                m.message
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+async fn generate_title(
+    state: &AppState,
+    model: &str,
+    conversation_id: i32,
+    branch: &[Message],
+    settings: &UpkeepSettings,
+) -> Result<(), ApiError> {
+    let title = llm::complete_bare(
+        state,
+        settings.org_id,
+        settings.project_id,
+        Some(settings.wallet_address.as_str()),
+        model,
+        &format!(
+            "Write a title of at most six words for the conversation below. Reply with the title only, without quotes.\n\n{}",
+            transcript(&branch[..branch.len().min(2)])
+        ),
+    )
+    .await?;
+
+    let title: String = title
+        .trim()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_matches(|c: char| c == '"' || c == '\'' || c.is_whitespace())
+        .chars()
+        .take(80)
+        .collect();
+
+    if title.is_empty() {
+        return Ok(());
+    }
+
+    // A title set by the user in the meantime wins
+    db::query(
+        "UPDATE conversations SET title = ?, title_source = 'generated' WHERE id = ? AND title_source = 'default'",
+    )
+    .bind(&title)
+    .bind(conversation_id)
+    .execute(&state.db)
+    .await?;
+
+    Ok(())
+}
+
+/// Folds messages that fell out of the history window into the rolling summary,
+/// once at least [`SUMMARY_BATCH`] of them have piled up.
+async fn update_summary(
+    state: &AppState,
+    model: &str,
+    conversation_id: i32,
+    branch: &[Message],
+    settings: &UpkeepSettings,
+) -> Result<(), ApiError> {
+    if branch.len() <= HISTORY_WINDOW {
+        return Ok(());
+    }
+
+    let cutoff = branch.len() - HISTORY_WINDOW;
+
+    // The existing summary only carries over if it covers this branch
+    let (previous, start) = match settings
+        .summary_until_id
+        .and_then(|id| branch.iter().position(|m| m.id == id))
+    {
+        Some(position) => (settings.summary.clone(), position + 1),
+        None => (None, 0),
+    };
+
+    if cutoff.saturating_sub(start) < SUMMARY_BATCH {
+        return Ok(());
+    }
+
+    let prompt = match previous {
+        Some(previous) => format!(
+            "Update the summary of a conversation with the new messages below. Keep names, facts, decisions and open questions. Reply with the updated summary only.\n\nSummary so far:\n{}\n\nNew messages:\n{}",
+            previous,
+            transcript(&branch[start..cutoff])
+        ),
+        None => format!(
+            "Summarise the conversation below. Keep names, facts, decisions and open questions. Reply with the summary only.\n\n{}",
+            transcript(&branch[start..cutoff])
+        ),
+    };
+
+    let summary = llm::complete_bare(
+        state,
+        settings.org_id,
+        settings.project_id,
+        Some(settings.wallet_address.as_str()),
+        model,
+        &prompt,
+    )
+    .await?;
+    let summary = summary.trim();
+
+    db::query("UPDATE conversations SET summary = ?, summary_until_id = ? WHERE id = ?")
+        .bind(summary)
+        .bind(branch[cutoff - 1].id)
+        .bind(conversation_id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(())
+}
+
+/// Titles new conversations and keeps the rolling summary current, when the project
+/// has them enabled. Runs after a reply has been stored.
+async fn conversation_upkeep(
+    state: web::Data<AppState>,
+    conversation_id: i32,
+    leaf_id: i32,
+) {
+    let result: Result<(), ApiError> = async {
+        let settings = conversation_upkeep_settings(conversation_id, &state.db).await?;
+        let wants_title = settings.auto_title_enabled && settings.title_source == "default";
+
+        if !wants_title && !settings.summary_enabled {
+            return Ok(());
+        }
+
+        let Some(model) = cheapest_model(settings.org_id, &state.db).await? else {
+            return Ok(());
+        };
+
+        let branch = branch_to(Some(leaf_id), &state.db).await?;
+
+        if wants_title {
+            generate_title(&state, &model, conversation_id, &branch, &settings).await?;
+        }
+
+        if settings.summary_enabled {
+            update_summary(&state, &model, conversation_id, &branch, &settings).await?;
+        }
+
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        tracing::error!(conversation_id, error = %e, "Conversation upkeep failed");
+    }
+}
+
+/// Where a new agent reply goes in the message tree.
+struct ReplyRequest {
+    conversation_id: i32,
+    /// Message the turn builds on; the model sees the branch ending here.
+    parent_id: Option<i32>,
+    /// New user message to store ahead of the reply, absent when regenerating.
+    user_message: Option<String>,
+    model: Option<String>,
+    temperature: Option<f32>,
+}
+
+/// Runs the agent on the branch ending at the request's parent, then stores the new
+/// messages and moves the active branch to them in one transaction, so a failed
+/// generation leaves the conversation untouched.
+async fn generate_reply(
+    api_caller: &ApiCaller,
+    state: &web::Data<AppState>,
+    request: &ReplyRequest,
+) -> Result<(Option<Message>, Message, serde_json::Value, Vec<Citation>), ApiError> {
+    let model = reply_model(request.model.as_deref(), &api_caller.project_uid, &state.db).await?;
+
+    let history = branch_to(request.parent_id, &state.db).await?;
+    let upkeep = conversation_upkeep_settings(request.conversation_id, &state.db).await?;
+
+    // A summary stands in for the part of the branch it covers
+    let mut messages: Vec<serde_json::Value> = Vec::new();
+    let mut recent = &history[..];
+    if let (true, Some(summary), Some(until_id)) =
+        (upkeep.summary_enabled, &upkeep.summary, upkeep.summary_until_id)
+    {
+        if let Some(position) = history.iter().position(|m| m.id == until_id) {
+            messages.push(json!({
+                "role": "system",
+                "content": format!("Summary of the earlier conversation:\n{}", summary),
+            }));
+            recent = &history[position + 1..];
+        }
+    }
+
+    messages.extend(recent.iter().map(|m| {
+        json!({
+            "role": if m.sender == "ai" { "assistant" } else { "user" },
+            "content": m.message,
+        })
+    }));
+    if let Some(user_message) = &request.user_message {
+        messages.push(json!({ "role": "user", "content": user_message }));
+    }
+
+    let params = LlmResponseParams {
+        model,
+        messages,
+        temperature: request.temperature.unwrap_or(0.7),
+        n: 1,
+        org_uid: api_caller.org_uid.clone(),
+        project_uid: api_caller.project_uid.clone(),
+        wallet_address: Some(api_caller.wallet_address.clone()),
+        metadata: serde_json::Map::new(),
+        invocation_id: None,
+        timeout: None,
+    };
+
+    let response = generate_llm_response(params, state).await?;
+
+    let reply = response
+        .choices
+        .first()
+        .and_then(|c| c.get("message"))
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    let mut tx = state.db.begin().await?;
+
+    let user_message = match &request.user_message {
+        Some(text) => Some(
+            db::query_as::<Message>(
+                "INSERT INTO agent_preview_messages (conversation_id, parent_id, message, sender, usage_id) VALUES (?, ?, ?, 'user', ?) RETURNING id, parent_id, message, sender, usage_id, created_at"
+            )
+            .bind(request.conversation_id)
+            .bind(request.parent_id)
+            .bind(text)
+            .bind(response.usage_id)
+            .fetch_one(&mut tx)
+            .await?,
+        ),
+        None => None,
+    };
+
+    let ai_message = db::query_as::<Message>(
+        "INSERT INTO agent_preview_messages (conversation_id, parent_id, message, sender, usage_id) VALUES (?, ?, ?, 'ai', ?) RETURNING id, parent_id, message, sender, usage_id, created_at"
+    )
+    .bind(request.conversation_id)
+    .bind(user_message.as_ref().map(|m| m.id).or(request.parent_id))
+    .bind(&reply)
+    .bind(response.usage_id)
+    .fetch_one(&mut tx)
+    .await?;
+
+    db::query("UPDATE conversations SET active_leaf_id = ? WHERE id = ?")
+        .bind(ai_message.id)
+        .bind(request.conversation_id)
+        .execute(&mut tx)
+        .await?;
+
+    tx.commit().await?;
+
+    tokio::spawn(
+        conversation_upkeep(state.clone(), request.conversation_id, ai_message.id)
+            .in_current_span(),
+    );
+
+    let usage = json!({
+        "usage_id": response.usage_id,
+        "model": response.model,
+        "total_cost": response.total_cost,
+        "expense_till_now": response.current_expenditure,
+        "prompt_tokens": response.prompt_tokens,
+    });
+
+    Ok((user_message, ai_message, usage, response.citations))
+}
+
+/// Generates the reply and answers with JSON, or with server-sent events when streaming.
+///
+/// The agent answers in one piece, so streamed `delta` events are the finished
+/// reply relayed in word chunks, not tokens as they are generated.
+async fn reply_response(
+    req: &HttpRequest,
+    api_caller: ApiCaller,
+    state: web::Data<AppState>,
+    request: ReplyRequest,
+    stream: bool,
+) -> Result<HttpResponse, ApiError> {
+    if !stream {
+        let (user_message, ai_message, usage, citations) =
+            generate_reply(&api_caller, &state, &request).await?;
+
+        return Ok(HttpResponse::Ok().json(json!({
+            "success": true,
+            "message": "message sent",
+            "data": {
+                "user_message": user_message,
+                "ai_message": ai_message,
+                "usage": usage,
+                "citations": citations,
+            }
+        })));
+    }
+
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel::<Bytes>();
+    // The request counts against concurrency limits until the reply is done
+    let guard = ratelimit::ConcurrencyGuard::take(req);
+
+    tokio::spawn(async move {
+        let _guard = guard;
+        // Comment line so clients see the stream open while the agent runs
+        let _ = sender.send(Bytes::from_static(b": generating\n\n"));
+
+        match generate_reply(&api_caller, &state, &request).await {
+            Ok((user_message, ai_message, usage, citations)) => {
+                if let Some(user_message) = user_message {
+                    let _ = sender.send(sse_event("user_message", &json!(user_message)));
+                }
+
+                for chunk in ai_message.message.split_inclusive(' ') {
+                    let _ = sender.send(sse_event("delta", &json!({ "content": chunk })));
+                }
+
+                let _ = sender.send(sse_event(
+                    "done",
+                    &json!({ "ai_message": ai_message, "usage": usage, "citations": citations }),
+                ));
+            }
+            Err(e) => {
+                let _ = sender.send(sse_event("error", &json!({ "message": e.to_string() })));
+            }
+        }
+    }.in_current_span());
+
+    let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver
+            .recv()
+            .await
+            .map(|bytes| (Ok::<_, actix_web::Error>(bytes), receiver))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(stream))
+}
+
+#[post(
+    "/conversations/{id}/messages/send",
+    wrap = "from_fn(ratelimit::enforce)"
+)]
+pub async fn send_conversation_message_handler(
+    req: HttpRequest,
+    api_caller: ApiCaller,
+    state: web::Data<AppState>,
+    path: web::Path<i32>,
+    web::Json(payload): web::Json<SendMessageBody>,
+) -> Result<HttpResponse, ApiError> {
+    let id = owned_conversation(&api_caller, path.into_inner(), &state.db).await?;
+
+    if payload.message.trim().is_empty() {
+        return Err(ApiError::BadRequest("Message cannot be empty".into()));
+    }
+
+    let request = ReplyRequest {
+        conversation_id: id,
+        parent_id: active_leaf(id, &state.db).await?,
+        user_message: Some(payload.message),
+        model: payload.model,
+        temperature: payload.temperature,
+    };
+
+    reply_response(&req, api_caller, state, request, payload.stream.unwrap_or(false)).await
+}
+
+/// Sends an edited copy of a user message as a new branch beside the original.
+#[post(
+    "/conversations/{id}/messages/{message_id}/edit",
+    wrap = "from_fn(ratelimit::enforce)"
+)]
+pub async fn edit_conversation_message_handler(
+    req: HttpRequest,
+    api_caller: ApiCaller,
+    state: web::Data<AppState>,
+    path: web::Path<(i32, i32)>,
+    web::Json(payload): web::Json<SendMessageBody>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, message_id) = path.into_inner();
+    let id = owned_conversation(&api_caller, id, &state.db).await?;
+
+    let original = conversation_message(id, message_id, &state.db).await?;
+    if original.sender != "user" {
+        return Err(ApiError::BadRequest("Only user messages can be edited".into()));
+    }
+
+    if payload.message.trim().is_empty() {
+        return Err(ApiError::BadRequest("Message cannot be empty".into()));
+    }
+
+    let request = ReplyRequest {
+        conversation_id: id,
+        parent_id: original.parent_id,
+        user_message: Some(payload.message),
+        model: payload.model,
+        temperature: payload.temperature,
+    };
+
+    reply_response(&req, api_caller, state, request, payload.stream.unwrap_or(false)).await
+}
+
+#[derive(Deserialize)]
+struct RegenerateMessageBody {
+    model: Option<String>,
+    temperature: Option<f32>,
+    stream: Option<bool>,
+}
+
+/// Generates another reply to the same user message, kept as a sibling of the original.
+#[post(
+    "/conversations/{id}/messages/{message_id}/regenerate",
+    wrap = "from_fn(ratelimit::enforce)"
+)]
+pub async fn regenerate_conversation_message_handler(
+    req: HttpRequest,
+    api_caller: ApiCaller,
+    state: web::Data<AppState>,
+    path: web::Path<(i32, i32)>,
+    body: Option<web::Json<RegenerateMessageBody>>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, message_id) = path.into_inner();
+    let id = owned_conversation(&api_caller, id, &state.db).await?;
+
+    let original = conversation_message(id, message_id, &state.db).await?;
+    if original.sender != "ai" {
+        return Err(ApiError::BadRequest("Only AI replies can be regenerated".into()));
+    }
+
+    let body = body.map(|b| b.into_inner());
+    let request = ReplyRequest {
+        conversation_id: id,
+        parent_id: original.parent_id,
+        user_message: None,
+        model: body.as_ref().and_then(|b| b.model.clone()),
+        temperature: body.as_ref().and_then(|b| b.temperature),
+    };
+
+    let stream = body.and_then(|b| b.stream).unwrap_or(false);
+    reply_response(&req, api_caller, state, request, stream).await
+}
+
+/// The end of the branch below `message_id` that follows the most recent reply at every step.
+async fn latest_descendant(message_id: i32, db: &Db) -> Result<i32, sqlx::Error> {
+    // Replies always have larger ids than their parents, so the deepest message is the largest.
+    // Ids are INTEGER on Postgres, so the bound id and MAX() are cast to keep them from widening.
+    db::query_scalar(
+        "WITH RECURSIVE descent(id) AS (
+            SELECT CAST(? AS INTEGER)
+            UNION ALL
+            SELECT (SELECT MAX(c.id) FROM agent_preview_messages c WHERE c.parent_id = d.id)
+            FROM descent d
+            WHERE EXISTS (SELECT 1 FROM agent_preview_messages c WHERE c.parent_id = d.id)
+         )
+         SELECT CAST(MAX(id) AS INTEGER) FROM descent",
+    )
+    .bind(message_id)
+    .fetch_one(db)
+    .await
+}
+
+#[derive(Deserialize)]
+struct PutActiveBranchBody {
+    message_id: i32,
+}
+
+/// Switches to the branch through `message_id`, following its most recent replies.
+#[put("/conversations/{id}/active-branch")]
+pub async fn put_active_branch_handler(
+    api_caller: ApiCaller,
+    state: web::Data<AppState>,
+    path: web::Path<i32>,
+    web::Json(payload): web::Json<PutActiveBranchBody>,
+) -> Result<impl Responder, ApiError> {
+    let id = owned_conversation(&api_caller, path.into_inner(), &state.db).await?;
+
+    conversation_message(id, payload.message_id, &state.db).await?;
+
+    let leaf_id = latest_descendant(payload.message_id, &state.db).await?;
+
+    db::query("UPDATE conversations SET active_leaf_id = ? WHERE id = ?")
+        .bind(leaf_id)
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+
+    let messages = branch_to(Some(leaf_id), &state.db).await?;
+
+    Ok(respond::ok("active branch updated", web::Json(messages)))
+}
+
 pub fn routes(cfg: &mut actix_web::web::ServiceConfig) {
     cfg.service(get_conversations_handlers)
+        .service(search_conversation_messages_handler)
         .service(post_conversations_handlers)
         .service(patch_conversations_handlers)
         .service(get_conversation_handler)
         .service(delete_conversation_handler)
         .service(get_conversation_messages_handler)
-        .service(post_conversation_messages_handler);
+        .service(export_conversation_handler)
+        .service(post_conversation_messages_handler)
+        .service(send_conversation_message_handler)
+        .service(edit_conversation_message_handler)
+        .service(regenerate_conversation_message_handler)
+        .service(put_active_branch_handler);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::db::{Backend, testing};
+
+    async fn message(db: &Db, conversation_id: i32, parent_id: Option<i32>, sender: &str) -> i32 {
+        db::query_scalar(
+            "INSERT INTO agent_preview_messages (conversation_id, parent_id, sender, message) VALUES (?, ?, ?, ?) RETURNING id",
+        )
+        .bind(conversation_id)
+        .bind(parent_id)
+        .bind(sender)
+        .bind(format!("{} message", sender))
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn walks_branches_on_both_backends() {
+        for backend in [Backend::Sqlite, Backend::Postgres] {
+            let db = testing::migrated(backend).await;
+            let project_id = testing::project(&db).await;
+            let conversation_id: i32 = db::query_scalar(
+                "INSERT INTO conversations (wallet_address, project_id) VALUES (?, ?) RETURNING id",
+            )
+            .bind(testing::OWNER)
+            .bind(project_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+            // question -> answer -> follow-up, then the answer regenerated and followed up
+            let question = message(&db, conversation_id, None, "user").await;
+            let answer = message(&db, conversation_id, Some(question), "ai").await;
+            let follow_up = message(&db, conversation_id, Some(answer), "user").await;
+            let regenerated = message(&db, conversation_id, Some(question), "ai").await;
+            let latest = message(&db, conversation_id, Some(regenerated), "user").await;
+
+            let ids = |messages: Vec<Message>| messages.iter().map(|m| m.id).collect::<Vec<_>>();
+            assert_eq!(
+                ids(branch_to(Some(follow_up), &db).await.unwrap()),
+                [question, answer, follow_up],
+                "{:?}",
+                backend
+            );
+            assert_eq!(
+                ids(branch_to(Some(latest), &db).await.unwrap()),
+                [question, regenerated, latest]
+            );
+            assert!(branch_to(None, &db).await.unwrap().is_empty());
+
+            assert_eq!(latest_descendant(question, &db).await.unwrap(), latest, "{:?}", backend);
+            assert_eq!(latest_descendant(answer, &db).await.unwrap(), follow_up);
+            assert_eq!(latest_descendant(latest, &db).await.unwrap(), latest);
+        }
+    }
 }
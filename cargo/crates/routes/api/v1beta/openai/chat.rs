@@ -1,6 +1,6 @@
 use crate::lib::extractors::ApiCaller;
 use crate::lib::state::AppState;
-use crate::lib::{error::ApiError, llm};
+use crate::lib::{error::ApiError, fetch, llm};
 use actix_web::{HttpResponse, Responder, post, web};
 use alith::{StructureTool, ToolError};
 use async_trait::async_trait;
@@ -16,6 +16,8 @@ pub struct GetChatCompletionsBody {
     pub messages: Vec<serde_json::Value>,
     pub n: Option<u32>,
     pub temperature: Option<f32>,
+    /// Values for `{{variable}}` placeholders in the project's prompt products.
+    pub metadata: Option<serde_json::Map<String, serde_json::Value>>,
 }
 
 #[post("/completions")]
@@ -28,14 +30,19 @@ async fn get_completions_handler(
     let temperature = body.temperature.unwrap_or(1.0);
     let n = body.n.unwrap_or(1);
     let messages = body.messages.clone();
+    let metadata = body.metadata.clone().unwrap_or_default();
 
     let params = llm::LlmResponseParams {
-        model: model.clone(),
+        model,
         messages,
         temperature,
         n,
         org_uid: api_caller.org_uid,
         project_uid: api_caller.project_uid,
+        wallet_address: Some(api_caller.wallet_address),
+        metadata,
+        invocation_id: None,
+        timeout: None,
     };
 
     let response = llm::generate_llm_response(params, &state).await?;
@@ -44,13 +51,14 @@ async fn get_completions_handler(
         "id": format!("chatcmpl-{}", uuid::Uuid::new_v4().to_string()),
         "object": "chat.completion",
         "created": chrono::Utc::now().timestamp(),
-        "model": model,
+        "model": response.model,
         "choices": response.choices,
         "usage": {
             "total_cost": response.total_cost,
             "expense_till_now": response.current_expenditure,
             "prompt_tokens": response.prompt_tokens,
-        }
+        },
+        "citations": response.citations,
     })))
 }
 
@@ -78,34 +86,36 @@ impl StructureTool for RpcTool {
     }
 
     async fn run_with_args<'a>(&'a self, input: Self::Input) -> Result<Self::Output, ToolError> {
-        let client = reqwest::Client::new();
+        let fetcher = fetch::client();
 
         let method = input.method.to_uppercase();
         let param_pos = input.param_position.unwrap_or_else(|| "body".to_string());
         let params = input.params.unwrap_or(json!({}));
 
-        let response = match method.as_str() {
-            "GET" => {
+        let req = match method.as_str() {
+            "GET" => fetcher.get(&input.url).map(|req| {
                 if param_pos == "query" {
-                    client.get(&input.url).query(&params).send().await
+                    req.query(&params)
                 } else {
-                    client.get(&input.url).send().await
+                    req
                 }
-            }
-            "POST" => {
+            }),
+            "POST" => fetcher.post(&input.url).map(|req| {
                 if param_pos == "query" {
-                    client.post(&input.url).query(&params).send().await
+                    req.query(&params)
                 } else {
-                    client.post(&input.url).json(&params).send().await
+                    req.json(&params)
                 }
-            }
+            }),
             _ => return Err(ToolError::Unknown("Unsupported HTTP method".to_string())),
-        };
+        }
+        .map_err(|e| ToolError::Unknown(e.to_string()))?;
 
-        let res = response.map_err(|e| ToolError::Unknown(format!("HTTP error: {e}")))?;
-        let json = res
-            .json::<serde_json::Value>()
+        let res = fetcher
+            .send(req, fetch::JSON_TYPES)
             .await
+            .map_err(|e| ToolError::Unknown(format!("HTTP error: {e}")))?;
+        let json = serde_json::from_slice::<serde_json::Value>(&res.body)
             .map_err(|e| ToolError::Unknown(format!("Invalid JSON: {e}")))?;
 
         Ok(json)
@@ -1,3 +1,5 @@
+use crate::lib::ratelimit;
+use actix_web::middleware::from_fn;
 use actix_web::web;
 
 pub mod chat;
@@ -6,6 +8,7 @@ pub mod models;
 pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/chat")
+            .wrap(from_fn(ratelimit::enforce))
             .configure(chat::routes)
         )
             .service(web::scope("/models").configure(models::routes));
@@ -1,3 +1,4 @@
+use crate::lib::db;
 use crate::lib::extractors::ApiCaller;
 use crate::lib::models;
 use crate::lib::state::AppState;
@@ -11,7 +12,7 @@ async fn get_models_handler(api_caller: ApiCaller, state: web::Data<AppState>) -
 
     // Get org_id from org_uid
     let org_id: i64 =
-        match sqlx::query_scalar("SELECT id FROM organizations WHERE organization_uid = ?")
+        match db::query_scalar("SELECT id FROM organizations WHERE organization_uid = ?")
             .bind(&api_caller.org_uid)
             .fetch_one(&state.db)
             .await
@@ -22,7 +23,7 @@ async fn get_models_handler(api_caller: ApiCaller, state: web::Data<AppState>) -
             }
         };
 
-    let enabled_model_ids = sqlx::query_scalar(
+    let enabled_model_ids = db::query_scalar(
         r#"
         SELECT model_id FROM org_model_enrollments WHERE org_id = ?
         "#,
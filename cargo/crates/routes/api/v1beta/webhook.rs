@@ -1,14 +1,267 @@
-use crate::lib::{error::ApiError, extractors::ApiCaller, respond, state::AppState};
-use actix_web::{HttpResponse, Responder, delete, get, patch, post, web};
-use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use crate::lib::db::{self, Db, projects};
+use crate::lib::llm::{LlmResponseParams, generate_llm_response};
+use crate::lib::webhooks::{self, TriggerInvocation, WebhookTrigger};
+use crate::lib::{error::ApiError, fetch, state::AppState, template};
+use actix_web::{HttpRequest, HttpResponse, post, web};
+use serde_json::{Value, json};
+use std::time::Duration;
+use tracing::Instrument;
+
+const SIGNATURE_TOLERANCE_SECS: i64 = 300;
+
+fn header<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    req.headers().get(name).and_then(|h| h.to_str().ok())
+}
+
+/// Runs the trigger's agent on the payload and records the outcome on the invocation.
+async fn run_trigger(
+    state: &AppState,
+    trigger: &WebhookTrigger,
+    invocation_id: i64,
+    org_uid: String,
+    project_uid: String,
+    payload: &Value,
+) -> Result<Value, ApiError> {
+    let params = LlmResponseParams {
+        model: trigger.model.clone(),
+        messages: vec![json!({
+            "role": "user",
+            "content": template::render(&trigger.prompt_template, payload)
+        })],
+        temperature: 0.7,
+        n: 1,
+        org_uid,
+        project_uid,
+        wallet_address: None,
+        metadata: payload.as_object().cloned().unwrap_or_default(),
+        invocation_id: Some(invocation_id),
+        timeout: Some(webhooks::TRIGGER_TIMEOUT),
+    };
+
+    let result = generate_llm_response(params, state).await;
+    let usage_id = result.as_ref().ok().map(|response| response.usage_id);
+    let result = result.map(|response| {
+        json!({
+            "invocation_id": invocation_id,
+            "trigger_uid": trigger.trigger_uid,
+            "model": response.model,
+            "choices": response.choices,
+            "usage": {
+                "total_cost": response.total_cost,
+                "expense_till_now": response.current_expenditure,
+                "prompt_tokens": response.prompt_tokens,
+            },
+            "citations": response.citations,
+        })
+    });
+
+    let (status, response, error) = match &result {
+        Ok(value) => ("succeeded", Some(value.to_string()), None),
+        Err(e) => ("failed", None, Some(e.to_string())),
+    };
+
+    db::query(
+        "UPDATE webhook_trigger_invocations SET status = ?, response = ?, error = ?, usage_id = ?, completed_at = CURRENT_TIMESTAMP WHERE id = ?",
+    )
+    .bind(status)
+    .bind(response)
+    .bind(error)
+    .bind(usage_id)
+    .bind(invocation_id)
+    .execute(&state.db)
+    .await?;
+
+    result
+}
+
+async fn post_callback(trigger: &WebhookTrigger, callback_url: &str, body: &Value) {
+    let body = body.to_string();
+    let timestamp = chrono::Utc::now().timestamp();
+    let signature = webhooks::sign(&trigger.secret, timestamp, &body);
+
+    let fetcher = fetch::client();
+    let result = async {
+        let req = fetcher
+            .post(callback_url)?
+            .timeout(Duration::from_secs(10))
+            .header("Content-Type", "application/json")
+            .header("Haithe-Trigger", &trigger.trigger_uid)
+            .header("Haithe-Signature", format!("t={},v1={}", timestamp, signature))
+            .body(body);
+        fetcher.send(req, &[]).await
+    }
+    .await;
+
+    if let Err(e) = result {
+        tracing::warn!(trigger_uid = %trigger.trigger_uid, error = %e, "Failed to post callback");
+    }
+}
+
+/// Claims the idempotency key for this call. Returns the earlier invocation instead
+/// when the key has already been used and is not eligible for a retry.
+async fn claim_invocation(
+    db: &Db,
+    trigger_id: i64,
+    idempotency_key: &str,
+) -> Result<Result<i64, TriggerInvocation>, ApiError> {
+    let now = chrono::Utc::now();
+    let inserted: Option<i64> = db::query_scalar(
+        "INSERT INTO webhook_trigger_invocations (trigger_id, idempotency_key, claimed_at) VALUES (?, ?, ?)
+         ON CONFLICT (trigger_id, idempotency_key) DO NOTHING RETURNING id",
+    )
+    .bind(trigger_id)
+    .bind(idempotency_key)
+    .bind(db::timestamp(now))
+    .fetch_optional(db)
+    .await?;
+
+    if let Some(id) = inserted {
+        return Ok(Ok(id));
+    }
+
+    // Failed invocations may be retried with the same key, and pending ones
+    // whose lease ran out were abandoned. Neither is rerun once charged.
+    let lease_cutoff = now - chrono::Duration::from_std(webhooks::INVOCATION_LEASE).unwrap();
+    let retried: Option<i64> = db::query_scalar(
+        "UPDATE webhook_trigger_invocations SET status = 'pending', error = NULL, completed_at = NULL, claimed_at = ?
+         WHERE trigger_id = ? AND idempotency_key = ? AND NOT charged
+         AND (status = 'failed' OR (status = 'pending' AND COALESCE(claimed_at, created_at) < ?))
+         RETURNING id",
+    )
+    .bind(db::timestamp(now))
+    .bind(trigger_id)
+    .bind(idempotency_key)
+    .bind(db::timestamp(lease_cutoff))
+    .fetch_optional(db)
+    .await?;
+
+    if let Some(id) = retried {
+        return Ok(Ok(id));
+    }
+
+    let existing = db::query_as::<TriggerInvocation>(
+        "SELECT * FROM webhook_trigger_invocations WHERE trigger_id = ? AND idempotency_key = ?",
+    )
+    .bind(trigger_id)
+    .bind(idempotency_key)
+    .fetch_one(db)
+    .await?;
+
+    Ok(Err(existing))
+}
 
 #[post("/{id}")]
 pub async fn call_webhook_handler(
-    api_caller: ApiCaller,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Bytes,
     state: web::Data<AppState>,
-) -> Result<impl Responder, ApiError> {
-    Ok(respond::ok("fetched", serde_json::json!([])))
+) -> Result<HttpResponse, ApiError> {
+    let trigger_uid = path.into_inner();
+
+    let trigger = db::query_as::<WebhookTrigger>(
+        "SELECT * FROM webhook_triggers WHERE trigger_uid = ? AND active = TRUE",
+    )
+    .bind(&trigger_uid)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Webhook not found".to_string()))?;
+
+    let Some(signature) = header(&req, "Haithe-Signature") else {
+        return Err(ApiError::Unauthorized);
+    };
+
+    if !webhooks::verify(&trigger.secret, signature, &body, SIGNATURE_TOLERANCE_SECS) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let payload: Value = serde_json::from_slice(&body)
+        .map_err(|_| ApiError::BadRequest("Body must be valid JSON".to_string()))?;
+
+    let (org_uid, project_uid) = projects::uids(&state.db, trigger.project_id).await?;
+
+    let idempotency_key = header(&req, "Idempotency-Key")
+        .map(|k| k.trim().to_string())
+        .filter(|k| !k.is_empty())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let invocation_id = match claim_invocation(&state.db, trigger.id, &idempotency_key).await? {
+        Ok(id) => id,
+        Err(existing) if existing.status == "succeeded" => {
+            let response = existing
+                .response
+                .as_deref()
+                .and_then(|r| serde_json::from_str::<Value>(r).ok())
+                .unwrap_or(Value::Null);
+
+            return Ok(HttpResponse::Ok()
+                .insert_header(("Idempotent-Replayed", "true"))
+                .json(json!({
+                    "success": true,
+                    "message": "Webhook already processed",
+                    "data": response,
+                })));
+        }
+        Err(existing) if existing.status == "pending" => {
+            return Err(ApiError::Conflict(format!(
+                "Invocation {} for this idempotency key is still in progress",
+                existing.id
+            )));
+        }
+        Err(existing) => {
+            return Err(ApiError::Conflict(format!(
+                "Invocation {} for this idempotency key failed after it was charged and is not retried, use a new key to run it again",
+                existing.id
+            )));
+        }
+    };
+
+    if let Some(callback_url) = trigger.callback_url.clone() {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let body = match run_trigger(
+                state.get_ref(),
+                &trigger,
+                invocation_id,
+                org_uid,
+                project_uid,
+                &payload,
+            )
+            .await
+            {
+                Ok(response) => response,
+                Err(e) => json!({
+                    "invocation_id": invocation_id,
+                    "trigger_uid": trigger.trigger_uid,
+                    "error": e.to_string(),
+                }),
+            };
+
+            post_callback(&trigger, &callback_url, &body).await;
+        }.in_current_span());
+
+        return Ok(HttpResponse::Accepted().json(json!({
+            "success": true,
+            "message": "Webhook accepted, the response will be posted to the callback URL",
+            "data": { "invocation_id": invocation_id },
+        })));
+    }
+
+    let response = run_trigger(
+        &state,
+        &trigger,
+        invocation_id,
+        org_uid,
+        project_uid,
+        &payload,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "message": "Webhook processed",
+        "data": response,
+    })))
 }
 
 pub fn routes(cfg: &mut actix_web::web::ServiceConfig) {
@@ -1,3 +1,4 @@
+use crate::lib::config;
 use chrono::{Duration, Utc};
 use ethers::prelude::*;
 use ethers::utils::keccak256;
@@ -50,16 +51,15 @@ pub fn generate_jwt(address: &str) -> String {
     jwt::encode(
         &jwt::Header::default(),
         &claims,
-        &jwt::EncodingKey::from_secret(std::env::var("JWT_SECRET").unwrap().as_ref()),
+        &jwt::EncodingKey::from_secret(config::get().jwt_secret.expose().as_ref()),
     )
     .expect("Failed to encode token")
 }
 
 pub fn decode_jwt(token: &str) -> Option<Claims> {
-    let secret = std::env::var("JWT_SECRET").unwrap();
     let token_data = jwt::decode::<Claims>(
         token,
-        &jwt::DecodingKey::from_secret(secret.as_ref()),
+        &jwt::DecodingKey::from_secret(config::get().jwt_secret.expose().as_ref()),
         &jwt::Validation::default(),
     )
     .ok()?;
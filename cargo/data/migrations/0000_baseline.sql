@@ -0,0 +1,278 @@
+-- SQLITE MIGRATION SCRIPT --
+CREATE TABLE
+    IF NOT EXISTS accounts (
+        wallet_address TEXT PRIMARY KEY,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        api_key_last_issued_at TIMESTAMP,
+        UNIQUE (wallet_address)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS sessions (
+        wallet_address TEXT PRIMARY KEY,
+        token TEXT,
+        ip TEXT,
+        user_agent TEXT,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE TABLE
+    IF NOT EXISTS organizations (
+        id INTEGER PRIMARY KEY,
+        organization_uid TEXT NOT NULL,
+        address TEXT NOT NULL,
+        orchestrator_idx INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        owner TEXT NOT NULL REFERENCES accounts (wallet_address) ON DELETE CASCADE,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        expenditure INTEGER NOT NULL DEFAULT 0 CHECK (expenditure >= 0),
+        UNIQUE (name),
+        UNIQUE (organization_uid),
+        UNIQUE (address)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS org_members (
+        org_id INTEGER NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+        wallet_address TEXT NOT NULL REFERENCES accounts (wallet_address) ON DELETE CASCADE,
+        role TEXT NOT NULL CHECK (role IN ('admin', 'member')),
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (org_id, wallet_address)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS projects (
+        id INTEGER PRIMARY KEY,
+        project_uid TEXT NOT NULL,
+        org_id INTEGER NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        search_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+        memory_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+        teloxide_token TEXT,
+        discord_token TEXT,
+        default_model_id INTEGER,
+        UNIQUE (name, org_id),
+        UNIQUE (project_uid)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS project_members (
+        project_id INTEGER NOT NULL REFERENCES projects (id) ON DELETE CASCADE,
+        wallet_address TEXT NOT NULL REFERENCES accounts (wallet_address) ON DELETE CASCADE,
+        role TEXT NOT NULL CHECK (role IN ('admin', 'developer', 'viewer')),
+        PRIMARY KEY (project_id, wallet_address)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS org_model_enrollments (
+        id UUID PRIMARY KEY,
+        org_id INTEGER NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+        model_id INTEGER NOT NULL,
+        enabled_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        UNIQUE (org_id, model_id)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS creators (
+        wallet_address TEXT PRIMARY KEY,
+        uri TEXT NOT NULL,
+        pvt_key_seed TEXT NOT NULL,
+        pub_key TEXT NOT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (wallet_address) REFERENCES accounts (wallet_address)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS products (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        address TEXT NOT NULL,
+        orchestrator_idx INTEGER NOT NULL,
+        creator TEXT NOT NULL REFERENCES creators (wallet_address) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        uri TEXT NOT NULL,
+        encrypted_key TEXT NOT NULL,
+        category TEXT NOT NULL,
+        price_per_call INTEGER NOT NULL CHECK (price_per_call >= 0),
+        description TEXT,
+        photo_url TEXT,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        UNIQUE (name, creator)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS project_products_enabled (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        project_id INTEGER NOT NULL REFERENCES projects (id) ON DELETE CASCADE,
+        product_id INTEGER NOT NULL REFERENCES products (id) ON DELETE CASCADE,
+        enabled_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        UNIQUE (project_id, product_id)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS faucet_requests (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        wallet_address TEXT NOT NULL REFERENCES accounts (wallet_address) ON DELETE CASCADE,
+        requested_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE TABLE
+    IF NOT EXISTS conversations (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        wallet_address TEXT NOT NULL REFERENCES accounts (wallet_address) ON DELETE CASCADE,
+        title TEXT,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP
+    );
+
+CREATE TABLE
+    IF NOT EXISTS agent_preview_messages (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        conversation_id INTEGER REFERENCES conversations (id) ON DELETE CASCADE,
+        sender TEXT NOT NULL CHECK (sender IN ('user', 'ai')),
+        message TEXT NOT NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE TABLE
+    IF NOT EXISTS waitlist (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        email TEXT NOT NULL UNIQUE,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+CREATE TABLE
+    IF NOT EXISTS audit_events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        org_id INTEGER,
+        actor TEXT NOT NULL,
+        action TEXT NOT NULL,
+        target_type TEXT NOT NULL,
+        target_id TEXT,
+        before_state TEXT,
+        after_state TEXT,
+        diff TEXT,
+        ip TEXT,
+        user_agent TEXT,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE INDEX IF NOT EXISTS idx_audit_events_org ON audit_events (org_id, id);
+
+CREATE TRIGGER IF NOT EXISTS audit_events_no_update BEFORE
+UPDATE ON audit_events BEGIN
+SELECT
+    RAISE (ABORT, 'audit_events is append-only');
+
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_events_no_delete BEFORE DELETE ON audit_events BEGIN
+SELECT
+    RAISE (ABORT, 'audit_events is append-only');
+
+END;
+
+CREATE TABLE
+    IF NOT EXISTS usage_records (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        org_id INTEGER NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+        project_id INTEGER REFERENCES projects (id) ON DELETE SET NULL,
+        wallet_address TEXT,
+        model TEXT NOT NULL,
+        cost INTEGER NOT NULL DEFAULT 0 CHECK (cost >= 0),
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE INDEX IF NOT EXISTS idx_usage_records_org ON usage_records (org_id, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_usage_records_project ON usage_records (project_id, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_usage_records_wallet ON usage_records (wallet_address, created_at);
+
+CREATE TABLE
+    IF NOT EXISTS rate_limits (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        org_id INTEGER NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+        scope TEXT NOT NULL CHECK (scope IN ('org', 'project', 'key')),
+        target TEXT NOT NULL DEFAULT '',
+        requests_per_minute INTEGER CHECK (requests_per_minute > 0),
+        max_concurrent INTEGER CHECK (max_concurrent > 0),
+        daily_spend_limit INTEGER CHECK (daily_spend_limit >= 0),
+        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        UNIQUE (org_id, scope, target)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS budgets (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        org_id INTEGER NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+        project_id INTEGER REFERENCES projects (id) ON DELETE CASCADE,
+        period TEXT NOT NULL CHECK (period IN ('monthly', 'total')),
+        hard_cap INTEGER CHECK (hard_cap >= 0),
+        soft_threshold INTEGER CHECK (soft_threshold >= 0),
+        alert_channel TEXT CHECK (alert_channel IN ('webhook', 'telegram', 'discord')),
+        alert_target TEXT,
+        last_alert_period TEXT,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_budgets_scope ON budgets (org_id, COALESCE(project_id, 0), period);
+
+CREATE TABLE
+    IF NOT EXISTS webhook_endpoints (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        project_id INTEGER NOT NULL REFERENCES projects (id) ON DELETE CASCADE,
+        url TEXT NOT NULL,
+        secret TEXT NOT NULL,
+        events TEXT NOT NULL DEFAULT '*',
+        active BOOLEAN NOT NULL DEFAULT TRUE,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE INDEX IF NOT EXISTS idx_webhook_endpoints_project ON webhook_endpoints (project_id);
+
+CREATE TABLE
+    IF NOT EXISTS webhook_deliveries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        endpoint_id INTEGER NOT NULL REFERENCES webhook_endpoints (id) ON DELETE CASCADE,
+        event TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'succeeded', 'failed')),
+        attempts INTEGER NOT NULL DEFAULT 0,
+        response_status INTEGER,
+        last_error TEXT,
+        replay_of INTEGER REFERENCES webhook_deliveries (id) ON DELETE SET NULL,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        delivered_at TIMESTAMP
+    );
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_endpoint ON webhook_deliveries (endpoint_id, id);
+
+CREATE TABLE
+    IF NOT EXISTS webhook_triggers (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        trigger_uid TEXT NOT NULL UNIQUE,
+        project_id INTEGER NOT NULL REFERENCES projects (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        secret TEXT NOT NULL,
+        prompt_template TEXT NOT NULL,
+        model TEXT NOT NULL,
+        callback_url TEXT,
+        active BOOLEAN NOT NULL DEFAULT TRUE,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    );
+
+CREATE INDEX IF NOT EXISTS idx_webhook_triggers_project ON webhook_triggers (project_id);
+
+CREATE TABLE
+    IF NOT EXISTS webhook_trigger_invocations (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        trigger_id INTEGER NOT NULL REFERENCES webhook_triggers (id) ON DELETE CASCADE,
+        idempotency_key TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'succeeded', 'failed')),
+        response TEXT,
+        error TEXT,
+        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        completed_at TIMESTAMP,
+        UNIQUE (trigger_id, idempotency_key)
+    );
@@ -0,0 +1,3 @@
+-- Links agent preview messages to the usage record of the call that produced them
+ALTER TABLE agent_preview_messages
+ADD COLUMN usage_id INTEGER REFERENCES usage_records (id) ON DELETE SET NULL;
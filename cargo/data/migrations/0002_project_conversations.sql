@@ -0,0 +1,84 @@
+-- Conversations are owned by a project and optionally an end-user of that project
+ALTER TABLE conversations
+ADD COLUMN project_id INTEGER REFERENCES projects (id) ON DELETE CASCADE;
+
+ALTER TABLE conversations
+ADD COLUMN end_user_id TEXT;
+
+-- Existing conversations move to the first project their owner has access to
+UPDATE conversations
+SET
+    project_id = (
+        SELECT
+            p.id
+        FROM
+            projects p
+            JOIN organizations o ON o.id = p.org_id
+        WHERE
+            lower(o.owner) = lower(conversations.wallet_address)
+            OR EXISTS (
+                SELECT
+                    1
+                FROM
+                    org_members om
+                WHERE
+                    om.org_id = o.id
+                    AND om.wallet_address = lower(conversations.wallet_address)
+            )
+            OR EXISTS (
+                SELECT
+                    1
+                FROM
+                    project_members pm
+                WHERE
+                    pm.project_id = p.id
+                    AND pm.wallet_address = lower(conversations.wallet_address)
+            )
+        ORDER BY
+            p.id
+        LIMIT
+            1
+    );
+
+UPDATE conversations
+SET
+    updated_at = COALESCE(
+        (
+            SELECT
+                MAX(m.created_at)
+            FROM
+                agent_preview_messages m
+            WHERE
+                m.conversation_id = conversations.id
+        ),
+        created_at,
+        CURRENT_TIMESTAMP
+    )
+WHERE
+    updated_at IS NULL;
+
+DELETE FROM agent_preview_messages
+WHERE
+    conversation_id IS NULL
+    OR conversation_id NOT IN (
+        SELECT
+            id
+        FROM
+            conversations
+    );
+
+-- Never written to, agent preview messages always belonged to `conversations`
+DROP TABLE IF EXISTS agent_preview_conversations;
+
+CREATE INDEX IF NOT EXISTS idx_conversations_project ON conversations (project_id, wallet_address, updated_at);
+
+CREATE INDEX IF NOT EXISTS idx_agent_preview_messages_conversation ON agent_preview_messages (conversation_id, id);
+
+CREATE TRIGGER IF NOT EXISTS conversations_touch_on_message AFTER INSERT ON agent_preview_messages BEGIN
+UPDATE conversations
+SET
+    updated_at = CURRENT_TIMESTAMP
+WHERE
+    id = NEW.conversation_id;
+
+END;
@@ -0,0 +1,28 @@
+-- Full-text index over agent preview message content
+CREATE VIRTUAL TABLE IF NOT EXISTS agent_preview_messages_fts USING fts5 (
+    message,
+    content = 'agent_preview_messages',
+    content_rowid = 'id'
+);
+
+INSERT INTO agent_preview_messages_fts (agent_preview_messages_fts) VALUES ('rebuild');
+
+CREATE TRIGGER IF NOT EXISTS agent_preview_messages_fts_insert AFTER INSERT ON agent_preview_messages BEGIN
+INSERT INTO agent_preview_messages_fts (rowid, message) VALUES (NEW.id, NEW.message);
+
+END;
+
+CREATE TRIGGER IF NOT EXISTS agent_preview_messages_fts_delete AFTER DELETE ON agent_preview_messages BEGIN
+INSERT INTO agent_preview_messages_fts (agent_preview_messages_fts, rowid, message) VALUES ('delete', OLD.id, OLD.message);
+
+END;
+
+CREATE TRIGGER IF NOT EXISTS agent_preview_messages_fts_update AFTER
+UPDATE OF message ON agent_preview_messages BEGIN
+INSERT INTO agent_preview_messages_fts (agent_preview_messages_fts, rowid, message) VALUES ('delete', OLD.id, OLD.message);
+
+INSERT INTO agent_preview_messages_fts (rowid, message) VALUES (NEW.id, NEW.message);
+
+END;
+
+CREATE INDEX IF NOT EXISTS idx_conversations_created ON conversations (project_id, wallet_address, created_at);
@@ -0,0 +1,32 @@
+-- Messages form a tree per conversation, with one branch selected as active
+ALTER TABLE agent_preview_messages
+ADD COLUMN parent_id INTEGER REFERENCES agent_preview_messages (id) ON DELETE CASCADE;
+
+ALTER TABLE conversations
+ADD COLUMN active_leaf_id INTEGER REFERENCES agent_preview_messages (id) ON DELETE SET NULL;
+
+-- Existing flat histories become a single branch
+UPDATE agent_preview_messages
+SET
+    parent_id = (
+        SELECT
+            MAX(prev.id)
+        FROM
+            agent_preview_messages prev
+        WHERE
+            prev.conversation_id = agent_preview_messages.conversation_id
+            AND prev.id < agent_preview_messages.id
+    );
+
+UPDATE conversations
+SET
+    active_leaf_id = (
+        SELECT
+            MAX(m.id)
+        FROM
+            agent_preview_messages m
+        WHERE
+            m.conversation_id = conversations.id
+    );
+
+CREATE INDEX IF NOT EXISTS idx_agent_preview_messages_parent ON agent_preview_messages (parent_id);
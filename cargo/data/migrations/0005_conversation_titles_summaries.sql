@@ -0,0 +1,23 @@
+-- Per-project opt-in, both make extra billed model calls
+ALTER TABLE projects
+ADD COLUMN auto_title_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE projects
+ADD COLUMN summary_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE conversations
+ADD COLUMN title_source TEXT NOT NULL DEFAULT 'default' CHECK (title_source IN ('default', 'generated', 'user'));
+
+UPDATE conversations
+SET
+    title_source = 'user'
+WHERE
+    title IS NOT NULL
+    AND title NOT LIKE 'New Conversation %';
+
+-- Rolling summary of the active branch up to and including `summary_until_id`
+ALTER TABLE conversations
+ADD COLUMN summary TEXT;
+
+ALTER TABLE conversations
+ADD COLUMN summary_until_id INTEGER REFERENCES agent_preview_messages (id) ON DELETE SET NULL;
@@ -0,0 +1,9 @@
+-- Retries per model before falling back, waiting retry_backoff_ms doubled each time
+ALTER TABLE projects
+ADD COLUMN max_retries INTEGER NOT NULL DEFAULT 1;
+
+ALTER TABLE projects
+ADD COLUMN retry_backoff_ms INTEGER NOT NULL DEFAULT 500;
+
+ALTER TABLE projects
+ADD COLUMN routing_strategy TEXT NOT NULL DEFAULT 'ordered' CHECK (routing_strategy IN ('ordered', 'cost', 'latency'));
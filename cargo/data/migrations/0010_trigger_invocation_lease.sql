@@ -0,0 +1,9 @@
+-- Pending invocations are leased from claimed_at, charged ones are never run again
+ALTER TABLE webhook_trigger_invocations
+ADD COLUMN claimed_at TIMESTAMP;
+
+ALTER TABLE webhook_trigger_invocations
+ADD COLUMN charged BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE webhook_trigger_invocations
+ADD COLUMN usage_id INTEGER REFERENCES usage_records (id) ON DELETE SET NULL;
@@ -0,0 +1,5 @@
+-- Pending deliveries are retried from next_attempt_at, so retries survive restarts
+ALTER TABLE webhook_deliveries
+ADD COLUMN next_attempt_at TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_pending ON webhook_deliveries (status, next_attempt_at);
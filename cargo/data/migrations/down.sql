@@ -1,28 +1,50 @@
--- Drop tables in reverse order to handle foreign key dependencies
-DROP TABLE IF EXISTS agent_preview_messages;
-
-DROP TABLE IF EXISTS agent_preview_conversations;
-
-DROP TABLE IF EXISTS conversations;
-
-DROP TABLE IF EXISTS faucet_requests;
-
-DROP TABLE IF EXISTS project_products_enabled;
-
-DROP TABLE IF EXISTS products;
-
-DROP TABLE IF EXISTS creators;
-
-DROP TABLE IF EXISTS org_model_enrollments;
-
-DROP TABLE IF EXISTS project_members;
-
-DROP TABLE IF EXISTS projects;
-
-DROP TABLE IF EXISTS org_members;
-
-DROP TABLE IF EXISTS organizations;
-
-DROP TABLE IF EXISTS sessions;
-
+-- Drop tables in reverse order to handle foreign key dependencies
+DROP TABLE IF EXISTS agent_preview_messages_fts;
+
+DROP TABLE IF EXISTS schema_migrations;
+
+DROP TABLE IF EXISTS knowledge_chunks;
+
+DROP TABLE IF EXISTS webhook_trigger_invocations;
+
+DROP TABLE IF EXISTS webhook_triggers;
+
+DROP TABLE IF EXISTS webhook_deliveries;
+
+DROP TABLE IF EXISTS webhook_endpoints;
+
+DROP TABLE IF EXISTS budgets;
+
+DROP TABLE IF EXISTS rate_limits;
+
+DROP TABLE IF EXISTS usage_records;
+
+DROP TABLE IF EXISTS audit_events;
+
+DROP TABLE IF EXISTS agent_preview_messages;
+
+DROP TABLE IF EXISTS agent_preview_conversations;
+
+DROP TABLE IF EXISTS conversations;
+
+DROP TABLE IF EXISTS faucet_requests;
+
+DROP TABLE IF EXISTS project_products_enabled;
+
+DROP TABLE IF EXISTS products;
+
+DROP TABLE IF EXISTS creators;
+
+DROP TABLE IF EXISTS org_model_enrollments;
+
+DROP TABLE IF EXISTS project_members;
+
+DROP TABLE IF EXISTS projects;
+
+DROP TABLE IF EXISTS org_members;
+
+DROP TABLE IF EXISTS organizations;
+
+DROP TABLE IF EXISTS sessions;
+
 DROP TABLE IF EXISTS accounts;
\ No newline at end of file
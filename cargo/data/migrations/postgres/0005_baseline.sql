@@ -0,0 +1,319 @@
+-- POSTGRES MIGRATION SCRIPT --
+-- Same schema as the SQLite migrations up to 0005. Timestamps are stored as
+-- 'YYYY-MM-DD HH:MM:SS' UTC text so both backends decode and compare them alike.
+CREATE OR REPLACE FUNCTION utc_now () RETURNS TEXT AS $$
+    SELECT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS');
+$$ LANGUAGE SQL STABLE;
+
+CREATE TABLE
+    IF NOT EXISTS accounts (
+        wallet_address TEXT PRIMARY KEY,
+        created_at TEXT DEFAULT utc_now (),
+        api_key_last_issued_at TEXT
+    );
+
+CREATE TABLE
+    IF NOT EXISTS sessions (
+        wallet_address TEXT PRIMARY KEY,
+        token TEXT,
+        ip TEXT,
+        user_agent TEXT,
+        created_at TEXT DEFAULT utc_now ()
+    );
+
+CREATE TABLE
+    IF NOT EXISTS organizations (
+        id BIGSERIAL PRIMARY KEY,
+        organization_uid TEXT NOT NULL,
+        address TEXT NOT NULL,
+        orchestrator_idx BIGINT NOT NULL,
+        name TEXT NOT NULL,
+        owner TEXT NOT NULL REFERENCES accounts (wallet_address) ON DELETE CASCADE,
+        created_at TEXT DEFAULT utc_now (),
+        expenditure BIGINT NOT NULL DEFAULT 0 CHECK (expenditure >= 0),
+        UNIQUE (name),
+        UNIQUE (organization_uid),
+        UNIQUE (address)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS org_members (
+        org_id BIGINT NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+        wallet_address TEXT NOT NULL REFERENCES accounts (wallet_address) ON DELETE CASCADE,
+        role TEXT NOT NULL CHECK (role IN ('admin', 'member')),
+        created_at TEXT DEFAULT utc_now (),
+        PRIMARY KEY (org_id, wallet_address)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS projects (
+        id BIGSERIAL PRIMARY KEY,
+        project_uid TEXT NOT NULL,
+        org_id BIGINT NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        created_at TEXT DEFAULT utc_now (),
+        search_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+        memory_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+        teloxide_token TEXT,
+        discord_token TEXT,
+        default_model_id BIGINT,
+        auto_title_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+        summary_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+        UNIQUE (name, org_id),
+        UNIQUE (project_uid)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS project_members (
+        project_id BIGINT NOT NULL REFERENCES projects (id) ON DELETE CASCADE,
+        wallet_address TEXT NOT NULL REFERENCES accounts (wallet_address) ON DELETE CASCADE,
+        role TEXT NOT NULL CHECK (role IN ('admin', 'developer', 'viewer')),
+        PRIMARY KEY (project_id, wallet_address)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS org_model_enrollments (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        org_id BIGINT NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+        model_id BIGINT NOT NULL,
+        enabled_at TEXT DEFAULT utc_now (),
+        UNIQUE (org_id, model_id)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS creators (
+        wallet_address TEXT PRIMARY KEY REFERENCES accounts (wallet_address),
+        uri TEXT NOT NULL,
+        pvt_key_seed TEXT NOT NULL,
+        pub_key TEXT NOT NULL,
+        created_at TEXT DEFAULT utc_now ()
+    );
+
+CREATE TABLE
+    IF NOT EXISTS products (
+        id BIGSERIAL PRIMARY KEY,
+        address TEXT NOT NULL,
+        orchestrator_idx BIGINT NOT NULL,
+        creator TEXT NOT NULL REFERENCES creators (wallet_address) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        uri TEXT NOT NULL,
+        encrypted_key TEXT NOT NULL,
+        category TEXT NOT NULL,
+        price_per_call BIGINT NOT NULL CHECK (price_per_call >= 0),
+        description TEXT,
+        photo_url TEXT,
+        created_at TEXT DEFAULT utc_now (),
+        UNIQUE (name, creator)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS project_products_enabled (
+        id BIGSERIAL PRIMARY KEY,
+        project_id BIGINT NOT NULL REFERENCES projects (id) ON DELETE CASCADE,
+        product_id BIGINT NOT NULL REFERENCES products (id) ON DELETE CASCADE,
+        enabled_at TEXT DEFAULT utc_now (),
+        UNIQUE (project_id, product_id)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS faucet_requests (
+        id BIGSERIAL PRIMARY KEY,
+        wallet_address TEXT NOT NULL REFERENCES accounts (wallet_address) ON DELETE CASCADE,
+        requested_at TEXT DEFAULT utc_now ()
+    );
+
+-- Conversation and message ids are decoded as 32-bit integers
+CREATE TABLE
+    IF NOT EXISTS conversations (
+        id SERIAL PRIMARY KEY,
+        wallet_address TEXT NOT NULL REFERENCES accounts (wallet_address) ON DELETE CASCADE,
+        title TEXT,
+        created_at TEXT DEFAULT utc_now (),
+        updated_at TEXT,
+        project_id BIGINT REFERENCES projects (id) ON DELETE CASCADE,
+        end_user_id TEXT,
+        title_source TEXT NOT NULL DEFAULT 'default' CHECK (title_source IN ('default', 'generated', 'user')),
+        summary TEXT
+    );
+
+CREATE TABLE
+    IF NOT EXISTS waitlist (
+        id BIGSERIAL PRIMARY KEY,
+        email TEXT NOT NULL UNIQUE,
+        created_at TEXT DEFAULT utc_now ()
+    );
+
+CREATE TABLE
+    IF NOT EXISTS audit_events (
+        id BIGSERIAL PRIMARY KEY,
+        org_id BIGINT,
+        actor TEXT NOT NULL,
+        action TEXT NOT NULL,
+        target_type TEXT NOT NULL,
+        target_id TEXT,
+        before_state TEXT,
+        after_state TEXT,
+        diff TEXT,
+        ip TEXT,
+        user_agent TEXT,
+        created_at TEXT DEFAULT utc_now ()
+    );
+
+CREATE INDEX IF NOT EXISTS idx_audit_events_org ON audit_events (org_id, id);
+
+CREATE OR REPLACE FUNCTION audit_events_append_only () RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER audit_events_no_update BEFORE
+UPDATE ON audit_events FOR EACH ROW
+EXECUTE FUNCTION audit_events_append_only ();
+
+CREATE OR REPLACE TRIGGER audit_events_no_delete BEFORE DELETE ON audit_events FOR EACH ROW
+EXECUTE FUNCTION audit_events_append_only ();
+
+CREATE TABLE
+    IF NOT EXISTS usage_records (
+        id BIGSERIAL PRIMARY KEY,
+        org_id BIGINT NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+        project_id BIGINT REFERENCES projects (id) ON DELETE SET NULL,
+        wallet_address TEXT,
+        model TEXT NOT NULL,
+        cost BIGINT NOT NULL DEFAULT 0 CHECK (cost >= 0),
+        created_at TEXT DEFAULT utc_now ()
+    );
+
+CREATE INDEX IF NOT EXISTS idx_usage_records_org ON usage_records (org_id, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_usage_records_project ON usage_records (project_id, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_usage_records_wallet ON usage_records (wallet_address, created_at);
+
+CREATE TABLE
+    IF NOT EXISTS agent_preview_messages (
+        id SERIAL PRIMARY KEY,
+        conversation_id INTEGER REFERENCES conversations (id) ON DELETE CASCADE,
+        sender TEXT NOT NULL CHECK (sender IN ('user', 'ai')),
+        message TEXT NOT NULL,
+        created_at TEXT DEFAULT utc_now (),
+        usage_id BIGINT REFERENCES usage_records (id) ON DELETE SET NULL,
+        parent_id INTEGER REFERENCES agent_preview_messages (id) ON DELETE CASCADE
+    );
+
+ALTER TABLE conversations
+ADD COLUMN IF NOT EXISTS active_leaf_id INTEGER REFERENCES agent_preview_messages (id) ON DELETE SET NULL;
+
+ALTER TABLE conversations
+ADD COLUMN IF NOT EXISTS summary_until_id INTEGER REFERENCES agent_preview_messages (id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_conversations_project ON conversations (project_id, wallet_address, updated_at);
+
+CREATE INDEX IF NOT EXISTS idx_conversations_created ON conversations (project_id, wallet_address, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_agent_preview_messages_conversation ON agent_preview_messages (conversation_id, id);
+
+CREATE INDEX IF NOT EXISTS idx_agent_preview_messages_parent ON agent_preview_messages (parent_id);
+
+-- Message search uses to_tsvector instead of the SQLite FTS5 table
+CREATE INDEX IF NOT EXISTS idx_agent_preview_messages_search ON agent_preview_messages USING GIN (to_tsvector('simple', message));
+
+CREATE OR REPLACE FUNCTION conversations_touch_on_message () RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE conversations SET updated_at = utc_now () WHERE id = NEW.conversation_id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER conversations_touch_on_message AFTER INSERT ON agent_preview_messages FOR EACH ROW
+EXECUTE FUNCTION conversations_touch_on_message ();
+
+CREATE TABLE
+    IF NOT EXISTS rate_limits (
+        id BIGSERIAL PRIMARY KEY,
+        org_id BIGINT NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+        scope TEXT NOT NULL CHECK (scope IN ('org', 'project', 'key')),
+        target TEXT NOT NULL DEFAULT '',
+        requests_per_minute BIGINT CHECK (requests_per_minute > 0),
+        max_concurrent BIGINT CHECK (max_concurrent > 0),
+        daily_spend_limit BIGINT CHECK (daily_spend_limit >= 0),
+        updated_at TEXT DEFAULT utc_now (),
+        UNIQUE (org_id, scope, target)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS budgets (
+        id BIGSERIAL PRIMARY KEY,
+        org_id BIGINT NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+        project_id BIGINT REFERENCES projects (id) ON DELETE CASCADE,
+        period TEXT NOT NULL CHECK (period IN ('monthly', 'total')),
+        hard_cap BIGINT CHECK (hard_cap >= 0),
+        soft_threshold BIGINT CHECK (soft_threshold >= 0),
+        alert_channel TEXT CHECK (alert_channel IN ('webhook', 'telegram', 'discord')),
+        alert_target TEXT,
+        last_alert_period TEXT,
+        created_at TEXT DEFAULT utc_now (),
+        updated_at TEXT DEFAULT utc_now ()
+    );
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_budgets_scope ON budgets (org_id, COALESCE(project_id, 0), period);
+
+CREATE TABLE
+    IF NOT EXISTS webhook_endpoints (
+        id BIGSERIAL PRIMARY KEY,
+        project_id BIGINT NOT NULL REFERENCES projects (id) ON DELETE CASCADE,
+        url TEXT NOT NULL,
+        secret TEXT NOT NULL,
+        events TEXT NOT NULL DEFAULT '*',
+        active BOOLEAN NOT NULL DEFAULT TRUE,
+        created_at TEXT DEFAULT utc_now ()
+    );
+
+CREATE INDEX IF NOT EXISTS idx_webhook_endpoints_project ON webhook_endpoints (project_id);
+
+CREATE TABLE
+    IF NOT EXISTS webhook_deliveries (
+        id BIGSERIAL PRIMARY KEY,
+        endpoint_id BIGINT NOT NULL REFERENCES webhook_endpoints (id) ON DELETE CASCADE,
+        event TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'succeeded', 'failed')),
+        attempts BIGINT NOT NULL DEFAULT 0,
+        response_status BIGINT,
+        last_error TEXT,
+        replay_of BIGINT REFERENCES webhook_deliveries (id) ON DELETE SET NULL,
+        created_at TEXT DEFAULT utc_now (),
+        delivered_at TEXT
+    );
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_endpoint ON webhook_deliveries (endpoint_id, id);
+
+CREATE TABLE
+    IF NOT EXISTS webhook_triggers (
+        id BIGSERIAL PRIMARY KEY,
+        trigger_uid TEXT NOT NULL UNIQUE,
+        project_id BIGINT NOT NULL REFERENCES projects (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        secret TEXT NOT NULL,
+        prompt_template TEXT NOT NULL,
+        model TEXT NOT NULL,
+        callback_url TEXT,
+        active BOOLEAN NOT NULL DEFAULT TRUE,
+        created_at TEXT DEFAULT utc_now ()
+    );
+
+CREATE INDEX IF NOT EXISTS idx_webhook_triggers_project ON webhook_triggers (project_id);
+
+CREATE TABLE
+    IF NOT EXISTS webhook_trigger_invocations (
+        id BIGSERIAL PRIMARY KEY,
+        trigger_id BIGINT NOT NULL REFERENCES webhook_triggers (id) ON DELETE CASCADE,
+        idempotency_key TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'succeeded', 'failed')),
+        response TEXT,
+        error TEXT,
+        created_at TEXT DEFAULT utc_now (),
+        completed_at TEXT,
+        UNIQUE (trigger_id, idempotency_key)
+    );
@@ -0,0 +1,13 @@
+-- Chunked product knowledge and its embeddings, rebuilt when the text's hash changes
+CREATE TABLE IF NOT EXISTS knowledge_chunks (
+    id BIGSERIAL PRIMARY KEY,
+    product_id BIGINT NOT NULL REFERENCES products (id) ON DELETE CASCADE,
+    embedder TEXT NOT NULL,
+    content_hash TEXT NOT NULL,
+    chunk_index BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    -- JSON array of floats
+    embedding TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT utc_now(),
+    UNIQUE (product_id, embedder, chunk_index)
+);
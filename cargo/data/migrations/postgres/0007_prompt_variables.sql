@@ -0,0 +1,3 @@
+-- JSON object of values for `{{variable}}` placeholders in promptset products
+ALTER TABLE projects
+ADD COLUMN prompt_variables TEXT NOT NULL DEFAULT '{}';
@@ -0,0 +1,23 @@
+-- Per-project agent configuration, unset columns keep the platform defaults
+ALTER TABLE projects
+ADD COLUMN system_prompt TEXT;
+
+ALTER TABLE projects
+ADD COLUMN persona_name TEXT;
+
+ALTER TABLE projects
+ADD COLUMN max_tokens BIGINT;
+
+ALTER TABLE projects
+ADD COLUMN min_temperature REAL;
+
+ALTER TABLE projects
+ADD COLUMN max_temperature REAL;
+
+-- JSON array of strings
+ALTER TABLE projects
+ADD COLUMN stop_sequences TEXT NOT NULL DEFAULT '[]';
+
+-- JSON array of model ids, tried in order when the requested model fails
+ALTER TABLE projects
+ADD COLUMN fallback_model_ids TEXT NOT NULL DEFAULT '[]';
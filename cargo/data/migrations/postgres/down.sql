@@ -0,0 +1,52 @@
+-- Drop every table, CASCADE covers the conversations <-> messages references
+DROP TABLE IF EXISTS schema_migrations CASCADE;
+
+DROP TABLE IF EXISTS knowledge_chunks CASCADE;
+
+DROP TABLE IF EXISTS webhook_trigger_invocations CASCADE;
+
+DROP TABLE IF EXISTS webhook_triggers CASCADE;
+
+DROP TABLE IF EXISTS webhook_deliveries CASCADE;
+
+DROP TABLE IF EXISTS webhook_endpoints CASCADE;
+
+DROP TABLE IF EXISTS budgets CASCADE;
+
+DROP TABLE IF EXISTS rate_limits CASCADE;
+
+DROP TABLE IF EXISTS usage_records CASCADE;
+
+DROP TABLE IF EXISTS audit_events CASCADE;
+
+DROP TABLE IF EXISTS agent_preview_messages CASCADE;
+
+DROP TABLE IF EXISTS conversations CASCADE;
+
+DROP TABLE IF EXISTS faucet_requests CASCADE;
+
+DROP TABLE IF EXISTS project_products_enabled CASCADE;
+
+DROP TABLE IF EXISTS products CASCADE;
+
+DROP TABLE IF EXISTS creators CASCADE;
+
+DROP TABLE IF EXISTS org_model_enrollments CASCADE;
+
+DROP TABLE IF EXISTS project_members CASCADE;
+
+DROP TABLE IF EXISTS projects CASCADE;
+
+DROP TABLE IF EXISTS org_members CASCADE;
+
+DROP TABLE IF EXISTS organizations CASCADE;
+
+DROP TABLE IF EXISTS sessions CASCADE;
+
+DROP TABLE IF EXISTS accounts CASCADE;
+
+DROP FUNCTION IF EXISTS conversations_touch_on_message;
+
+DROP FUNCTION IF EXISTS audit_events_append_only;
+
+DROP FUNCTION IF EXISTS utc_now;